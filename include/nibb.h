@@ -1,4 +1,4 @@
-/* Generated with cbindgen:0.29.0 */
+/* Generated with cbindgen:0.29.4 */
 
 #include <stdarg.h>
 #include <stdbool.h>
@@ -6,10 +6,20 @@
 #include <stdlib.h>
 
 /**
- * Loads a snippet by name and returns its JSON representation.
+ * Callback receiving snippet change events from [`watch_ffi`].
+ *
+ * `event_json` is a JSON-encoded `SnippetEvent`, e.g. `{"event": "modified", "slug": "<slug>"}`.
+ * It is only valid during the call and must not be freed. `user_data` is the pointer given to [`watch_ffi`].
+ * May be null, which [`watch_ffi`] refuses.
+ */
+typedef void (*SnippetEventCallback)(const char *event_json, void *user_data);
+
+/**
+ * Loads a snippet by name or ID and returns its JSON representation.
  *
  * # Arguments
- * - `name`: A null-terminated C string representing the name of the snippet.
+ * - `name`: A null-terminated C string representing the name or the ID of the snippet.
+ *   Snippets in a namespace are loaded by their path, e.g. `rust/serde/untagged-enum`.
  *
  * # Returns
  * A newly allocated C string (`*mut c_char`) containing the snippet's JSON representation.
@@ -37,6 +47,26 @@ char *load_snippet_ffi(const char *name);
  */
 bool save_snippet_ffi(const char *snippet_json);
 
+/**
+ * Saves a single snippet from its JSON representation, unless it was changed since it was loaded.
+ *
+ * # Arguments
+ * - `snippet_json`: A null-terminated C string containing a JSON-encoded snippet.
+ * - `expected_json`: A null-terminated C string containing the version the caller loaded,
+ *   either `{"modified": "<timestamp>"}` or `{"hash": "<version hash>"}`.
+ *
+ * # Returns
+ * - A JSON string of the saved snippet, with its new `modified` timestamp.
+ * - A JSON error object otherwise. If the stored snippet is not the expected version, it is of
+ *   type `Conflict`, with the `slug`, the `current` version (`null` if deleted) and the `attempted` one.
+ *
+ * # Safety
+ * - `snippet_json` and `expected_json` must be valid, null-terminated UTF-8 strings.
+ * - The returned pointer must be freed with `free_string_ffi`.
+ */
+char *save_snippet_if_unmodified_ffi(const char *snippet_json,
+                                     const char *expected_json);
+
 /**
  * Deletes a snippet from the repo
  *
@@ -52,11 +82,114 @@ bool save_snippet_ffi(const char *snippet_json);
  */
 bool delete_snippet_ffi(const char *name);
 
+/**
+ * Renames a snippet, moving it to the slug of its new name.
+ *
+ * # Arguments
+ * - `name`: A null-terminated C string of the snippet's current name or slug.
+ * - `new_name`: A null-terminated C string of the new name. A name with a namespace path,
+ *   e.g. `rust/serde/Untagged enum`, moves the snippet into that namespace.
+ *
+ * # Returns
+ * A newly allocated C string (`*mut c_char`).
+ * - On success: JSON-encoded renamed `Snippet` (must be freed with `free_string_ffi`).
+ * - On failure: JSON-encoded error object (must also be freed).
+ *   If another snippet already uses the new slug, the error type is `InvalidSlug`.
+ *
+ * # Safety
+ * - `name` and `new_name` must be valid, null-terminated UTF-8 strings.
+ * - Caller is responsible for freeing the returned string using `free_string_ffi`.
+ */
+char *rename_snippet_ffi(const char *name, const char *new_name);
+
+/**
+ * Lists the deleted snippets in the trash, oldest deletion first.
+ *
+ * # Returns
+ * A newly allocated C string (`*mut c_char`).
+ * - On success: JSON array of entries, e.g.
+ *   `[{"id": "20250101T120000.000000000Z", "slug": "<slug>", "deleted": "<RFC 3339 timestamp>"}]`
+ *   (must be freed with `free_string_ffi`).
+ * - On failure: JSON-encoded error object (must also be freed).
+ *
+ * # Safety
+ * - Caller is responsible for freeing the returned string using `free_string_ffi`.
+ */
+char *list_trash_ffi(void);
+
+/**
+ * Restores the most recently deleted snippet with the given name from the trash.
+ *
+ * # Arguments
+ * - `name`: A null-terminated C string of the deleted snippet's name or slug.
+ *
+ * # Returns
+ * A newly allocated C string (`*mut c_char`).
+ * - On success: JSON-encoded restored `Snippet` (must be freed with `free_string_ffi`).
+ * - On failure: JSON-encoded error object (must also be freed).
+ *   If another snippet uses the slug by now, the error type is `InvalidSlug`.
+ *
+ * # Safety
+ * - `name` must be a valid, null-terminated UTF-8 string.
+ * - Caller is responsible for freeing the returned string using `free_string_ffi`.
+ */
+char *restore_trash_ffi(const char *name);
+
+/**
+ * Permanently removes deleted snippets from the trash.
+ *
+ * # Arguments
+ * - `older_than_secs`: Only remove snippets deleted at least this many seconds ago.
+ *   `0` removes everything.
+ *
+ * # Returns
+ * - `true` if the trash was emptied successfully.
+ * - `false` if an error occurred.
+ */
+bool empty_trash_ffi(uint64_t older_than_secs);
+
+/**
+ * Starts watching the snippets for changes, calling `callback` once per change.
+ *
+ * # Arguments
+ * - `callback`: Function called with each event. Called from a background thread.
+ * - `user_data`: Pointer passed to every call of `callback`, may be null.
+ * - `debounce_ms`: Milliseconds to wait for further changes before reporting them.
+ *
+ * # Returns
+ * - A handle to pass to [`unwatch_ffi`], never `0` on success.
+ * - `0` if `callback` is null or the repository cannot be watched.
+ *
+ * # Safety
+ * - `callback` must be safe to call from another thread until [`unwatch_ffi`] returned.
+ * - `user_data` must stay valid until [`unwatch_ffi`] returned.
+ */
+uint64_t watch_ffi(SnippetEventCallback callback, void *user_data, uint64_t debounce_ms);
+
+/**
+ * Stops a watcher started with [`watch_ffi`].
+ *
+ * Once this returns, the watcher's callback is not called anymore.
+ *
+ * # Arguments
+ * - `handle`: The handle returned by [`watch_ffi`].
+ *
+ * # Returns
+ * - `true` if the watcher was stopped.
+ * - `false` if `handle` is unknown.
+ *
+ * # Safety
+ * - Must not be called from within the watcher's own callback.
+ */
+bool unwatch_ffi(uint64_t handle);
+
 /**
  * Loads all snippets from the repository and returns them as a JSON array.
  *
+ * Broken snippets are skipped, use [`load_all_report_ffi`] to find out which ones.
+ *
  * # Returns
- * A newly allocated C string (`*mut c_char`) containing the JSON array of all snippets.
+ * A newly allocated C string (`*mut c_char`) containing the JSON array of all loadable snippets.
  * - On success: JSON array of snippets (must be freed with `free_string_ffi`).
  * - On failure: JSON-encoded error object (must also be freed).
  *
@@ -65,6 +198,119 @@ bool delete_snippet_ffi(const char *name);
  */
 char *load_all_ffi(void);
 
+/**
+ * Lists the metadata of a page of snippets, ordered by slug, without reading their content.
+ *
+ * Snippets whose metadata cannot be read are left out of the pages and reported as diagnostics.
+ *
+ * # Arguments
+ * - `offset`: Number of snippets to skip.
+ * - `limit`: Maximum number of snippets to return. `0` returns all remaining ones.
+ *
+ * # Returns
+ * A newly allocated C string (`*mut c_char`).
+ * - On success: JSON object `{"total": <number of snippets>, "entries": [{"slug": "<slug>", "meta": { ... }}],
+ *   "diagnostics": [{"slug": "<slug>", "message": "<reason>"}]}` (must be freed with `free_string_ffi`).
+ * - On failure: JSON-encoded error object (must also be freed).
+ *
+ * # Safety
+ * - Caller is responsible for freeing the returned string using `free_string_ffi`.
+ */
+char *list_meta_ffi(uint64_t offset,
+                    uint64_t limit);
+
+/**
+ * Loads a page of snippets, ordered by slug.
+ *
+ * Only the snippets of the page are read. Pages are counted like in [`list_meta_ffi`],
+ * snippets of the page that cannot be loaded are left out of it and reported as diagnostics.
+ *
+ * # Arguments
+ * - `offset`: Number of snippets to skip.
+ * - `limit`: Maximum number of snippets to return. `0` returns all remaining ones.
+ *
+ * # Returns
+ * A newly allocated C string (`*mut c_char`).
+ * - On success: JSON object `{"total": <number of snippets>, "snippets": [ ... ],
+ *   "diagnostics": [{"slug": "<slug>", "message": "<reason>"}]}` (must be freed with `free_string_ffi`).
+ * - On failure: JSON-encoded error object (must also be freed).
+ *
+ * # Safety
+ * - Caller is responsible for freeing the returned string using `free_string_ffi`.
+ */
+char *load_page_ffi(uint64_t offset,
+                    uint64_t limit);
+
+/**
+ * Loads the snippets filed under a namespace and returns them as a JSON array.
+ *
+ * Broken snippets are skipped, like in [`load_all_ffi`].
+ *
+ * # Arguments
+ * - `namespace`: A null-terminated C string with the namespace, e.g. `rust/serde`.
+ *   Snippets in its sub-namespaces are included, an empty string matches every snippet.
+ *
+ * # Returns
+ * A newly allocated C string (`*mut c_char`) containing the JSON array of matching snippets.
+ * - On failure: JSON-encoded error object (must also be freed).
+ *
+ * # Safety
+ * - `namespace` must be a valid, null-terminated UTF-8 string.
+ * - Caller is responsible for freeing the returned string using `free_string_ffi`.
+ */
+char *load_namespace_ffi(const char *namespace_);
+
+/**
+ * Lists the snippets linked to or from a snippet, for showing them next to it.
+ *
+ * # Arguments
+ * - `name`: A null-terminated C string of the snippet's name, slug or ID.
+ *
+ * # Returns
+ * A newly allocated C string (`*mut c_char`).
+ * - On success: JSON array of relations, e.g.
+ *   `[{"kind": "see_also", "direction": "outgoing", "meta": { ... }}]`
+ *   (must be freed with `free_string_ffi`).
+ * - On failure: JSON-encoded error object (must also be freed).
+ *
+ * # Safety
+ * - `name` must be a valid, null-terminated UTF-8 string.
+ * - Caller is responsible for freeing the returned string using `free_string_ffi`.
+ */
+char *related_snippets_ffi(const char *name);
+
+/**
+ * Lists the links whose target does not exist, e.g. because it was deleted.
+ *
+ * # Returns
+ * A newly allocated C string (`*mut c_char`).
+ * - On success: JSON array, e.g. `[{"slug": "<slug>", "link": {"kind": "depends_on", "target": "<slug or ID>"}}]`
+ *   (must be freed with `free_string_ffi`).
+ * - On failure: JSON-encoded error object (must also be freed).
+ *
+ * # Safety
+ * - Caller is responsible for freeing the returned string using `free_string_ffi`.
+ */
+char *dangling_links_ffi(void);
+
+/**
+ * Loads all snippets from the repository, together with diagnostics for the ones that are broken.
+ *
+ * # Returns
+ * A newly allocated C string (`*mut c_char`) containing a JSON object:
+ *   ```json
+ *   {
+ *     "snippets": [ ... ],
+ *     "diagnostics": [ { "slug": "<slug>", "message": "<what is wrong>" } ]
+ *   }
+ *   ```
+ * - On failure: JSON-encoded error object (must also be freed).
+ *
+ * # Safety
+ * - Caller is responsible for freeing the returned string using `free_string_ffi`.
+ */
+char *load_all_report_ffi(void);
+
 /**
  * Saves a list of snippets from a JSON array.
  *
@@ -73,7 +319,7 @@ char *load_all_ffi(void);
  *
  * # Returns
  * - `true` if all snippets were saved successfully.
- * - `false` if deserialization or saving failed.
+ * - `false` if deserialization or saving failed, in which case none of them were saved.
  *
  * # Safety
  * - `snippets_json` must be a valid, null-terminated UTF-8 string.
@@ -81,7 +327,7 @@ char *load_all_ffi(void);
 bool save_all_ffi(const char *snippets_json);
 
 /**
- * Executes a generic Git command inside the `.nibb` directory and returns the output as JSON.
+ * Executes a generic Git command inside the Nibb directory (`$NIBB_HOME` or `~/.nibb`) and returns the output as JSON.
  *
  * # Arguments
  * - `args`: A pointer to a null-terminated C string containing the Git arguments as a whitespace-separated string, e.g. `"status -s"`.
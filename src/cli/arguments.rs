@@ -1,3 +1,4 @@
+use std::path::PathBuf;
 use clap::{Parser, Subcommand, ValueEnum};
 
 #[derive(Parser, Debug, Clone)]
@@ -10,6 +11,12 @@ version = "0.5.0",
 pub struct Arguments {
     #[clap(subcommand)]
     pub command: NibbCommand,
+    /// Use the global repository in `~/.nibb` (or `$NIBB_HOME`), even inside a project with its own `.nibb/` directory
+    #[clap(short, long, global = true)]
+    pub global: bool,
+    /// Use the repository at this path instead of the global one. Project repositories are ignored
+    #[clap(short, long, global = true)]
+    pub repo: Option<PathBuf>,
 
 }
 #[derive(Subcommand, Debug, Clone)]
@@ -33,6 +40,15 @@ pub enum NibbCommand {
         /// Initialize the snippet as public
         #[clap(short, long)]
         public: bool,
+        /// File the snippet under this namespace, e.g. `rust/serde`. The name may start with it as well
+        #[clap(short, long)]
+        namespace: Option<String>,
+        /// Create a multi-file snippet from these files, instead of a single content
+        #[clap(short = 'F', long = "file", conflicts_with = "content")]
+        files: Vec<PathBuf>,
+        /// Create a binary snippet from the raw bytes of this file, e.g. an icon or a test fixture
+        #[clap(short, long, conflicts_with_all = ["content", "files"])]
+        binary: Option<PathBuf>,
     },
     /// List snippets
     List {
@@ -41,18 +57,64 @@ pub enum NibbCommand {
         /// it will list all snippets created before that time.
         #[clap(short, long)]
         filter: Option<String>,
+        /// Only list snippets filed under this namespace or one of its sub-namespaces, e.g. `rust/serde`
+        #[clap(long = "in", value_name = "NAMESPACE")]
+        namespace: Option<String>,
         /// Output the results in JSON format, instead of a printed list. Will be written to stdout.
         #[clap(short, long)]
         json: bool,
     },
-    /// Delete a snippet
+    /// Delete a snippet, moving it to the trash
     Delete {
         /// Name
         name: String,
     },
+    /// List, restore or permanently remove deleted snippets
+    Trash {
+        #[clap(subcommand)]
+        command: TrashCommand,
+    },
+    /// Check the repository for inconsistencies
+    Fsck {
+        /// Fix the inconsistencies that can be fixed safely
+        #[clap(long)]
+        repair: bool,
+        /// Output the report in JSON format. Will be written to stdout.
+        #[clap(short, long)]
+        json: bool,
+    },
+    /// Upgrade all snippet metadata to the current schema version
+    Migrate,
+    /// Watch the snippets for changes and print each one as a line of JSON
+    Watch {
+        /// Milliseconds to wait for further changes before reporting them
+        #[clap(long, default_value_t = 200)]
+        debounce: u64,
+    },
+    /// Link a snippet to another one, or remove the link
+    Link {
+        /// Name or ID
+        name: String,
+        /// Kind of the link
+        #[clap(value_enum)]
+        kind: LinkType,
+        /// Name, slug or ID of the snippet to link to
+        target: String,
+        /// Remove the link instead of adding it
+        #[clap(long)]
+        remove: bool,
+    },
+    /// List the snippets linked to or from a snippet
+    Related {
+        /// Name or ID
+        name: String,
+        /// Output the results in JSON format, instead of a printed list. Will be written to stdout.
+        #[clap(short, long)]
+        json: bool,
+    },
     /// Edit an existing snippet
     Edit {
-        /// Name
+        /// Name or ID
         name: String,
         /// Key you want to edit
         #[clap(value_enum)]
@@ -62,6 +124,40 @@ pub enum NibbCommand {
     },
 }
 
+#[derive(Subcommand, Debug, Clone)]
+pub enum TrashCommand {
+    /// List deleted snippets
+    List,
+    /// Restore the most recently deleted snippet with the given name
+    Restore {
+        /// Name
+        name: String,
+    },
+    /// Permanently remove deleted snippets
+    Empty {
+        /// Only remove snippets deleted at least this long ago, e.g. `30d`, `12h`, `15m` or `90s`
+        #[clap(long, value_parser = parse_age)]
+        older_than: Option<chrono::Duration>,
+    },
+}
+
+/// Parses an age like `30d` into a duration. Supported units are `s`, `m`, `h`, `d` and `w`.
+fn parse_age(s: &str) -> Result<chrono::Duration, String> {
+    let unit_at = s.len() - s.chars().last().map_or(0, |c| c.len_utf8());
+    let (amount, unit) = s.split_at(unit_at);
+    let amount: u32 = amount.parse().map_err(|_| format!("invalid age: {}", s))?;
+    let seconds = match unit {
+        "s" => 1,
+        "m" => 60,
+        "h" => 60 * 60,
+        "d" => 24 * 60 * 60,
+        "w" => 7 * 24 * 60 * 60,
+        _ => return Err(format!("invalid unit in {}, expected one of s, m, h, d, w", s)),
+    };
+    chrono::Duration::try_seconds(i64::from(amount) * seconds)
+        .ok_or(format!("age out of range: {}", s))
+}
+
 #[derive(Debug, Clone, Copy, ValueEnum)]
 pub enum SnippetKey {
     Name,
@@ -70,4 +166,12 @@ pub enum SnippetKey {
     Language,
     Tags,
     Visibility,
-}
\ No newline at end of file
+    Namespace,
+}
+#[derive(Debug, Clone, Copy, ValueEnum)]
+pub enum LinkType {
+    SeeAlso,
+    Replaces,
+    DependsOn,
+    DerivedFrom,
+}
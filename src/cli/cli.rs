@@ -1,14 +1,101 @@
-use crate::cli::arguments::{Arguments, NibbCommand, SnippetKey};
+use std::fmt;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use crate::cli::arguments::{Arguments, LinkType, NibbCommand, SnippetKey, TrashCommand};
 use anyhow::{bail, Context, Result};
-use chrono::Utc;
 #[cfg(feature = "ansi")]
 use colored::Colorize;
-use slug::slugify;
-use nibb_core::{get_nibb_dir, FSRepo, FileType, Meta, Snippet, SnippetRepository, Visibility};
-use nibb_core::snippets::utils::filter_snippets;
+use serde::Serialize;
+use nibb_core::{find_project_dir, BinaryContent, ExpectedVersion, LinkDirection, LinkKind, META_SCHEMA_VERSION, NibbError, resolve_nibb_dir, slugify_path, FSRepo, FileType, Meta, Snippet, SnippetContent, SnippetFile, SnippetLink, SnippetRepository, Visibility};
+use nibb_core::snippets::utils::matches_filter;
+
+/// Where a repository was found.
+#[derive(Debug, Clone, Copy, Serialize)]
+#[serde(rename_all = "lowercase")]
+enum Source {
+    /// A `.nibb/` directory in the current directory or one of its ancestors.
+    Project,
+    /// The global `~/.nibb` directory, or the one given by `$NIBB_HOME` or `--repo`.
+    Global,
+}
+
+impl fmt::Display for Source {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Source::Project => write!(f, "project"),
+            Source::Global => write!(f, "global"),
+        }
+    }
+}
+
+/// The repositories visible from the current directory, innermost first.
+///
+/// That is the project repository, if there is one and neither `--global` nor `--repo` is given,
+/// and the global repository.
+struct Repos(Vec<(Source, FSRepo)>);
+
+impl Repos {
+    fn open(global: bool, repo: Option<&Path>) -> Result<Self> {
+        let mut repos = Vec::new();
+        let project_dir = match global || repo.is_some() {
+            true => None,
+            false => find_project_dir(std::env::current_dir()?),
+        };
+        if let Some(dir) = project_dir {
+            let repo = FSRepo::new(&dir)
+                .with_context(|| format!("Failed to open project repo: {:?}", dir))?;
+            repos.push((Source::Project, repo));
+        }
+        repos.push((Source::Global, FSRepo::new(resolve_nibb_dir(repo)?).with_context(|| "Failed to create repo")?));
+        Ok(Self(repos))
+    }
+    /// The repository that new snippets go to.
+    fn innermost(&self) -> &FSRepo {
+        &self.0[0].1
+    }
+    /// The repository holding the snippet with the given ID or slug, looked up in the same order as `list`
+    /// shows them: the project's first, then the global one.
+    ///
+    /// Falls back to the innermost one if none has it, so the error names the usual repository.
+    fn resolve(&self, key: &str) -> Result<&FSRepo> {
+        for (source, repo) in &self.0 {
+            match repo.load_by_key(key) {
+                Ok(_) => return Ok(repo),
+                Err(NibbError::NotFound(_)) => {}
+                Err(e) => return Err(e).with_context(|| format!("Failed to load {} snippet: {}", source, key)),
+            }
+        }
+        Ok(self.innermost())
+    }
+    /// The repository whose trash holds a snippet with the given name, looked up in the same order as
+    /// [`resolve()`](Self::resolve): the project's first, then the global one.
+    ///
+    /// Falls back to the innermost one if none has it, so the error names the usual repository.
+    fn resolve_trash(&self, name: &str) -> Result<&FSRepo> {
+        let slug = slugify_path(name);
+        for (source, repo) in &self.0 {
+            let entries = repo.list_trash()
+                .with_context(|| format!("Failed to list {} trash", source))?;
+            if entries.iter().any(|entry| entry.slug == slug) {
+                return Ok(repo);
+            }
+        }
+        Ok(self.innermost())
+    }
+}
+
+/// A snippet of `nibb list`, labeled with the repository it comes from.
+#[derive(Serialize)]
+struct ListedSnippet {
+    source: Source,
+    #[serde(flatten)]
+    snippet: Snippet,
+}
 
 pub fn execute_cli(cli_args: Arguments) -> Result<()> {
-    let repo = FSRepo::new(get_nibb_dir()?).with_context(|| "Failed to create repo")?;
+    let repos = Repos::open(cli_args.global, cli_args.repo.as_deref())?;
+    let repo = repos.innermost();
 
     match cli_args.command {
         NibbCommand::New {
@@ -17,28 +104,76 @@ pub fn execute_cli(cli_args: Arguments) -> Result<()> {
             content,
             language,
             tags,
-            public
+            public,
+            files,
+            binary,
+            namespace,
         } => {
-            cli_new(&repo, name, description, content.unwrap_or("".to_string()), language, tags, public)?;
+            // binary snippets are stored as such, unless a language is given
+            let language = language.or(binary.as_ref().map(|_| "binary".to_string()));
+            let content = match (binary, files.is_empty()) {
+                (Some(path), _) => std::fs::read(&path)
+                    .map(|data| SnippetContent::Binary(BinaryContent::new(data)))
+                    .with_context(|| format!("Failed to read file: {:?}", path))?,
+                (None, true) => SnippetContent::Text(content.unwrap_or("".to_string())),
+                (None, false) => read_snippet_files(&files)?,
+            };
+            let name = match namespace {
+                Some(namespace) => format!("{}/{}", namespace, name),
+                None => name,
+            };
+            cli_new(repo, name, description, content, language, tags, public)?;
         }
-        NibbCommand::List {filter, json } => {
-            cli_list(&repo, filter, json)?;
+        NibbCommand::List {filter, namespace, json } => {
+            cli_list(&repos, filter, namespace, json)?;
         }
         NibbCommand::Edit {name, key, value} => {
-            cli_edit(&repo, name, key, value)?;
+            cli_edit(repos.resolve(&name)?, name, key, value)?;
+        }
+        NibbCommand::Link {name, kind, target, remove} => {
+            cli_link(repos.resolve(&name)?, name, kind, target, remove)?;
+        }
+        NibbCommand::Related {name, json} => {
+            cli_related(repos.resolve(&name)?, name, json)?;
         }
         NibbCommand::Delete {name} => {
-            repo.delete(&name)?;
+            cli_delete(repos.resolve(&name)?, name)?;
+        }
+        NibbCommand::Trash {command} => {
+            cli_trash(&repos, command)?;
+        }
+        NibbCommand::Fsck {repair, json} => {
+            cli_fsck(repo, repair, json)?;
+        }
+        NibbCommand::Watch {debounce} => {
+            cli_watch(repo, debounce)?;
+        }
+        NibbCommand::Migrate => {
+            let upgraded = repo.migrate().with_context(|| "Failed to migrate repository")?;
+            println!("Upgraded {} file(s) to schema version {}", upgraded, META_SCHEMA_VERSION);
         }
     }
     Ok(())
 }
 
-fn cli_list(repo: &FSRepo, filter: Option<String>, json: bool) -> Result<()> {
-    let snippets = filter_snippets(
-        repo.load_all().with_context(|| "Failed to load snippets")?,
-        &filter.unwrap_or("".to_string())
-    );
+fn cli_list(repos: &Repos, filter: Option<String>, namespace: Option<String>, json: bool) -> Result<()> {
+    let filter = filter.unwrap_or("".to_string());
+    let namespace = namespace.unwrap_or("".to_string());
+    let mut snippets = Vec::new();
+    for (source, repo) in &repos.0 {
+        let report = repo.load_all_lenient()
+            .with_context(|| format!("Failed to load {} snippets", source))?;
+        // warnings go to stderr, so `--json` output stays parsable
+        for diagnostic in &report.diagnostics {
+            #[cfg(feature = "ansi")]
+            eprintln!("{} skipping {} '{}': {}", "Warning:".bold().yellow(), source, diagnostic.slug, diagnostic.message);
+            #[cfg(not(feature = "ansi"))]
+            eprintln!("Warning: skipping {} '{}': {}", source, diagnostic.slug, diagnostic.message);
+        }
+        snippets.extend(report.snippets.into_iter()
+            .filter(|snippet| snippet.meta.is_in(&namespace) && matches_filter(&snippet.meta, &filter))
+            .map(|snippet| ListedSnippet { source: *source, snippet }));
+    }
     if json {
         if snippets.is_empty() {
             println!("[]");
@@ -63,8 +198,12 @@ fn cli_list(repo: &FSRepo, filter: Option<String>, json: bool) -> Result<()> {
         println!("{}", "Snippets:".bold().green());
         #[cfg(not(feature = "ansi"))]
         println!("Snippets:");
-        for snippet in snippets {
-            let display = format!("{}", snippet);
+        for listed in snippets {
+            #[cfg(feature = "ansi")]
+            println!("  {}", format!("[{}]", listed.source).bold().magenta());
+            #[cfg(not(feature = "ansi"))]
+            println!("  [{}]", listed.source);
+            let display = format!("{}", listed.snippet);
             for line in display.lines() {
                 println!("  {}", line);
             }
@@ -73,35 +212,217 @@ fn cli_list(repo: &FSRepo, filter: Option<String>, json: bool) -> Result<()> {
     }
 }
 
+fn cli_delete(repo: &FSRepo, name: String) -> Result<()> {
+    // the links of other snippets to this one are left dangling
+    let incoming: Vec<_> = repo.related(&name)
+        .unwrap_or_default()
+        .into_iter()
+        .filter(|relation| matches!(relation.direction, LinkDirection::Incoming))
+        .collect();
+    repo.delete(&name)?;
+    for relation in incoming {
+        #[cfg(feature = "ansi")]
+        eprintln!("{} {} still links to {} ({})", "Warning:".bold().yellow(), relation.meta.get_slug(), name, relation.kind);
+        #[cfg(not(feature = "ansi"))]
+        eprintln!("Warning: {} still links to {} ({})", relation.meta.get_slug(), name, relation.kind);
+    }
+    Ok(())
+}
+
+fn cli_link(repo: &FSRepo, name: String, kind: LinkType, target: String, remove: bool) -> Result<()> {
+    let mut snippet = repo.load_by_key(&name)
+        .with_context(|| format!("Failed to load snippet: {}", name))?;
+    let expected = ExpectedVersion::Modified(snippet.meta.modified);
+    let kind = match kind {
+        LinkType::SeeAlso => LinkKind::SeeAlso,
+        LinkType::Replaces => LinkKind::Replaces,
+        LinkType::DependsOn => LinkKind::DependsOn,
+        LinkType::DerivedFrom => LinkKind::DerivedFrom,
+    };
+    let link = SnippetLink::new(kind, &target);
+    if remove {
+        let before = snippet.meta.links.len();
+        snippet.meta.links.retain(|other| *other != link);
+        if snippet.meta.links.len() == before {
+            bail!("{} has no {} link to {}", name, link.kind, link.target);
+        }
+    }
+    else if snippet.meta.links.contains(&link) {
+        return Ok(());
+    }
+    else {
+        snippet.meta.links.push(link);
+    }
+    repo.save_if_unmodified(&snippet, &expected).with_context(|| format!("Failed to save snippet: {}", name))
+}
+
+fn cli_related(repo: &FSRepo, name: String, json: bool) -> Result<()> {
+    let relations = repo.related(&name)
+        .with_context(|| format!("Failed to load snippet: {}", name))?;
+    if json {
+        println!(
+            "{}",
+            serde_json::to_string_pretty(&relations)
+                .with_context(|| "Failed to serialize relations to JSON")?
+        );
+        return Ok(());
+    }
+    if relations.is_empty() {
+        #[cfg(feature = "ansi")]
+        println!("{}", "No related snippets".bold().yellow());
+        #[cfg(not(feature = "ansi"))]
+        println!("No related snippets");
+        return Ok(());
+    }
+    for relation in relations {
+        let arrow = match relation.direction {
+            LinkDirection::Outgoing => "->",
+            LinkDirection::Incoming => "<-",
+        };
+        println!("  {} {} {} ({})", arrow, relation.kind, relation.meta.get_slug(), relation.meta.name);
+    }
+    Ok(())
+}
+
+fn cli_trash(repos: &Repos, command: TrashCommand) -> Result<()> {
+    match command {
+        TrashCommand::List => {
+            let mut entries = Vec::new();
+            for (source, repo) in &repos.0 {
+                let trash = repo.list_trash()
+                    .with_context(|| format!("Failed to list {} trash", source))?;
+                entries.extend(trash.into_iter().map(|entry| (*source, entry)));
+            }
+            if entries.is_empty() {
+                #[cfg(feature = "ansi")]
+                println!("{}", "Trash is empty".bold().yellow());
+                #[cfg(not(feature = "ansi"))]
+                println!("Trash is empty");
+                return Ok(());
+            }
+            #[cfg(feature = "ansi")]
+            println!("{}", "Trash:".bold().green());
+            #[cfg(not(feature = "ansi"))]
+            println!("Trash:");
+            for (source, entry) in entries {
+                #[cfg(feature = "ansi")]
+                println!("  {} {} (deleted {})", format!("[{}]", source).bold().magenta(), entry.slug, entry.deleted);
+                #[cfg(not(feature = "ansi"))]
+                println!("  [{}] {} (deleted {})", source, entry.slug, entry.deleted);
+            }
+        }
+        TrashCommand::Restore {name} => {
+            repos.resolve_trash(&name)?
+                .restore_trash(&name)
+                .with_context(|| format!("Failed to restore snippet: {}", name))?;
+        }
+        TrashCommand::Empty {older_than} => {
+            let removed = repos.innermost().empty_trash(older_than).with_context(|| "Failed to empty trash")?;
+            println!("Removed {} snippet(s) from the trash", removed);
+        }
+    }
+    Ok(())
+}
+
+fn cli_watch(repo: &FSRepo, debounce: u64) -> Result<()> {
+    let watcher = repo.watch(Duration::from_millis(debounce))
+        .with_context(|| "Failed to watch repository")?;
+    // one event per line (NDJSON), flushed right away for consumers reading a pipe
+    let mut stdout = std::io::stdout().lock();
+    for event in watcher {
+        writeln!(stdout, "{}", event.to_json())?;
+        stdout.flush()?;
+    }
+    Ok(())
+}
+
+fn cli_fsck(repo: &FSRepo, repair: bool, json: bool) -> Result<()> {
+    let report = repo.fsck(repair).with_context(|| "Failed to check repository")?;
+    if json {
+        println!(
+            "{}",
+            serde_json::to_string_pretty(&report)
+                .with_context(|| "Failed to serialize report to JSON")?
+        );
+    }
+    else if report.issues.is_empty() {
+        #[cfg(feature = "ansi")]
+        println!("{}", "No issues found".bold().green());
+        #[cfg(not(feature = "ansi"))]
+        println!("No issues found");
+    }
+    else {
+        for issue in &report.issues {
+            let status = if issue.repaired { " (repaired)" } else { "" };
+            #[cfg(feature = "ansi")]
+            println!("  {} {}: {}{}", format!("[{}]", issue.category).bold().yellow(), issue.path.display(), issue.message, status.green());
+            #[cfg(not(feature = "ansi"))]
+            println!("  [{}] {}: {}{}", issue.category, issue.path.display(), issue.message, status);
+        }
+    }
+    let unrepaired = report.unrepaired().count();
+    if unrepaired > 0 {
+        bail!("{} issue(s) left{}", unrepaired, if repair { "" } else { ", run with --repair to fix the safe ones" });
+    }
+    Ok(())
+}
+
+/// Reads the files given with `nibb new --file` into the content of a multi-file snippet.
+fn read_snippet_files(paths: &[PathBuf]) -> Result<SnippetContent> {
+    let mut files = Vec::new();
+    for path in paths {
+        let name = path.file_name()
+            .and_then(|name| name.to_str())
+            .with_context(|| format!("Invalid file name: {:?}", path))?;
+        let content = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read file: {:?}", path))?;
+        files.push(SnippetFile::new(name, content));
+    }
+    Ok(SnippetContent::Files(files))
+}
+
 fn cli_new(
     repo: &FSRepo,
     name: String,
     description: Option<String>,
-    content: String,
+    content: SnippetContent,
     language: Option<String>,
     tags: Vec<String>,
     public: bool,
 ) -> Result<()> {
-    let meta = Meta::new(
-        name.clone(),
+    // everything up to the last `/` is the namespace, like when renaming
+    let (namespace, short_name) = name.rsplit_once('/').unwrap_or(("", &name));
+    let mut meta = Meta::new(
+        short_name.to_string(),
         description.unwrap_or("".to_string()),
         tags,
         FileType::from(language.unwrap_or("".to_string()).as_str()),
         if public { Some(Visibility::Public) } else { Some(Visibility::Private) }
     );
+    meta.namespace = namespace.to_string();
     let new_snippet = Snippet::new(meta, content);
     repo.save(&new_snippet).with_context(|| format!("Failed to save snippet: {}", name))
 }
 
 fn cli_edit(repo: &FSRepo, name: String, key: SnippetKey, value: String) -> Result<()> {
-    let mut snippet = repo.load(slugify(&name).as_str())
+    let mut snippet = repo.load_by_key(&name)
         .with_context(|| format!(
             "Failed to load snippet: {}",
             name
         ))?;
+    let expected = ExpectedVersion::Modified(snippet.meta.modified);
     match key {
         SnippetKey::Name => {
-            snippet.meta.name = value;
+            // renaming changes the slug, so the snippet has to be moved instead of saved
+            repo.rename(&snippet.meta.get_slug(), &value)
+                .with_context(|| format!("Failed to rename snippet: {}", name))?;
+            return Ok(());
+        }
+        SnippetKey::Namespace => {
+            // a name with a namespace path moves the snippet there, see `SnippetRepository::rename()`
+            repo.rename(&snippet.meta.get_slug(), &format!("{}/{}", value, snippet.meta.name))
+                .with_context(|| format!("Failed to move snippet: {}", name))?;
+            return Ok(());
         }
         SnippetKey::Description => {
             snippet.meta.description = value;
@@ -116,10 +437,15 @@ fn cli_edit(repo: &FSRepo, name: String, key: SnippetKey, value: String) -> Resu
             snippet.meta.tags = value.split(',').map(|s| s.to_string()).collect();
         }
         SnippetKey::Content => {
-            snippet.content = value;
+            match snippet.content {
+                SnippetContent::Files(_) => bail!("{} is a multi-file snippet, its content cannot be set to a single value", name),
+                SnippetContent::Binary(_) => bail!("{} is a binary snippet, its content cannot be set to a text", name),
+                SnippetContent::Locked => bail!("{} is locked, set NIBB_PASSPHRASE or encryption.key_file to edit it", name),
+                SnippetContent::Text(_) => {}
+            }
+            snippet.content = value.into();
         }
     }
-    snippet.meta.modified = Utc::now();
-    repo.save(&snippet)?;
+    repo.save_if_unmodified(&snippet, &expected)?;
     Ok(())
 }
\ No newline at end of file
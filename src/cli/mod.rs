@@ -1,2 +1,3 @@
 pub mod arguments;
+#[allow(clippy::module_inception)]
 pub mod cli;
\ No newline at end of file
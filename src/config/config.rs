@@ -1,10 +1,20 @@
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use serde::{Deserialize, Serialize};
+use crate::migration::{migrate_config, CONFIG_SCHEMA_VERSION};
 use crate::result::NibbResult;
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct Config {
+    /// Version of the `config.toml` schema, see [`CONFIG_SCHEMA_VERSION`].
+    #[serde(default = "current_schema_version")]
+    pub schema_version: u32,
     pub git: GitConfig,
+    #[serde(default)]
+    pub history: HistoryConfig,
+    #[serde(default)]
+    pub lock: LockConfig,
+    #[serde(default)]
+    pub encryption: EncryptionConfig,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -27,12 +37,50 @@ pub struct GitConfig {
     pub branch: String,
 }
 
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct HistoryConfig {
+    /// Record a revision every time a snippet is overwritten.
+    pub enabled: bool,
+    /// Number of revisions kept per snippet. `0` keeps all of them.
+    pub max_revisions: usize,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct LockConfig {
+    /// Milliseconds to wait for another process to release the repository lock.
+    pub timeout_ms: u64,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct EncryptionConfig {
+    /// Encrypt the content of private snippets. The key comes from `$NIBB_PASSPHRASE` or `key_file`.
+    pub enabled: bool,
+    /// Encrypt the description of private snippets as well.
+    pub encrypt_description: bool,
+    /// File whose contents are used as the key, if `$NIBB_PASSPHRASE` is not set.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub key_file: Option<PathBuf>,
+    /// Base64 encoded salt for deriving the key, generated when encryption is first enabled.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub salt: Option<String>,
+}
+
 impl Config {
     pub fn new() -> Self {
         Config {
+            schema_version: CONFIG_SCHEMA_VERSION,
             git: GitConfig::default(),
+            history: HistoryConfig::default(),
+            lock: LockConfig::default(),
+            encryption: EncryptionConfig::default(),
         }
     }
+    /// Loads `config.toml`, creating it if missing.
+    ///
+    /// A file written with an older schema is upgraded in place.
     pub fn load(path: &Path)-> NibbResult<Self> {
         if !path.exists() {
             std::fs::File::create(path)?;
@@ -40,10 +88,29 @@ impl Config {
             cfg.save(path)?;
             return Ok(cfg);
         }
-        let content = std::fs::read_to_string(path)?;
-        let config: Config = toml::from_str(&content)?;
+        let (config, migrated) = Self::parse(path)?;
+        if migrated {
+            config.save(path)?;
+        }
         Ok(config)
     }
+    /// Reads the configuration at `path` without creating or upgrading the file.
+    ///
+    /// A missing file yields the defaults, an outdated one is upgraded in memory only.
+    pub fn read(path: &Path) -> NibbResult<Self> {
+        if !path.exists() {
+            return Ok(Config::default());
+        }
+        Ok(Self::parse(path)?.0)
+    }
+    /// Parses the file at `path` and upgrades it to the current schema.
+    /// Returns the configuration and whether it was upgraded.
+    fn parse(path: &Path) -> NibbResult<(Self, bool)> {
+        let content = std::fs::read_to_string(path)?;
+        let mut table: toml::Table = toml::from_str(&content)?;
+        let migrated = migrate_config(&mut table, path)?;
+        Ok((toml::Value::Table(table).try_into()?, migrated))
+    }
     pub fn save(&self, path: &Path) -> NibbResult<()> {
         let content = toml::to_string(self)?;
         if !path.exists() {
@@ -56,12 +123,14 @@ impl Config {
 
 impl Default for Config {
     fn default() -> Self {
-        Self {
-            git: GitConfig::default(),
-        }
+        Self::new()
     }
 }
 
+fn current_schema_version() -> u32 {
+    CONFIG_SCHEMA_VERSION
+}
+
 impl GitConfig {
     pub fn new() -> Self {
         GitConfig {
@@ -90,4 +159,21 @@ impl Default for GitConfig {
             branch: String::from("master"),       
         }
     }
-}
\ No newline at end of file
+}
+
+impl Default for HistoryConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            max_revisions: 50,
+        }
+    }
+}
+
+impl Default for LockConfig {
+    fn default() -> Self {
+        Self {
+            timeout_ms: 5000,
+        }
+    }
+}
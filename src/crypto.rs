@@ -0,0 +1,177 @@
+use std::collections::HashMap;
+use std::sync::Mutex;
+use argon2::Argon2;
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD;
+use chacha20poly1305::aead::{Aead, Generate, Payload};
+use chacha20poly1305::{Key, KeyInit, XChaCha20Poly1305, XNonce};
+use crate::result::{NibbError, NibbResult};
+
+/// Environment variable holding the passphrase for encrypted snippets.
+pub const NIBB_PASSPHRASE_ENV: &str = "NIBB_PASSPHRASE";
+
+/// Prefix of every file written by [`Cipher::encrypt()`] or escaped by [`frame_plain()`],
+/// followed by a format byte.
+const MAGIC: &[u8] = b"NIBBENC";
+
+/// Format of plain text that starts with [`MAGIC`] itself, followed by the text.
+const FORMAT_PLAIN: u8 = 0;
+
+/// Format of encrypted files: the length of the salt, the salt, the nonce and the ciphertext.
+/// Everything before the nonce is authenticated along with the ciphertext.
+const FORMAT_ENCRYPTED: u8 = 1;
+
+/// Length of the salts generated by [`generate_salt()`].
+const SALT_LEN: usize = 16;
+
+/// Prefix of encrypted strings in `meta.toml`, followed by the base64 encoded encrypted bytes.
+const STR_PREFIX: &str = "nibb-enc:";
+
+/// Length of an XChaCha20-Poly1305 nonce.
+const NONCE_LEN: usize = 24;
+
+/// Length of a Poly1305 tag, the shortest possible ciphertext.
+const TAG_LEN: usize = 16;
+
+/// An encrypted file, split into its parts by [`parse()`].
+struct Sealed<'a> {
+    /// Salt the key was derived with.
+    salt: &'a [u8],
+    /// Authenticated data: everything before the nonce.
+    header: &'a [u8],
+    nonce: &'a [u8],
+    ciphertext: &'a [u8],
+}
+
+/// Splits `data` into its parts if it was written by [`Cipher::encrypt()`].
+fn parse(data: &[u8]) -> Option<Sealed<'_>> {
+    let (&format, rest) = data.strip_prefix(MAGIC)?.split_first()?;
+    if format != FORMAT_ENCRYPTED {
+        return None;
+    }
+    let (&salt_len, rest) = rest.split_first()?;
+    let salt = rest.get(..usize::from(salt_len))?;
+    let (header, rest) = data.split_at(MAGIC.len() + 2 + salt.len());
+    if rest.len() < NONCE_LEN + TAG_LEN {
+        return None;
+    }
+    let (nonce, ciphertext) = rest.split_at(NONCE_LEN);
+    Some(Sealed { salt, header, nonce, ciphertext })
+}
+
+/// Encrypts and decrypts snippet files with XChaCha20-Poly1305,
+/// using a key derived from a passphrase or key file with Argon2id.
+///
+/// Every file carries the salt its key was derived with, so it can be decrypted with the passphrase alone,
+/// e.g. in a clone of the repository without its `config.toml`.
+pub(crate) struct Cipher {
+    secret: Vec<u8>,
+    /// `encryption.salt`, used for new files.
+    salt: Option<Vec<u8>>,
+    /// Keys derived so far, by salt, since deriving one takes a while.
+    keys: Mutex<HashMap<Vec<u8>, Key>>,
+}
+
+impl Cipher {
+    /// Creates a cipher for `secret`, encrypting with the repository's `salt`.
+    ///
+    /// Keys are derived on first use.
+    pub(crate) fn new(secret: Vec<u8>, salt: Option<Vec<u8>>) -> Self {
+        Self { secret, salt, keys: Mutex::new(HashMap::new()) }
+    }
+    /// Returns the cipher for the key derived from the secret and `salt`.
+    fn with_salt(&self, salt: &[u8]) -> NibbResult<XChaCha20Poly1305> {
+        let mut keys = self.keys.lock()
+            .map_err(|e| NibbError::Encryption(format!("key cache poisoned: {}", e)))?;
+        if let Some(key) = keys.get(salt) {
+            return Ok(XChaCha20Poly1305::new(key));
+        }
+        let mut key = Key::default();
+        Argon2::default()
+            .hash_password_into(&self.secret, salt, &mut key)
+            .map_err(|e| NibbError::Encryption(format!("key derivation failed: {}", e)))?;
+        keys.insert(salt.to_vec(), key);
+        Ok(XChaCha20Poly1305::new(&key))
+    }
+    fn repo_salt(&self) -> NibbResult<&[u8]> {
+        self.salt.as_deref()
+            .ok_or(NibbError::Encryption("encryption.salt is not set".to_string()))
+    }
+    pub(crate) fn encrypt(&self, plaintext: &[u8]) -> NibbResult<Vec<u8>> {
+        let salt = self.repo_salt()?;
+        let salt_len = u8::try_from(salt.len())
+            .map_err(|_| NibbError::Encryption("encryption.salt is too long".to_string()))?;
+        let header = [MAGIC, &[FORMAT_ENCRYPTED, salt_len], salt].concat();
+        let nonce = XNonce::generate();
+        let ciphertext = self.with_salt(salt)?
+            .encrypt(&nonce, Payload { msg: plaintext, aad: &header })
+            .map_err(|_| NibbError::Encryption("encryption failed".to_string()))?;
+        Ok([&header, nonce.as_slice(), &ciphertext].concat())
+    }
+    pub(crate) fn decrypt(&self, data: &[u8]) -> NibbResult<Vec<u8>> {
+        let sealed = parse(data)
+            .ok_or(NibbError::Encryption("not an encrypted file".to_string()))?;
+        let nonce = XNonce::try_from(sealed.nonce)
+            .map_err(|_| NibbError::Encryption("invalid nonce".to_string()))?;
+        self.with_salt(sealed.salt)?
+            .decrypt(&nonce, Payload { msg: sealed.ciphertext, aad: sealed.header })
+            .map_err(|_| NibbError::Encryption("decryption failed, wrong passphrase or key file".to_string()))
+    }
+    /// Encrypts a string stored in `meta.toml`, e.g. a description.
+    pub(crate) fn encrypt_str(&self, plaintext: &str) -> NibbResult<String> {
+        Ok(format!("{}{}", STR_PREFIX, STANDARD.encode(self.encrypt(plaintext.as_bytes())?)))
+    }
+    pub(crate) fn decrypt_str(&self, data: &str) -> NibbResult<String> {
+        let encoded = data.strip_prefix(STR_PREFIX)
+            .ok_or(NibbError::Encryption("not an encrypted string".to_string()))?;
+        let encrypted = STANDARD.decode(encoded)
+            .map_err(|e| NibbError::Encryption(e.to_string()))?;
+        String::from_utf8(self.decrypt(&encrypted)?)
+            .map_err(|e| NibbError::Encryption(e.to_string()))
+    }
+}
+
+/// Returns whether `data` was written by [`Cipher::encrypt()`].
+pub(crate) fn is_encrypted(data: &[u8]) -> bool {
+    parse(data).is_some()
+}
+
+/// Returns whether `data` was written by [`Cipher::encrypt_str()`].
+pub(crate) fn is_encrypted_str(data: &str) -> bool {
+    data.strip_prefix(STR_PREFIX)
+        .and_then(|encoded| STANDARD.decode(encoded).ok())
+        .is_some_and(|encrypted| is_encrypted(&encrypted))
+}
+
+/// Prepares `plaintext` to be stored without encryption.
+///
+/// It is stored as it is, unless it starts like an encrypted file. Then it is escaped with [`FORMAT_PLAIN`],
+/// so it is not mistaken for one when read back with [`unframe_plain()`].
+pub(crate) fn frame_plain(plaintext: &[u8]) -> Vec<u8> {
+    match plaintext.starts_with(MAGIC) {
+        true => [MAGIC, &[FORMAT_PLAIN], plaintext].concat(),
+        false => plaintext.to_vec(),
+    }
+}
+
+/// Returns the plain text stored by [`frame_plain()`].
+pub(crate) fn unframe_plain(data: Vec<u8>) -> Vec<u8> {
+    match data.strip_prefix(MAGIC).and_then(|rest| rest.strip_prefix(&[FORMAT_PLAIN])) {
+        Some(plaintext) => plaintext.to_vec(),
+        None => data,
+    }
+}
+
+/// Generates a random, base64 encoded salt for `encryption.salt`.
+pub(crate) fn generate_salt() -> NibbResult<String> {
+    let mut salt = [0u8; SALT_LEN];
+    getrandom::fill(&mut salt)
+        .map_err(|e| NibbError::Encryption(format!("failed to generate a salt: {}", e)))?;
+    Ok(STANDARD.encode(salt))
+}
+
+/// Decodes `encryption.salt`.
+pub(crate) fn decode_salt(salt: &str) -> NibbResult<Vec<u8>> {
+    STANDARD.decode(salt)
+        .map_err(|e| NibbError::Encryption(format!("invalid encryption.salt: {}", e)))
+}
@@ -1,16 +1,20 @@
-use std::ffi::{CStr, CString};
+use std::ffi::{c_void, CStr, CString};
 use std::os::raw::c_char;
-use slug::slugify;
-use crate::ffi::ffi_utils::{c_str_from_str, load_repo_ffi, str_from_c_str};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use crate::ffi::ffi_utils::{c_str_from_str, load_repo_ffi, str_from_c_str, FFIWatch, UserData, FFI_WATCHES, NEXT_WATCH_HANDLE};
 use crate::fs::get_nibb_dir;
+use serde::Serialize;
 use crate::result::NibbFFIError;
-use crate::{FSRepo, Snippet, SnippetRepository};
+use crate::{ExpectedVersion, LoadDiagnostic, Meta, MetaReport, Snippet, SnippetRepository};
 use crate::git::git_integration::nibb_git_generic;
 
-/// Loads a snippet by name and returns its JSON representation.
+/// Loads a snippet by name or ID and returns its JSON representation.
 ///
 /// # Arguments
-/// - `name`: A null-terminated C string representing the name of the snippet.
+/// - `name`: A null-terminated C string representing the name or the ID of the snippet.
+///   Snippets in a namespace are loaded by their path, e.g. `rust/serde/untagged-enum`.
 ///
 /// # Returns
 /// A newly allocated C string (`*mut c_char`) containing the snippet's JSON representation.
@@ -32,8 +36,7 @@ pub extern "C" fn load_snippet_ffi(name: *const c_char) -> *mut c_char {
         Ok(name) => name,
         Err(e) => return c_str_from_str(&NibbFFIError::FFIError(e.to_string()).to_json())
     };
-    let slug = slugify(name);
-    let snippet = match repo.load(&slug) {
+    let snippet = match repo.load_by_key(name) {
         Ok(snippet) => snippet,
         Err(e) => return c_str_from_str(&e.to_json())
     };
@@ -62,13 +65,48 @@ pub extern "C" fn save_snippet_ffi(snippet_json: *const c_char) -> bool {
             return false;
         }
     };
-    let repo = match FSRepo::new(get_nibb_dir().unwrap()) {
+    let repo = match load_repo_ffi() {
         Ok(repo) => repo,
         Err(_) => return false
     };
-    match repo.save(&snippet) {
-        Ok(_) => true,
-        Err(_) => false,
+    repo.save(&snippet).is_ok()
+}
+
+/// Saves a single snippet from its JSON representation, unless it was changed since it was loaded.
+///
+/// # Arguments
+/// - `snippet_json`: A null-terminated C string containing a JSON-encoded snippet.
+/// - `expected_json`: A null-terminated C string containing the version the caller loaded,
+///   either `{"modified": "<timestamp>"}` or `{"hash": "<version hash>"}`.
+///
+/// # Returns
+/// - A JSON string of the saved snippet, with its new `modified` timestamp.
+/// - A JSON error object otherwise. If the stored snippet is not the expected version, it is of
+///   type `Conflict`, with the `slug`, the `current` version (`null` if deleted) and the `attempted` one.
+///
+/// # Safety
+/// - `snippet_json` and `expected_json` must be valid, null-terminated UTF-8 strings.
+/// - The returned pointer must be freed with `free_string_ffi`.
+#[unsafe(no_mangle)]
+pub extern "C" fn save_snippet_if_unmodified_ffi(snippet_json: *const c_char, expected_json: *const c_char) -> *mut c_char {
+    let snippet: Snippet = match serde_json::from_str(&str_from_c_str(snippet_json)) {
+        Ok(snippet) => snippet,
+        Err(e) => return c_str_from_str(&NibbFFIError::FFIError(e.to_string()).to_json())
+    };
+    let expected: ExpectedVersion = match serde_json::from_str(&str_from_c_str(expected_json)) {
+        Ok(expected) => expected,
+        Err(e) => return c_str_from_str(&NibbFFIError::FFIError(e.to_string()).to_json())
+    };
+    let repo = match load_repo_ffi() {
+        Ok(repo) => repo,
+        Err(e) => return e
+    };
+    if let Err(e) = repo.save_if_unmodified(&snippet, &expected) {
+        return c_str_from_str(&e.to_json());
+    }
+    match repo.load(&snippet.meta.get_slug()) {
+        Ok(saved) => c_str_from_str(&saved.to_json()),
+        Err(e) => c_str_from_str(&e.to_json())
     }
 }
 
@@ -85,21 +123,219 @@ pub extern "C" fn save_snippet_ffi(snippet_json: *const c_char) -> bool {
 /// - `name` needs to be a valid, null-terminated, UTF-8 string.
 #[unsafe(no_mangle)]
 pub extern "C" fn delete_snippet_ffi(name: *const c_char) -> bool {
-    let repo = match FSRepo::new(get_nibb_dir().unwrap()) {
+    let repo = match load_repo_ffi() {
         Ok(repo) => repo,
         Err(_) => return false
     };
     let name = str_from_c_str(name);
-    match repo.delete(&name) {
-        Ok(_) => true,
-        Err(_) => false,
+    repo.delete(&name).is_ok()
+}
+
+/// Renames a snippet, moving it to the slug of its new name.
+///
+/// # Arguments
+/// - `name`: A null-terminated C string of the snippet's current name or slug.
+/// - `new_name`: A null-terminated C string of the new name. A name with a namespace path,
+///   e.g. `rust/serde/Untagged enum`, moves the snippet into that namespace.
+///
+/// # Returns
+/// A newly allocated C string (`*mut c_char`).
+/// - On success: JSON-encoded renamed `Snippet` (must be freed with `free_string_ffi`).
+/// - On failure: JSON-encoded error object (must also be freed).
+///   If another snippet already uses the new slug, the error type is `InvalidSlug`.
+///
+/// # Safety
+/// - `name` and `new_name` must be valid, null-terminated UTF-8 strings.
+/// - Caller is responsible for freeing the returned string using `free_string_ffi`.
+#[unsafe(no_mangle)]
+pub extern "C" fn rename_snippet_ffi(name: *const c_char, new_name: *const c_char) -> *mut c_char {
+    let repo = match load_repo_ffi() {
+        Ok(repo) => repo,
+        Err(e) => return e,
+    };
+    let name = str_from_c_str(name);
+    let new_name = str_from_c_str(new_name);
+    match repo.rename(&name, &new_name) {
+        Ok(snippet) => c_str_from_str(&snippet.to_json()),
+        Err(e) => c_str_from_str(&e.to_json())
+    }
+}
+
+/// Lists the deleted snippets in the trash, oldest deletion first.
+///
+/// # Returns
+/// A newly allocated C string (`*mut c_char`).
+/// - On success: JSON array of entries, e.g.
+///   `[{"id": "20250101T120000.000000000Z", "slug": "<slug>", "deleted": "<RFC 3339 timestamp>"}]`
+///   (must be freed with `free_string_ffi`).
+/// - On failure: JSON-encoded error object (must also be freed).
+///
+/// # Safety
+/// - Caller is responsible for freeing the returned string using `free_string_ffi`.
+#[unsafe(no_mangle)]
+pub extern "C" fn list_trash_ffi() -> *mut c_char {
+    let repo = match load_repo_ffi() {
+        Ok(repo) => repo,
+        Err(e) => return e,
+    };
+    let entries = match repo.list_trash() {
+        Ok(entries) => entries,
+        Err(e) => return c_str_from_str(&e.to_json())
+    };
+    c_str_from_str(
+        &serde_json::to_string(&entries)
+            .unwrap_or_else(|_| "{\"type\":\"Other\",\"message\":\"Serialization failed\"}"
+                .to_string())
+    )
+}
+
+/// Restores the most recently deleted snippet with the given name from the trash.
+///
+/// # Arguments
+/// - `name`: A null-terminated C string of the deleted snippet's name or slug.
+///
+/// # Returns
+/// A newly allocated C string (`*mut c_char`).
+/// - On success: JSON-encoded restored `Snippet` (must be freed with `free_string_ffi`).
+/// - On failure: JSON-encoded error object (must also be freed).
+///   If another snippet uses the slug by now, the error type is `InvalidSlug`.
+///
+/// # Safety
+/// - `name` must be a valid, null-terminated UTF-8 string.
+/// - Caller is responsible for freeing the returned string using `free_string_ffi`.
+#[unsafe(no_mangle)]
+pub extern "C" fn restore_trash_ffi(name: *const c_char) -> *mut c_char {
+    let repo = match load_repo_ffi() {
+        Ok(repo) => repo,
+        Err(e) => return e,
+    };
+    let name = str_from_c_str(name);
+    match repo.restore_trash(&name) {
+        Ok(snippet) => c_str_from_str(&snippet.to_json()),
+        Err(e) => c_str_from_str(&e.to_json())
     }
 }
 
+/// Permanently removes deleted snippets from the trash.
+///
+/// # Arguments
+/// - `older_than_secs`: Only remove snippets deleted at least this many seconds ago.
+///   `0` removes everything.
+///
+/// # Returns
+/// - `true` if the trash was emptied successfully.
+/// - `false` if an error occurred.
+#[unsafe(no_mangle)]
+pub extern "C" fn empty_trash_ffi(older_than_secs: u64) -> bool {
+    let repo = match load_repo_ffi() {
+        Ok(repo) => repo,
+        Err(_) => return false,
+    };
+    let older_than = match older_than_secs {
+        0 => None,
+        secs => Some(i64::try_from(secs).ok()
+            .and_then(chrono::Duration::try_seconds)
+            .unwrap_or(chrono::Duration::MAX)),
+    };
+    repo.empty_trash(older_than).is_ok()
+}
+
+/// Callback receiving snippet change events from [`watch_ffi`].
+///
+/// `event_json` is a JSON-encoded `SnippetEvent`, e.g. `{"event": "modified", "slug": "<slug>"}`.
+/// It is only valid during the call and must not be freed. `user_data` is the pointer given to [`watch_ffi`].
+/// May be null, which [`watch_ffi`] refuses.
+pub type SnippetEventCallback = Option<extern "C" fn(event_json: *const c_char, user_data: *mut c_void)>;
+
+/// Starts watching the snippets for changes, calling `callback` once per change.
+///
+/// # Arguments
+/// - `callback`: Function called with each event. Called from a background thread.
+/// - `user_data`: Pointer passed to every call of `callback`, may be null.
+/// - `debounce_ms`: Milliseconds to wait for further changes before reporting them.
+///
+/// # Returns
+/// - A handle to pass to [`unwatch_ffi`], never `0` on success.
+/// - `0` if `callback` is null or the repository cannot be watched.
+///
+/// # Safety
+/// - `callback` must be safe to call from another thread until [`unwatch_ffi`] returned.
+/// - `user_data` must stay valid until [`unwatch_ffi`] returned.
+#[unsafe(no_mangle)]
+pub extern "C" fn watch_ffi(callback: SnippetEventCallback, user_data: *mut c_void, debounce_ms: u64) -> u64 {
+    let Some(callback) = callback else {
+        return 0;
+    };
+    let repo = match load_repo_ffi() {
+        Ok(repo) => repo,
+        Err(e) => {
+            free_string_ffi(e);
+            return 0;
+        }
+    };
+    let watcher = match repo.watch(Duration::from_millis(debounce_ms)) {
+        Ok(watcher) => watcher,
+        Err(_) => return 0,
+    };
+    let stop = Arc::new(AtomicBool::new(false));
+    let user_data = UserData(user_data);
+    let thread = {
+        let stop = Arc::clone(&stop);
+        std::thread::spawn(move || {
+            let user_data = user_data;
+            // wake up regularly, so unwatching does not wait for the next event
+            while !stop.load(Ordering::Relaxed) {
+                let Some(event) = watcher.recv_timeout(Duration::from_millis(100)) else {
+                    continue;
+                };
+                if let Ok(event_json) = CString::new(event.to_json()) {
+                    callback(event_json.as_ptr(), user_data.0);
+                }
+            }
+        })
+    };
+    let handle = NEXT_WATCH_HANDLE.fetch_add(1, Ordering::Relaxed);
+    match FFI_WATCHES.lock() {
+        Ok(mut watches) => {
+            watches.insert(handle, FFIWatch { stop, thread });
+            handle
+        }
+        Err(_) => 0,
+    }
+}
+
+/// Stops a watcher started with [`watch_ffi`].
+///
+/// Once this returns, the watcher's callback is not called anymore.
+///
+/// # Arguments
+/// - `handle`: The handle returned by [`watch_ffi`].
+///
+/// # Returns
+/// - `true` if the watcher was stopped.
+/// - `false` if `handle` is unknown.
+///
+/// # Safety
+/// - Must not be called from within the watcher's own callback.
+#[unsafe(no_mangle)]
+pub extern "C" fn unwatch_ffi(handle: u64) -> bool {
+    let watch = match FFI_WATCHES.lock() {
+        Ok(mut watches) => watches.remove(&handle),
+        Err(_) => None,
+    };
+    let Some(watch) = watch else {
+        return false;
+    };
+    watch.stop.store(true, Ordering::Relaxed);
+    watch.thread.join().is_ok()
+}
+
 /// Loads all snippets from the repository and returns them as a JSON array.
 ///
+/// Broken snippets are skipped, use [`load_all_report_ffi`] to find out which ones.
+///
 /// # Returns
-/// A newly allocated C string (`*mut c_char`) containing the JSON array of all snippets.
+/// A newly allocated C string (`*mut c_char`) containing the JSON array of all loadable snippets.
 /// - On success: JSON array of snippets (must be freed with `free_string_ffi`).
 /// - On failure: JSON-encoded error object (must also be freed).
 ///
@@ -111,10 +347,161 @@ pub extern "C" fn load_all_ffi() -> *mut c_char {
         Ok(repo) => repo,
         Err(e) => return e,
     };
-    let snippets = match repo.load_all() {
-        Ok(snippets) => snippets,
+    let report = match repo.load_all_lenient() {
+        Ok(report) => report,
         Err(e) => return c_str_from_str(&e.to_json())
     };
+    c_str_from_str(
+        &serde_json::to_string(&report.snippets)
+            .unwrap_or_else(|_| "{\"type\":\"Other\",\"message\":\"Serialization failed\"}"
+                .to_string())
+    )
+}
+
+/// A page of metadata returned by [`list_meta_ffi`].
+#[derive(Serialize)]
+struct MetaPage {
+    /// Number of readable snippets in the repository, across all pages.
+    total: usize,
+    entries: Vec<MetaEntry>,
+    /// Snippets whose metadata cannot be read, on every page.
+    diagnostics: Vec<LoadDiagnostic>,
+}
+
+/// A page of snippets returned by [`load_page_ffi`].
+#[derive(Serialize)]
+struct SnippetPage {
+    /// Number of snippets with readable metadata in the repository, across all pages.
+    total: usize,
+    snippets: Vec<Snippet>,
+    /// Snippets whose metadata cannot be read, on every page, and snippets of this page that cannot be loaded.
+    diagnostics: Vec<LoadDiagnostic>,
+}
+
+#[derive(Serialize)]
+struct MetaEntry {
+    slug: String,
+    meta: Meta,
+}
+
+/// Returns the range of `len` items selected by `offset` and `limit`, where a `limit` of `0` means no limit.
+fn page_range(len: usize, offset: u64, limit: u64) -> std::ops::Range<usize> {
+    let start = usize::try_from(offset).unwrap_or(usize::MAX).min(len);
+    let end = match limit {
+        0 => len,
+        limit => start.saturating_add(usize::try_from(limit).unwrap_or(usize::MAX)).min(len),
+    };
+    start..end
+}
+
+/// Lists the metadata of a page of snippets, ordered by slug, without reading their content.
+///
+/// Snippets whose metadata cannot be read are left out of the pages and reported as diagnostics.
+///
+/// # Arguments
+/// - `offset`: Number of snippets to skip.
+/// - `limit`: Maximum number of snippets to return. `0` returns all remaining ones.
+///
+/// # Returns
+/// A newly allocated C string (`*mut c_char`).
+/// - On success: JSON object `{"total": <number of snippets>, "entries": [{"slug": "<slug>", "meta": { ... }}],
+///   "diagnostics": [{"slug": "<slug>", "message": "<reason>"}]}` (must be freed with `free_string_ffi`).
+/// - On failure: JSON-encoded error object (must also be freed).
+///
+/// # Safety
+/// - Caller is responsible for freeing the returned string using `free_string_ffi`.
+#[unsafe(no_mangle)]
+pub extern "C" fn list_meta_ffi(offset: u64, limit: u64) -> *mut c_char {
+    let repo = match load_repo_ffi() {
+        Ok(repo) => repo,
+        Err(e) => return e,
+    };
+    let MetaReport { mut metas, diagnostics } = match repo.list_meta_lenient() {
+        Ok(report) => report,
+        Err(e) => return c_str_from_str(&e.to_json())
+    };
+    let total = metas.len();
+    let entries = metas.drain(page_range(total, offset, limit))
+        .map(|(slug, meta)| MetaEntry { slug, meta })
+        .collect();
+    c_str_from_str(
+        &serde_json::to_string(&MetaPage { total, entries, diagnostics })
+            .unwrap_or_else(|_| "{\"type\":\"Other\",\"message\":\"Serialization failed\"}"
+                .to_string())
+    )
+}
+
+/// Loads a page of snippets, ordered by slug.
+///
+/// Only the snippets of the page are read. Pages are counted like in [`list_meta_ffi`],
+/// snippets of the page that cannot be loaded are left out of it and reported as diagnostics.
+///
+/// # Arguments
+/// - `offset`: Number of snippets to skip.
+/// - `limit`: Maximum number of snippets to return. `0` returns all remaining ones.
+///
+/// # Returns
+/// A newly allocated C string (`*mut c_char`).
+/// - On success: JSON object `{"total": <number of snippets>, "snippets": [ ... ],
+///   "diagnostics": [{"slug": "<slug>", "message": "<reason>"}]}` (must be freed with `free_string_ffi`).
+/// - On failure: JSON-encoded error object (must also be freed).
+///
+/// # Safety
+/// - Caller is responsible for freeing the returned string using `free_string_ffi`.
+#[unsafe(no_mangle)]
+pub extern "C" fn load_page_ffi(offset: u64, limit: u64) -> *mut c_char {
+    let repo = match load_repo_ffi() {
+        Ok(repo) => repo,
+        Err(e) => return e,
+    };
+    let MetaReport { metas, mut diagnostics } = match repo.list_meta_lenient() {
+        Ok(report) => report,
+        Err(e) => return c_str_from_str(&e.to_json())
+    };
+    let total = metas.len();
+    let mut snippets = Vec::new();
+    for (slug, _) in &metas[page_range(total, offset, limit)] {
+        match repo.load(slug) {
+            Ok(snippet) => snippets.push(snippet),
+            Err(e) => diagnostics.push(LoadDiagnostic::new(slug, &e)),
+        }
+    }
+    c_str_from_str(
+        &serde_json::to_string(&SnippetPage { total, snippets, diagnostics })
+            .unwrap_or_else(|_| "{\"type\":\"Other\",\"message\":\"Serialization failed\"}"
+                .to_string())
+    )
+}
+
+/// Loads the snippets filed under a namespace and returns them as a JSON array.
+///
+/// Broken snippets are skipped, like in [`load_all_ffi`].
+///
+/// # Arguments
+/// - `namespace`: A null-terminated C string with the namespace, e.g. `rust/serde`.
+///   Snippets in its sub-namespaces are included, an empty string matches every snippet.
+///
+/// # Returns
+/// A newly allocated C string (`*mut c_char`) containing the JSON array of matching snippets.
+/// - On failure: JSON-encoded error object (must also be freed).
+///
+/// # Safety
+/// - `namespace` must be a valid, null-terminated UTF-8 string.
+/// - Caller is responsible for freeing the returned string using `free_string_ffi`.
+#[unsafe(no_mangle)]
+pub extern "C" fn load_namespace_ffi(namespace: *const c_char) -> *mut c_char {
+    let namespace = str_from_c_str(namespace);
+    let repo = match load_repo_ffi() {
+        Ok(repo) => repo,
+        Err(e) => return e,
+    };
+    let report = match repo.load_all_lenient() {
+        Ok(report) => report,
+        Err(e) => return c_str_from_str(&e.to_json())
+    };
+    let snippets: Vec<_> = report.snippets.into_iter()
+        .filter(|snippet| snippet.meta.is_in(&namespace))
+        .collect();
     c_str_from_str(
         &serde_json::to_string(&snippets)
             .unwrap_or_else(|_| "{\"type\":\"Other\",\"message\":\"Serialization failed\"}"
@@ -122,6 +509,92 @@ pub extern "C" fn load_all_ffi() -> *mut c_char {
     )
 }
 
+/// Lists the snippets linked to or from a snippet, for showing them next to it.
+///
+/// # Arguments
+/// - `name`: A null-terminated C string of the snippet's name, slug or ID.
+///
+/// # Returns
+/// A newly allocated C string (`*mut c_char`).
+/// - On success: JSON array of relations, e.g.
+///   `[{"kind": "see_also", "direction": "outgoing", "meta": { ... }}]`
+///   (must be freed with `free_string_ffi`).
+/// - On failure: JSON-encoded error object (must also be freed).
+///
+/// # Safety
+/// - `name` must be a valid, null-terminated UTF-8 string.
+/// - Caller is responsible for freeing the returned string using `free_string_ffi`.
+#[unsafe(no_mangle)]
+pub extern "C" fn related_snippets_ffi(name: *const c_char) -> *mut c_char {
+    let name = str_from_c_str(name);
+    let repo = match load_repo_ffi() {
+        Ok(repo) => repo,
+        Err(e) => return e,
+    };
+    let relations = match repo.related(&name) {
+        Ok(relations) => relations,
+        Err(e) => return c_str_from_str(&e.to_json())
+    };
+    c_str_from_str(
+        &serde_json::to_string(&relations)
+            .unwrap_or_else(|_| "{\"type\":\"Other\",\"message\":\"Serialization failed\"}"
+                .to_string())
+    )
+}
+
+/// Lists the links whose target does not exist, e.g. because it was deleted.
+///
+/// # Returns
+/// A newly allocated C string (`*mut c_char`).
+/// - On success: JSON array, e.g. `[{"slug": "<slug>", "link": {"kind": "depends_on", "target": "<slug or ID>"}}]`
+///   (must be freed with `free_string_ffi`).
+/// - On failure: JSON-encoded error object (must also be freed).
+///
+/// # Safety
+/// - Caller is responsible for freeing the returned string using `free_string_ffi`.
+#[unsafe(no_mangle)]
+pub extern "C" fn dangling_links_ffi() -> *mut c_char {
+    let repo = match load_repo_ffi() {
+        Ok(repo) => repo,
+        Err(e) => return e,
+    };
+    let dangling = match repo.dangling_links() {
+        Ok(dangling) => dangling,
+        Err(e) => return c_str_from_str(&e.to_json())
+    };
+    c_str_from_str(
+        &serde_json::to_string(&dangling)
+            .unwrap_or_else(|_| "{\"type\":\"Other\",\"message\":\"Serialization failed\"}"
+                .to_string())
+    )
+}
+
+/// Loads all snippets from the repository, together with diagnostics for the ones that are broken.
+///
+/// # Returns
+/// A newly allocated C string (`*mut c_char`) containing a JSON object:
+///   ```json
+///   {
+///     "snippets": [ ... ],
+///     "diagnostics": [ { "slug": "<slug>", "message": "<what is wrong>" } ]
+///   }
+///   ```
+/// - On failure: JSON-encoded error object (must also be freed).
+///
+/// # Safety
+/// - Caller is responsible for freeing the returned string using `free_string_ffi`.
+#[unsafe(no_mangle)]
+pub extern "C" fn load_all_report_ffi() -> *mut c_char {
+    let repo = match load_repo_ffi() {
+        Ok(repo) => repo,
+        Err(e) => return e,
+    };
+    match repo.load_all_lenient() {
+        Ok(report) => c_str_from_str(&report.to_json()),
+        Err(e) => c_str_from_str(&e.to_json())
+    }
+}
+
 /// Saves a list of snippets from a JSON array.
 ///
 /// # Arguments
@@ -129,7 +602,7 @@ pub extern "C" fn load_all_ffi() -> *mut c_char {
 ///
 /// # Returns
 /// - `true` if all snippets were saved successfully.
-/// - `false` if deserialization or saving failed.
+/// - `false` if deserialization or saving failed, in which case none of them were saved.
 ///
 /// # Safety
 /// - `snippets_json` must be a valid, null-terminated UTF-8 string.
@@ -148,14 +621,10 @@ pub extern "C" fn save_all_ffi(snippets_json: *const c_char) -> bool {
         Ok(repo) => repo,
         Err(_) => return false,
     };
-    match repo.save_all(&snippets) {
-        Ok(_) => true,
-        Err(_) => false,
-    }
-
+    repo.save_all(&snippets).is_ok()
 }
 
-/// Executes a generic Git command inside the `.nibb` directory and returns the output as JSON.
+/// Executes a generic Git command inside the Nibb directory (`$NIBB_HOME` or `~/.nibb`) and returns the output as JSON.
 ///
 /// # Arguments
 /// - `args`: A pointer to a null-terminated C string containing the Git arguments as a whitespace-separated string, e.g. `"status -s"`.
@@ -185,7 +654,9 @@ pub extern "C" fn save_all_ffi(snippets_json: *const c_char) -> bool {
 pub extern "C" fn nibb_git_generic_ffi(args: *const c_char) -> *const c_char{
     let args = str_from_c_str(args);
     let args: Vec<String> = args.split_whitespace().map(|s| s.to_string()).collect();
-    let out = nibb_git_generic(args).unwrap_or_else(|e| e.to_json());
+    let out = get_nibb_dir()
+        .and_then(|path| nibb_git_generic(&path, args))
+        .unwrap_or_else(|e| e.to_json());
     c_str_from_str(&out)
 }
 
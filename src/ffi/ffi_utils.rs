@@ -1,5 +1,9 @@
-use std::ffi::{CStr, CString};
+use std::collections::BTreeMap;
+use std::ffi::{c_void, CStr, CString};
 use std::os::raw::c_char;
+use std::sync::atomic::{AtomicBool, AtomicU64};
+use std::sync::{Arc, Mutex};
+use std::thread::JoinHandle;
 use crate::fs::get_nibb_dir;
 use crate::FSRepo;
 
@@ -20,6 +24,9 @@ pub fn str_from_c_str(s: *const c_char) -> String {
 }
 
 
+/// Opens the repository at [`get_nibb_dir()`], which honors `$NIBB_HOME`.
+///
+/// On failure, returns the JSON-encoded error as a C string.
 pub fn load_repo_ffi() -> Result<FSRepo, *mut c_char> {
     let path = match get_nibb_dir() {
         Ok(path) => path,
@@ -31,4 +38,23 @@ pub fn load_repo_ffi() -> Result<FSRepo, *mut c_char> {
         Err(e) => return Err(c_str_from_str(&e.to_json()))
     };
     Ok(repo)
-}
\ No newline at end of file
+}
+
+/// A watcher registered with [`watch_ffi`](crate::watch_ffi), forwarding events on its own thread.
+pub struct FFIWatch {
+    pub stop: Arc<AtomicBool>,
+    pub thread: JoinHandle<()>,
+}
+
+/// Watchers registered with [`watch_ffi`](crate::watch_ffi), by handle.
+pub static FFI_WATCHES: Mutex<BTreeMap<u64, FFIWatch>> = Mutex::new(BTreeMap::new());
+
+/// Next handle returned by [`watch_ffi`](crate::watch_ffi). `0` is never used, it signals failure.
+pub static NEXT_WATCH_HANDLE: AtomicU64 = AtomicU64::new(1);
+
+/// User data pointer handed back to a watch callback.
+///
+/// The caller of [`watch_ffi`](crate::watch_ffi) guarantees it may be used from the watch thread.
+pub struct UserData(pub *mut c_void);
+
+unsafe impl Send for UserData {}
@@ -4,5 +4,10 @@
 //! All returned strings must be freed using [`free_string_ffi`].
 //!
 //! Designed for use in Lua, C, Python, TypeScript, and other native environments.
+// Pointers handed to the FFI functions are documented under `# Safety`
+// and are expected to come from a C caller, not from Rust code.
+#![allow(clippy::not_unsafe_ptr_arg_deref)]
+
+#[allow(clippy::module_inception)]
 pub mod ffi;
 mod ffi_utils;
\ No newline at end of file
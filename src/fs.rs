@@ -1,8 +1,55 @@
-use std::path::PathBuf;
+use std::{env, fs};
+use std::path::{Path, PathBuf};
 use dirs::home_dir;
 use crate::result::{NibbError, NibbResult};
 
+/// Environment variable overriding the location of the main Nibb directory.
+pub const NIBB_HOME_ENV: &str = "NIBB_HOME";
+
 /// Get the path to the main Nibb directory
+///
+/// That is `$NIBB_HOME` if it is set, `~/.nibb` otherwise.
 pub fn get_nibb_dir() -> NibbResult<PathBuf> {
-    Ok(home_dir().ok_or(NibbError::NotFound("home directory".to_string()))?.join(".nibb"))
-}
\ No newline at end of file
+    resolve_nibb_dir(None)
+}
+
+/// Resolve the directory of the repository to use.
+///
+/// In order of precedence: `explicit` (e.g. the `--repo` flag of the CLI), `$NIBB_HOME` and `~/.nibb`.
+pub fn resolve_nibb_dir(explicit: Option<&Path>) -> NibbResult<PathBuf> {
+    if let Some(path) = explicit {
+        return Ok(path.to_path_buf());
+    }
+    match env::var_os(NIBB_HOME_ENV) {
+        Some(path) if !path.is_empty() => Ok(PathBuf::from(path)),
+        _ => Ok(home_dir().ok_or(NibbError::NotFound("home directory".to_string()))?.join(".nibb")),
+    }
+}
+
+/// Find the project repository for `start`, the way git finds `.git`.
+///
+/// Returns the innermost `.nibb/` directory in `start` or one of its ancestors,
+/// skipping the global directory from [`get_nibb_dir()`].
+pub fn find_project_dir<P: AsRef<Path>>(start: P) -> Option<PathBuf> {
+    let global = get_nibb_dir().ok().and_then(|dir| dir.canonicalize().ok());
+    start.as_ref()
+        .ancestors()
+        .map(|dir| dir.join(".nibb"))
+        .filter(|candidate| candidate.is_dir())
+        .find(|candidate| candidate.canonicalize().ok() != global)
+}
+
+/// Recursively copies the directory `from` to `to`, creating `to` if missing.
+pub(crate) fn copy_dir_all(from: &Path, to: &Path) -> NibbResult<()> {
+    fs::create_dir_all(to)?;
+    for entry in fs::read_dir(from)? {
+        let entry = entry?;
+        let target = to.join(entry.file_name());
+        if entry.file_type()?.is_dir() {
+            copy_dir_all(&entry.path(), &target)?;
+        } else {
+            fs::copy(entry.path(), target)?;
+        }
+    }
+    Ok(())
+}
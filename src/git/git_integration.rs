@@ -1,15 +1,17 @@
 use std::path::{Path, PathBuf};
+use std::sync::{Mutex, MutexGuard};
 use std::process::Command;
-use git2::{Repository, Signature};
+use std::time::Duration;
+use git2::{IndexAddOption, Repository, Signature};
 use serde_json::json;
-use walkdir::WalkDir;
 use crate::config::config::Config;
+use crate::lock::RepoLock;
 use crate::result::{NibbError, NibbResult};
-use crate::{get_nibb_dir, Snippet};
+use crate::Snippet;
 
 pub struct GitRepo {
-    repo: Repository,
-    root: PathBuf,
+    /// Behind a lock, so an [`FSRepo`](crate::FSRepo) can be shared between threads.
+    repo: Mutex<Repository>,
 }
 
 impl GitRepo {
@@ -17,43 +19,45 @@ impl GitRepo {
         let path = path.as_ref();
         let repo = match Repository::open(path) {
             Ok(r) => r,
-            Err(e) => Repository::init(path)?,
+            Err(_) => Repository::init(path)?,
         };
         Ok(GitRepo {
-            repo,
-            root: path.to_path_buf(),
+            repo: Mutex::new(repo),
         })
     }
+    fn repo(&self) -> Result<MutexGuard<'_, Repository>, git2::Error> {
+        self.repo.lock().map_err(|_| git2::Error::from_str("git repository poisoned"))
+    }
     pub fn add_and_commit(&self, snippet: &Snippet, cfg: &Config) -> Result<(), git2::Error> {
         let rel_path = PathBuf::from("snippets").join(snippet.meta.get_slug());
-        let abs_path = self.repo.path().parent().unwrap().join(&rel_path);
-
         let message = &format_commit_msg(&cfg.git.commit_message, snippet);
-
-        let mut index = self.repo.index()?;
-
-        for entry in WalkDir::new(&abs_path)
-            .into_iter()
-            .filter_map(|e| e.ok())
-            .filter(|e| e.file_type().is_file())
-        {
-            let abs_file_path = entry.path();
-            let rel_repo_path = abs_file_path.strip_prefix(self.repo.path().parent().unwrap()).unwrap();
-            index.add_path(rel_repo_path)?;
-        }
+        self.commit_paths(&[rel_path], message, cfg)
+    }
+    /// Stages everything below `rel_paths` (relative to the repository root) and commits it.
+    ///
+    /// New and modified files are added, files that no longer exist are removed from the index,
+    /// so moved and deleted snippets are committed correctly.
+    pub fn commit_paths(&self, rel_paths: &[PathBuf], message: &str, cfg: &Config) -> Result<(), git2::Error> {
+        let repo = self.repo()?;
+        let mut index = repo.index()?;
+        let pathspecs: Vec<String> = rel_paths.iter()
+            .map(|p| p.to_string_lossy().replace('\\', "/"))
+            .collect();
+        index.add_all(pathspecs.iter(), IndexAddOption::DEFAULT, None)?;
+        index.update_all(pathspecs.iter(), None)?;
 
         index.write()?;
         let oid = index.write_tree()?;
-        let tree = self.repo.find_tree(oid)?;
+        let tree = repo.find_tree(oid)?;
         let sig = Signature::now(&cfg.git.author, &cfg.git.author_email)?;
 
-        let parent_commit = self.repo.head()
+        let parent_commit = repo.head()
             .ok()
             .and_then(|h| h.target())
-            .and_then(|oid| self.repo.find_commit(oid).ok());
+            .and_then(|oid| repo.find_commit(oid).ok());
 
         if let Some(parent) = parent_commit {
-            self.repo.commit(
+            repo.commit(
                 Some("HEAD"),
                 &sig,
                 &sig,
@@ -62,7 +66,7 @@ impl GitRepo {
                 &[&parent],
             )?;
         } else {
-            self.repo.commit(
+            repo.commit(
                 Some("HEAD"),
                 &sig,
                 &sig,
@@ -75,7 +79,8 @@ impl GitRepo {
         Ok(())
     }
     pub fn push(&self, remote_name: &str, branch: &str) -> Result<(), git2::Error> {
-        let mut remote = self.repo.find_remote(remote_name)?;
+        let repo = self.repo()?;
+        let mut remote = repo.find_remote(remote_name)?;
         remote.push(&[format!("refs/head/{}", branch)], None)?;
         Ok(())
     }
@@ -91,9 +96,10 @@ fn format_commit_msg(msg: &str, snippet: &Snippet) -> String {
         .replace("{created}", &snippet.meta.created.to_string())
 }
 
-/// Executes a generic Git command inside the `.nibb` directory.
+/// Executes a generic Git command inside the repository at `base_dir`.
 ///
 /// # Arguments
+/// - `base_dir`: The repository directory, e.g. from [`get_nibb_dir()`](crate::get_nibb_dir).
 /// - `args`: A vector of strings representing the Git command arguments, e.g. `["status", "-s"]`.
 ///
 /// # Returns
@@ -104,22 +110,23 @@ fn format_commit_msg(msg: &str, snippet: &Snippet) -> String {
 /// - Ensures only allowed Git subcommands and safe path arguments are accepted.
 ///
 /// # Example
-/// ```rust
-/// let output = nibb_git_generic(vec!["status".into(), "-s".into()])?;
+/// ```rust,ignore
+/// let output = nibb_git_generic(&get_nibb_dir()?, vec!["status".into(), "-s".into()])?;
 /// println!("{}", output);
 /// ```
-pub fn nibb_git_generic(args: Vec<String>) -> NibbResult<String> {
-    validate_git_args(&args).map_err(|e| NibbError::NibbGitError(e))?;
-    let path = get_nibb_dir()?;
-    let old_cwd = std::env::current_dir()?;
-    std::env::set_current_dir(&path)?;
+pub fn nibb_git_generic(base_dir: &Path, args: Vec<String>) -> NibbResult<String> {
+    validate_git_args(&args).map_err(NibbError::NibbGitError)?;
+    let config = Config::read(&base_dir.join("config.toml"))?;
+    let _lock = RepoLock::exclusive(
+        &RepoLock::path(base_dir),
+        Duration::from_millis(config.lock.timeout_ms),
+    )?;
 
     let output = Command::new("git")
+        .current_dir(base_dir)
         .args(&args)
         .output();
 
-    std::env::set_current_dir(old_cwd)?;
-
     match output {
         Ok(out) => {
             let stdout = String::from_utf8_lossy(&out.stdout).to_string();
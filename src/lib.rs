@@ -4,24 +4,66 @@ pub mod fs;
 pub mod ffi;
 mod git;
 mod config;
+mod lock;
+mod crypto;
+mod migration;
 
 // === Lib ===
 // ---
-pub use fs::get_nibb_dir;
+pub use fs::{find_project_dir, get_nibb_dir, resolve_nibb_dir, NIBB_HOME_ENV};
+pub use crypto::NIBB_PASSPHRASE_ENV;
+
+pub use result::{NibbError, NibbResult};
+
+pub use migration::{CONFIG_SCHEMA_VERSION, META_SCHEMA_VERSION};
 
 pub use snippets::repo::{FSRepo, SnippetRepository};
 
-pub use snippets::snippet::{Meta, Visibility, Snippet};
+pub use snippets::memory::MemoryRepo;
+
+pub use snippets::layered::LayeredRepo;
+
+#[cfg(feature = "sqlite")]
+pub use snippets::sqlite::SqliteRepo;
+
+pub use snippets::snippet::{slugify_path, BinaryContent, Meta, Visibility, Snippet, SnippetContent, SnippetFile};
 
 pub use snippets::file_type::FileType;
 
+pub use snippets::conflict::{Conflict, ExpectedVersion};
+
+pub use snippets::links::{DanglingLink, LinkDirection, LinkKind, Relation, SnippetLink};
+
+pub use snippets::history::Revision;
+
+pub use snippets::trash::TrashEntry;
+
+pub use snippets::report::{LoadDiagnostic, LoadReport, MetaReport};
+pub use snippets::fsck::{FsckCategory, FsckIssue, FsckReport};
+
+pub use snippets::watch::{SnippetEvent, SnippetWatcher};
+
 pub use ffi::ffi::{
     load_all_ffi,
+    load_all_report_ffi,
+    load_namespace_ffi,
+    list_meta_ffi,
+    load_page_ffi,
+    related_snippets_ffi,
+    dangling_links_ffi,
     save_all_ffi,
     load_snippet_ffi,
     save_snippet_ffi,
+    save_snippet_if_unmodified_ffi,
     free_string_ffi,
     delete_snippet_ffi,
+    rename_snippet_ffi,
+    list_trash_ffi,
+    restore_trash_ffi,
+    empty_trash_ffi,
+    watch_ffi,
+    unwatch_ffi,
+    SnippetEventCallback,
     nibb_git_generic_ffi
 };
 // ---
\ No newline at end of file
@@ -0,0 +1,77 @@
+use std::fs::{File, OpenOptions, TryLockError};
+use std::path::{Path, PathBuf};
+use std::thread;
+use std::time::{Duration, Instant};
+use crate::result::{NibbError, NibbResult};
+
+/// Name of the lock file inside a repository's base directory.
+const LOCK_FILE_NAME: &str = "nibb.lock";
+
+/// Interval between two attempts to acquire a contended lock.
+const RETRY_INTERVAL: Duration = Duration::from_millis(10);
+
+/// Advisory lock on a repository, shared between processes.
+///
+/// Wraps an OS file lock on a lock file inside the repository. Nibb processes
+/// (CLI, editor plugins over FFI, ...) take a shared lock to read and an exclusive
+/// lock to write. The lock is released when the guard is dropped.
+pub(crate) struct RepoLock {
+    _file: Option<File>,
+}
+
+impl RepoLock {
+    /// Returns the path of the lock file of the repository at `base_dir`.
+    pub(crate) fn path(base_dir: &Path) -> PathBuf {
+        base_dir.join(LOCK_FILE_NAME)
+    }
+    /// Acquires a shared lock on `path`, waiting at most `timeout`.
+    pub(crate) fn shared(path: &Path, timeout: Duration) -> NibbResult<Self> {
+        Self::acquire(path, timeout, File::try_lock_shared)
+    }
+    /// Acquires an exclusive lock on `path`, waiting at most `timeout`.
+    pub(crate) fn exclusive(path: &Path, timeout: Duration) -> NibbResult<Self> {
+        Self::acquire(path, timeout, File::try_lock)
+    }
+    /// Acquires a shared lock on `path` like [`shared()`](Self::shared), but without creating the lock file
+    /// or opening it for writing, for repositories opened read-only.
+    ///
+    /// Nothing is locked if there is no lock file, since no nibb process has written to the repository then.
+    pub(crate) fn shared_read_only(path: &Path, timeout: Duration) -> NibbResult<Self> {
+        match File::open(path) {
+            Ok(file) => Self::wait_for(file, path, timeout, File::try_lock_shared),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Self { _file: None }),
+            Err(e) => Err(e.into()),
+        }
+    }
+    fn acquire(
+        path: &Path,
+        timeout: Duration,
+        try_lock: fn(&File) -> Result<(), TryLockError>,
+    ) -> NibbResult<Self> {
+        let file = OpenOptions::new()
+            .create(true)
+            .truncate(false)
+            .write(true)
+            .open(path)?;
+        Self::wait_for(file, path, timeout, try_lock)
+    }
+    /// Locks `file`, retrying until `timeout` has passed.
+    fn wait_for(
+        file: File,
+        path: &Path,
+        timeout: Duration,
+        try_lock: fn(&File) -> Result<(), TryLockError>,
+    ) -> NibbResult<Self> {
+        let deadline = Instant::now() + timeout;
+        loop {
+            match try_lock(&file) {
+                Ok(()) => return Ok(Self { _file: Some(file) }),
+                Err(TryLockError::Error(e)) => return Err(e.into()),
+                Err(TryLockError::WouldBlock) if Instant::now() >= deadline => {
+                    return Err(NibbError::LockTimeout(format!("{:?} after {}ms", path, timeout.as_millis())));
+                }
+                Err(TryLockError::WouldBlock) => thread::sleep(RETRY_INTERVAL),
+            }
+        }
+    }
+}
@@ -0,0 +1,79 @@
+use std::path::Path;
+use toml::{Table, Value};
+use crate::config::config::GitConfig;
+use crate::result::{NibbError, NibbResult};
+
+/// Upgrades a TOML document by one schema version.
+type Migration = fn(&mut Table) -> NibbResult<()>;
+
+/// Migrations of `meta.toml`, `META_MIGRATIONS[i]` upgrades version `i + 1`.
+///
+/// - `1`: files written before versioning, without `schema_version`
+/// - `2`: adds `schema_version`, fills in `visibility`. A missing `id` is filled in
+///   by [`Meta::ensure_id()`](crate::Meta) afterwards.
+const META_MIGRATIONS: &[Migration] = &[meta_v1_to_v2];
+
+/// Migrations of `config.toml`, `CONFIG_MIGRATIONS[i]` upgrades version `i + 1`.
+///
+/// - `1`: files written before versioning, without `schema_version`
+/// - `2`: adds `schema_version`, fills in the `[git]` section
+const CONFIG_MIGRATIONS: &[Migration] = &[config_v1_to_v2];
+
+/// Schema version of `meta.toml` files written by this version of nibb.
+pub const META_SCHEMA_VERSION: u32 = META_MIGRATIONS.len() as u32 + 1;
+
+/// Schema version of `config.toml` files written by this version of nibb.
+pub const CONFIG_SCHEMA_VERSION: u32 = CONFIG_MIGRATIONS.len() as u32 + 1;
+
+/// Upgrades a parsed `meta.toml` to [`META_SCHEMA_VERSION`].
+///
+/// Returns whether anything was upgraded.
+pub(crate) fn migrate_meta(table: &mut Table, path: &Path) -> NibbResult<bool> {
+    migrate(table, META_MIGRATIONS, path)
+}
+
+/// Upgrades a parsed `config.toml` to [`CONFIG_SCHEMA_VERSION`].
+///
+/// Returns whether anything was upgraded.
+pub(crate) fn migrate_config(table: &mut Table, path: &Path) -> NibbResult<bool> {
+    migrate(table, CONFIG_MIGRATIONS, path)
+}
+
+/// Applies the migrations from the `schema_version` of `table` up to the latest one.
+///
+/// Files without `schema_version` are version 1. Files of a newer version are refused,
+/// since they may contain fields this version of nibb would drop.
+fn migrate(table: &mut Table, migrations: &[Migration], path: &Path) -> NibbResult<bool> {
+    let current = migrations.len() as u32 + 1;
+    let version = match table.get("schema_version") {
+        None => 1,
+        Some(Value::Integer(version)) => u32::try_from(*version).ok().filter(|version| *version > 0)
+            .ok_or(NibbError::UnsupportedSchema(format!("invalid schema_version {} in {:?}", version, path)))?,
+        Some(other) => {
+            return Err(NibbError::UnsupportedSchema(format!("invalid schema_version {} in {:?}", other, path)));
+        }
+    };
+    if version > current {
+        return Err(NibbError::UnsupportedSchema(format!(
+            "{:?} has schema version {}, but this version of nibb only supports up to {}",
+            path, version, current
+        )));
+    }
+    for migration in &migrations[(version - 1) as usize..] {
+        migration(table)?;
+    }
+    table.insert("schema_version".to_string(), Value::Integer(i64::from(current)));
+    Ok(version < current)
+}
+
+fn meta_v1_to_v2(table: &mut Table) -> NibbResult<()> {
+    table.entry("visibility").or_insert_with(|| Value::String("private".to_string()));
+    Ok(())
+}
+
+fn config_v1_to_v2(table: &mut Table) -> NibbResult<()> {
+    if !table.contains_key("git") {
+        table.insert("git".to_string(), Value::try_from(GitConfig::default())?);
+    }
+    Ok(())
+}
@@ -1,6 +1,7 @@
 use std::{io};
 use serde::{Serialize};
 use thiserror::Error;
+use crate::snippets::conflict::Conflict;
 
 #[derive(Debug, Error)]
 pub enum NibbError {
@@ -22,6 +23,13 @@ pub enum NibbError {
     #[error("Git error: {0}")]
     NibbGitError(String),
 
+    #[error("Watch error: {0}")]
+    Watch(#[from] notify::Error),
+
+    #[cfg(feature = "sqlite")]
+    #[error("SQLite error: {0}")]
+    Sqlite(#[from] rusqlite::Error),
+
     #[error("Unsupported language extension: {0}")]
     UnsupportedLanguage(String),
 
@@ -34,6 +42,33 @@ pub enum NibbError {
     #[error("Invalid snippet slug: {0}")]
     InvalidSlug(String),
 
+    #[error("Invalid snippet file name: {0}")]
+    InvalidFileName(String),
+
+    #[error("Invalid snippet link: {0}")]
+    InvalidLink(String),
+
+    #[error("Read-only: {0}")]
+    ReadOnly(String),
+
+    #[error("Conflict: {} was modified since it was loaded", .0.slug)]
+    Conflict(Box<Conflict>),
+
+    #[error("Timed out waiting for repository lock: {0}")]
+    LockTimeout(String),
+
+    #[error("Unsupported schema version: {0}")]
+    UnsupportedSchema(String),
+
+    #[error("Snippet is locked: {0}")]
+    Locked(String),
+
+    #[error("Encryption error: {0}")]
+    Encryption(String),
+
+    #[error("{error} (rolling back failed as well: {rollback})")]
+    RollbackFailed { error: Box<NibbError>, rollback: Box<NibbError> },
+
     #[error("Other: {0}")]
     Other(String),
 }
@@ -59,9 +94,21 @@ pub enum NibbFFIError {
     MissingField(&'static str),
     NotFound(String),
     InvalidSlug(String),
+    InvalidFileName(String),
+    InvalidLink(String),
+    ReadOnly(String),
+    Conflict(Box<Conflict>),
+    LockTimeout(String),
+    UnsupportedSchema(String),
+    Locked(String),
+    Encryption(String),
+    RollbackFailed(String),
     FFIError(String),
     GitError(String),
     NibbGitError(String),
+    Watch(String),
+    #[cfg(feature = "sqlite")]
+    Sqlite(String),
     Other(String),
 }
 
@@ -83,9 +130,21 @@ impl From<&NibbError> for NibbFFIError {
             NibbError::MissingField(f) => Self::MissingField(f),
             NibbError::NotFound(s) => Self::NotFound(s.to_string()),
             NibbError::InvalidSlug(s) => Self::InvalidSlug(s.to_string()),
+            NibbError::InvalidFileName(s) => Self::InvalidFileName(s.to_string()),
+            NibbError::InvalidLink(s) => Self::InvalidLink(s.to_string()),
+            NibbError::ReadOnly(s) => Self::ReadOnly(s.to_string()),
+            NibbError::Conflict(c) => Self::Conflict(c.clone()),
+            NibbError::LockTimeout(s) => Self::LockTimeout(s.to_string()),
+            NibbError::UnsupportedSchema(s) => Self::UnsupportedSchema(s.to_string()),
+            NibbError::Locked(s) => Self::Locked(s.to_string()),
+            NibbError::Encryption(s) => Self::Encryption(s.to_string()),
+            NibbError::RollbackFailed { .. } => Self::RollbackFailed(err.to_string()),
             NibbError::Other(s) => Self::Other(s.to_string()),
             NibbError::GitError(e) => Self::GitError(e.to_string()),
             NibbError::NibbGitError(e) => Self::NibbGitError(e.to_string()),
+            NibbError::Watch(e) => Self::Watch(e.to_string()),
+            #[cfg(feature = "sqlite")]
+            NibbError::Sqlite(e) => Self::Sqlite(e.to_string()),
         }
     }
 }
\ No newline at end of file
@@ -0,0 +1,53 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use crate::result::{NibbError, NibbResult};
+use crate::snippets::snippet::Snippet;
+
+/// Version of a snippet a caller expects to overwrite, see
+/// [`save_if_unmodified()`](crate::SnippetRepository::save_if_unmodified).
+///
+/// Serialized externally tagged, e.g. `{"modified": "2025-01-01T12:00:00Z"}` or `{"hash": "<hex>"}`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ExpectedVersion {
+    /// [`Meta::modified`](crate::Meta::modified) of the snippet as it was loaded.
+    Modified(DateTime<Utc>),
+    /// [`Snippet::version_hash()`] of the snippet as it was loaded.
+    Hash(String),
+}
+
+impl ExpectedVersion {
+    /// Returns whether `current`, the stored version of the snippet, is the expected one.
+    pub fn matches(&self, current: &Snippet) -> bool {
+        match self {
+            Self::Modified(modified) => current.meta.modified == *modified,
+            Self::Hash(hash) => current.version_hash() == *hash,
+        }
+    }
+}
+
+/// Both versions of a snippet that was changed by someone else since it was loaded,
+/// carried by [`NibbError::Conflict`](crate::NibbError::Conflict).
+#[derive(Debug, Clone, Serialize)]
+pub struct Conflict {
+    /// Slug of the snippet.
+    pub slug: String,
+    /// The version currently stored, `None` if the snippet was deleted in the meantime.
+    pub current: Option<Snippet>,
+    /// The version that was about to be saved.
+    pub attempted: Snippet,
+}
+
+/// Checks `current`, the stored version of `attempted`, against `expected`.
+///
+/// Fails with [`NibbError::Conflict`] if it is not the expected version or if there is none.
+pub(crate) fn check_unmodified(attempted: &Snippet, current: Option<Snippet>, expected: &ExpectedVersion) -> NibbResult<()> {
+    if current.as_ref().is_some_and(|current| expected.matches(current)) {
+        return Ok(());
+    }
+    Err(NibbError::Conflict(Box::new(Conflict {
+        slug: attempted.meta.get_slug(),
+        current,
+        attempted: attempted.clone(),
+    })))
+}
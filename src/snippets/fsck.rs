@@ -0,0 +1,92 @@
+use std::fmt;
+use std::path::PathBuf;
+use serde::Serialize;
+
+/// Outcome of [`FSRepo::fsck()`](crate::FSRepo::fsck).
+#[derive(Debug, Default, Serialize)]
+pub struct FsckReport {
+    /// Every inconsistency that was found, in the order the repository was walked.
+    pub issues: Vec<FsckIssue>,
+}
+
+impl FsckReport {
+    /// Returns `true` if no issue is left, either because none was found or all were repaired.
+    pub fn is_clean(&self) -> bool {
+        self.issues.iter().all(|issue| issue.repaired)
+    }
+    /// Returns the issues that were not repaired.
+    pub fn unrepaired(&self) -> impl Iterator<Item = &FsckIssue> {
+        self.issues.iter().filter(|issue| !issue.repaired)
+    }
+    pub fn to_json(&self) -> String {
+        serde_json::to_string(self)
+            .unwrap_or_else(|_| "{\"type\":\"Other\",\"message\":\"Serialization failed\"}".to_string())
+    }
+}
+
+/// A single inconsistency in the layout of a repository.
+#[derive(Debug, Clone, Serialize)]
+pub struct FsckIssue {
+    /// Kind of the inconsistency.
+    pub category: FsckCategory,
+    /// Path of the affected entry, relative to the repository root.
+    pub path: PathBuf,
+    /// Human-readable description of the problem.
+    pub message: String,
+    /// Whether the issue was repaired.
+    pub repaired: bool,
+}
+
+impl FsckIssue {
+    pub fn new(category: FsckCategory, path: PathBuf, message: impl Into<String>) -> Self {
+        Self {
+            category,
+            path,
+            message: message.into(),
+            repaired: false,
+        }
+    }
+}
+
+/// Kinds of inconsistencies found by [`FSRepo::fsck()`](crate::FSRepo::fsck).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FsckCategory {
+    /// `meta.toml` or `files.toml` is missing or cannot be parsed.
+    /// Repaired by moving the snippet directory to `lost+found/`.
+    UnreadableMeta,
+    /// The directory name differs from the slug of the snippet's name.
+    /// Repaired by renaming the directory, unless the slug is taken.
+    SlugMismatch,
+    /// There is no content file, or a file listed in `files.toml` does not exist. Never repaired.
+    MissingContent,
+    /// There are several `content.*` files.
+    /// Repaired by removing the ones not matching `meta.language`, if one matches.
+    DuplicateContent,
+    /// The extension of the only `content.*` file does not match `meta.language`.
+    /// Repaired by renaming the file.
+    ExtensionMismatch,
+    /// A file or directory that is not part of the layout.
+    /// Repaired by moving it to `lost+found/`.
+    StrayFile,
+    /// A link in `meta.toml` points to a snippet that does not exist. Never repaired.
+    DanglingLink,
+    /// `meta.toml` was written with an older schema, see [`FSRepo::migrate()`](crate::FSRepo::migrate).
+    /// Repaired by upgrading it.
+    OutdatedSchema,
+}
+
+impl fmt::Display for FsckCategory {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FsckCategory::UnreadableMeta => write!(f, "unreadable_meta"),
+            FsckCategory::SlugMismatch => write!(f, "slug_mismatch"),
+            FsckCategory::MissingContent => write!(f, "missing_content"),
+            FsckCategory::DuplicateContent => write!(f, "duplicate_content"),
+            FsckCategory::ExtensionMismatch => write!(f, "extension_mismatch"),
+            FsckCategory::StrayFile => write!(f, "stray_file"),
+            FsckCategory::DanglingLink => write!(f, "dangling_link"),
+            FsckCategory::OutdatedSchema => write!(f, "outdated_schema"),
+        }
+    }
+}
@@ -0,0 +1,84 @@
+use chrono::{DateTime, NaiveDateTime, Utc};
+use serde::{Deserialize, Serialize};
+use crate::result::NibbResult;
+use crate::snippets::snippet::{slugify_path, Meta, Snippet};
+
+/// `chrono` format of revision identifiers.
+///
+/// Identifiers sort lexicographically in the order the revisions were recorded.
+pub(crate) const REVISION_ID_FORMAT: &str = "%Y%m%dT%H%M%S%.9fZ";
+
+/// A previous version of a snippet, recorded in the `history/` directory of a repository.
+///
+/// A revision is taken every time a snippet is overwritten or deleted, and holds
+/// the [`Meta`](crate::Meta) and content the snippet had right before that.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Revision {
+    /// Identifier of the revision, e.g. `20250101T120000.000000000Z`.
+    pub id: String,
+    /// Slug of the snippet the revision belongs to.
+    pub slug: String,
+    /// Timestamp indicating when the revision was recorded (UTC).
+    pub recorded: DateTime<Utc>,
+}
+
+impl Revision {
+    /// Creates a new revision of `slug`, recorded at `recorded`.
+    pub fn new(slug: &str, recorded: DateTime<Utc>) -> Self {
+        Self {
+            id: recorded.format(REVISION_ID_FORMAT).to_string(),
+            slug: slug.to_string(),
+            recorded,
+        }
+    }
+    /// Reconstructs a revision from its identifier.
+    ///
+    /// Returns `None` if `id` is not a valid revision identifier.
+    pub fn from_id(slug: &str, id: &str) -> Option<Self> {
+        let recorded = NaiveDateTime::parse_from_str(id, REVISION_ID_FORMAT).ok()?.and_utc();
+        Some(Self {
+            id: id.to_string(),
+            slug: slug.to_string(),
+            recorded,
+        })
+    }
+    /// Returns a revision of `slug` recorded at `recorded`, or a nanosecond later, and so on,
+    /// whichever identifier is not `taken` first.
+    ///
+    /// Keeps revisions apart that would otherwise share an identifier, e.g. when the history
+    /// of a renamed snippet is merged with the one left behind under the new slug.
+    pub(crate) fn first_free(
+        slug: &str,
+        mut recorded: DateTime<Utc>,
+        mut taken: impl FnMut(&str) -> NibbResult<bool>,
+    ) -> NibbResult<Self> {
+        let mut revision = Self::new(slug, recorded);
+        while taken(&revision.id)? {
+            recorded += chrono::Duration::nanoseconds(1);
+            revision = Self::new(slug, recorded);
+        }
+        Ok(revision)
+    }
+}
+
+/// Prepares a revision of `slug` to be restored over `current`, the metadata stored under `slug` now.
+///
+/// A revision recorded before the snippet was renamed still carries its old name and namespace,
+/// so it takes over the current ones and is written back to `slug`, not to the old slug.
+/// Without a current snippet, the name and namespace are derived from `slug` if the revision's do not produce it.
+pub(crate) fn restore_under(slug: &str, mut snippet: Snippet, current: Option<&Meta>) -> Snippet {
+    let slug = slugify_path(slug);
+    match current {
+        Some(current) => {
+            snippet.meta.name = current.name.clone();
+            snippet.meta.namespace = current.namespace.clone();
+        }
+        None if snippet.meta.get_slug() != slug => {
+            let (namespace, name) = slug.rsplit_once('/').unwrap_or(("", &slug));
+            snippet.meta.namespace = namespace.to_string();
+            snippet.meta.name = name.to_string();
+        }
+        None => {}
+    }
+    snippet
+}
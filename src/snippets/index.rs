@@ -0,0 +1,76 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+use std::time::UNIX_EPOCH;
+use serde::{Deserialize, Serialize};
+use crate::result::NibbResult;
+use crate::snippets::snippet::Meta;
+
+/// Version of the `index.json` format. Indexes with another version are rebuilt.
+const INDEX_VERSION: u32 = 2;
+
+/// Cache of the [`Meta`] of every snippet in a repository, stored as `index.json`.
+///
+/// Each entry remembers the modification time and size of the `meta.toml` it was read from,
+/// so stale entries can be detected with a `stat` instead of parsing the file again.
+#[derive(Debug, Serialize, Deserialize)]
+pub(crate) struct SnippetIndex {
+    version: u32,
+    pub(crate) entries: BTreeMap<String, IndexEntry>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub(crate) struct IndexEntry {
+    pub(crate) meta: Meta,
+    pub(crate) stamp: FileStamp,
+}
+
+/// Modification time (nanoseconds since the Unix epoch) and size of a file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub(crate) struct FileStamp {
+    modified: u64,
+    len: u64,
+}
+
+impl Default for SnippetIndex {
+    fn default() -> Self {
+        Self {
+            version: INDEX_VERSION,
+            entries: BTreeMap::new(),
+        }
+    }
+}
+
+impl SnippetIndex {
+    /// Reads the index at `path`.
+    ///
+    /// Returns `None` if it is missing, unreadable or written in another format version.
+    pub(crate) fn read(path: &Path) -> Option<Self> {
+        let content = fs::read_to_string(path).ok()?;
+        let index: Self = serde_json::from_str(&content).ok()?;
+        (index.version == INDEX_VERSION).then_some(index)
+    }
+    /// Writes the index to `path`, replacing the previous file atomically.
+    pub(crate) fn write(&self, path: &Path) -> NibbResult<()> {
+        let dir = path.parent().unwrap_or(Path::new("."));
+        let mut file = tempfile::NamedTempFile::new_in(dir)?;
+        serde_json::to_writer(&mut file, self)?;
+        file.persist(path).map_err(|e| e.error)?;
+        Ok(())
+    }
+}
+
+impl FileStamp {
+    /// Reads the stamp of the file at `path`.
+    pub(crate) fn of(path: &Path) -> std::io::Result<Self> {
+        let metadata = fs::metadata(path)?;
+        let modified = metadata.modified()?
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_nanos() as u64)
+            .unwrap_or_default();
+        Ok(Self {
+            modified,
+            len: metadata.len(),
+        })
+    }
+}
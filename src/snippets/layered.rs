@@ -0,0 +1,302 @@
+use std::collections::{BTreeMap, BTreeSet};
+use uuid::Uuid;
+use crate::result::{NibbError, NibbResult};
+use crate::snippets::conflict::{check_unmodified, ExpectedVersion};
+use crate::snippets::history::{restore_under, Revision};
+use crate::snippets::report::{LoadReport, MetaReport};
+use crate::snippets::repo::SnippetRepository;
+use crate::snippets::snippet::{slugify_path, Meta, Snippet};
+use crate::snippets::trash::TrashEntry;
+
+/// A backend mounted into a [`LayeredRepo`].
+struct Layer {
+    name: String,
+    repo: Box<dyn SnippetRepository>,
+}
+
+/// Implementation of [`SnippetRepository`] that merges several backends in priority order.
+///
+/// The top layer is the only writable one, e.g. a personal [`FSRepo`](crate::FSRepo) in `~/.nibb`.
+/// Layers mounted with [`with_layer()`](Self::with_layer) are read-only, e.g. a checked-out team library
+/// opened with [`FSRepo::open_read_only()`](crate::FSRepo::open_read_only).
+/// A snippet shadows the ones with the same slug in the layers below it.
+///
+/// Saving a snippet of a lower layer copies it up into the top layer, which then overrides it.
+/// Deleting the override reveals the lower snippet again. Snippets of lower layers cannot be
+/// deleted or renamed. Every loaded snippet carries the name of its layer in [`Snippet::layer`].
+///
+/// Links of saved snippets may point to snippets of any layer, e.g. an override of a team snippet
+/// to the team helpers it depends on.
+pub struct LayeredRepo {
+    /// Highest priority first, only the first one is written to.
+    layers: Vec<Layer>,
+}
+
+impl LayeredRepo {
+    /// Creates a [`LayeredRepo`] whose only layer, named `name`, is the writable `repo`.
+    pub fn new<R: SnippetRepository + 'static>(name: &str, repo: R) -> Self {
+        Self { layers: vec![Layer { name: name.to_string(), repo: Box::new(repo) }] }
+    }
+    /// Mounts `repo` read-only below the layers added so far.
+    ///
+    /// Nothing is written to it through the [`LayeredRepo`]. An [`FSRepo`](crate::FSRepo) should be opened with
+    /// [`open_read_only()`](crate::FSRepo::open_read_only), since [`FSRepo::new()`](crate::FSRepo::new) sets up the directory.
+    pub fn with_layer<R: SnippetRepository + 'static>(mut self, name: &str, repo: R) -> Self {
+        self.layers.push(Layer { name: name.to_string(), repo: Box::new(repo) });
+        self
+    }
+    /// Returns the names of the layers, highest priority first.
+    pub fn layer_names(&self) -> Vec<&str> {
+        self.layers.iter().map(|layer| layer.name.as_str()).collect()
+    }
+    fn top(&self) -> &dyn SnippetRepository {
+        self.layers[0].repo.as_ref()
+    }
+    /// Calls `load` on each layer from the top down and returns the first snippet found,
+    /// tagged with its layer, along with the index of that layer.
+    ///
+    /// Only [`NibbError::NotFound`] moves on to the next layer, other errors are returned.
+    fn resolve(&self, load: impl Fn(&dyn SnippetRepository) -> NibbResult<Snippet>) -> NibbResult<(usize, Snippet)> {
+        let mut not_found = None;
+        for (i, layer) in self.layers.iter().enumerate() {
+            match load(layer.repo.as_ref()) {
+                Ok(mut snippet) => {
+                    snippet.layer = Some(layer.name.clone());
+                    return Ok((i, snippet));
+                }
+                Err(NibbError::NotFound(e)) => {
+                    not_found.get_or_insert(e);
+                }
+                Err(e) => return Err(e),
+            }
+        }
+        Err(NibbError::NotFound(not_found.unwrap_or_default()))
+    }
+    /// Returns the index of the topmost layer holding `slug`, or of the top layer if none does.
+    fn layer_of(&self, slug: &str) -> NibbResult<usize> {
+        match self.resolve(|repo| repo.load(slug)) {
+            Ok((i, _)) => Ok(i),
+            Err(NibbError::NotFound(_)) => Ok(0),
+            Err(e) => Err(e),
+        }
+    }
+    /// Prepares `snippet` to be written to the top layer.
+    ///
+    /// A snippet without an ID that overrides one of a lower layer takes over its ID.
+    fn copy_up(&self, snippet: &Snippet) -> NibbResult<Snippet> {
+        let mut copy = snippet.clone();
+        copy.layer = None;
+        if copy.meta.id.is_nil() {
+            let slug = copy.meta.get_slug();
+            match self.resolve(|repo| repo.load(&slug)) {
+                Ok((i, lower)) if i > 0 => copy.meta.id = lower.meta.id,
+                Ok(_) | Err(NibbError::NotFound(_)) => {}
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(copy)
+    }
+    /// Returns the metadata of the snippets of the read-only layers, if any of `snippets` has links.
+    ///
+    /// The top layer checks the links against its own snippets and these.
+    fn linkable(&self, snippets: &[Snippet]) -> NibbResult<Vec<Meta>> {
+        let mut metas = Vec::new();
+        if snippets.iter().all(|snippet| snippet.meta.links.is_empty()) {
+            return Ok(metas);
+        }
+        for layer in &self.layers[1..] {
+            metas.extend(layer.repo.list_meta()?.into_iter().map(|(_, meta)| meta));
+        }
+        Ok(metas)
+    }
+    fn read_only(&self, slug: &str, layer: usize) -> NibbError {
+        NibbError::ReadOnly(format!("{} belongs to the read-only layer {}", slug, self.layers[layer].name))
+    }
+    fn tagged_by_top(&self, mut snippet: Snippet) -> Snippet {
+        snippet.layer = Some(self.layers[0].name.clone());
+        snippet
+    }
+}
+
+impl SnippetRepository for LayeredRepo {
+    /// Merges the snippets of all layers, ordered by slug. Each slug is taken from its topmost layer.
+    fn load_all(&self) -> NibbResult<Vec<Snippet>> {
+        let mut snippets = BTreeMap::new();
+        for layer in &self.layers {
+            for mut snippet in layer.repo.load_all()? {
+                snippets.entry(snippet.meta.get_slug()).or_insert_with(|| {
+                    snippet.layer = Some(layer.name.clone());
+                    snippet
+                });
+            }
+        }
+        Ok(snippets.into_values().collect())
+    }
+    /// Merges the snippets of all layers like [`load_all()`](Self::load_all), along with the diagnostics of each layer.
+    ///
+    /// A broken snippet shadows the ones with the same slug below it, like a readable one would.
+    fn load_all_lenient(&self) -> NibbResult<LoadReport> {
+        let mut snippets = BTreeMap::new();
+        let mut broken = BTreeSet::new();
+        let mut report = LoadReport::default();
+        for layer in &self.layers {
+            let layer_report = layer.repo.load_all_lenient()?;
+            for mut snippet in layer_report.snippets {
+                let slug = snippet.meta.get_slug();
+                if !broken.contains(&slug) {
+                    snippets.entry(slug).or_insert_with(|| {
+                        snippet.layer = Some(layer.name.clone());
+                        snippet
+                    });
+                }
+            }
+            for diagnostic in layer_report.diagnostics {
+                if !snippets.contains_key(&diagnostic.slug) && broken.insert(diagnostic.slug.clone()) {
+                    report.diagnostics.push(diagnostic);
+                }
+            }
+        }
+        report.snippets = snippets.into_values().collect();
+        Ok(report)
+    }
+    /// Merges the metadata of all layers, ordered by slug. Each slug is taken from its topmost layer.
+    fn list_meta(&self) -> NibbResult<Vec<(String, Meta)>> {
+        Ok(self.list_meta_lenient()?.metas)
+    }
+    /// Merges the metadata of all layers like [`list_meta()`](Self::list_meta), along with the diagnostics of each layer.
+    ///
+    /// A broken snippet shadows the ones with the same slug below it, like a readable one would.
+    fn list_meta_lenient(&self) -> NibbResult<MetaReport> {
+        let mut metas = BTreeMap::new();
+        let mut broken = BTreeSet::new();
+        let mut report = MetaReport::default();
+        for layer in &self.layers {
+            let layer_report = layer.repo.list_meta_lenient()?;
+            for (slug, meta) in layer_report.metas {
+                if !broken.contains(&slug) {
+                    metas.entry(slug).or_insert(meta);
+                }
+            }
+            for diagnostic in layer_report.diagnostics {
+                if !metas.contains_key(&diagnostic.slug) && broken.insert(diagnostic.slug.clone()) {
+                    report.diagnostics.push(diagnostic);
+                }
+            }
+        }
+        report.metas = metas.into_iter().collect();
+        Ok(report)
+    }
+    /// Loads a snippet from the topmost layer that has it.
+    fn load(&self, slug: &str) -> NibbResult<Snippet> {
+        let slug = slugify_path(slug); // just to be sure
+        Ok(self.resolve(|repo| repo.load(&slug))?.1)
+    }
+    /// Loads a snippet by ID from the topmost layer that has it.
+    fn load_by_id(&self, id: Uuid) -> NibbResult<Snippet> {
+        Ok(self.resolve(|repo| repo.load_by_id(id))?.1)
+    }
+    /// Saves a snippet to the top layer, copying it up if it overrides one of a lower layer.
+    fn save(&self, snippet: &Snippet) -> NibbResult<()> {
+        let copy = self.copy_up(snippet)?;
+        self.top().save_linking(&copy, None, &self.linkable(std::slice::from_ref(&copy))?)
+    }
+    /// Saves a snippet like [`save()`](Self::save) if the version visible through the layers matches `expected`.
+    ///
+    /// A snippet of the top layer is compared by the top layer itself. A snippet of a lower layer
+    /// is compared before it is copied up, read-only layers do not change in the meantime.
+    fn save_if_unmodified(&self, snippet: &Snippet, expected: &ExpectedVersion) -> NibbResult<()> {
+        let slug = snippet.meta.get_slug();
+        match self.resolve(|repo| repo.load(&slug)) {
+            Ok((0, _)) | Err(NibbError::NotFound(_)) => {
+                let copy = self.copy_up(snippet)?;
+                let linkable = self.linkable(std::slice::from_ref(&copy))?;
+                match self.top().save_linking(&copy, Some(expected), &linkable) {
+                    Err(NibbError::Conflict(mut conflict)) => {
+                        conflict.current = conflict.current.map(|current| self.tagged_by_top(current));
+                        conflict.attempted = snippet.clone();
+                        Err(NibbError::Conflict(conflict))
+                    }
+                    result => result,
+                }
+            }
+            Ok((_, current)) => {
+                check_unmodified(snippet, Some(current), expected)?;
+                self.save(snippet)
+            }
+            Err(e) => Err(e),
+        }
+    }
+    /// Saves a list of snippets to the top layer, like [`save()`](Self::save).
+    fn save_all(&self, snippets: &[Snippet]) -> NibbResult<()> {
+        let copies = snippets.iter()
+            .map(|snippet| self.copy_up(snippet))
+            .collect::<NibbResult<Vec<_>>>()?;
+        self.top().save_all_linking(&copies, &self.linkable(&copies)?)
+    }
+    /// Deletes a snippet of the top layer, revealing the one it overrides, if any.
+    ///
+    /// Fails with [`NibbError::ReadOnly`] if the snippet belongs to a lower layer.
+    fn delete(&self, slug: &str) -> NibbResult<()> {
+        let slug = slugify_path(slug); // just to be sure
+        match self.resolve(|repo| repo.load(&slug))?.0 {
+            0 => self.top().delete(&slug),
+            i => Err(self.read_only(&slug, i)),
+        }
+    }
+    /// Renames a snippet of the top layer.
+    ///
+    /// Fails with [`NibbError::ReadOnly`] if the snippet belongs to a lower layer,
+    /// and with [`NibbError::InvalidSlug`] if any layer already uses the new slug.
+    fn rename(&self, slug: &str, new_name: &str) -> NibbResult<Snippet> {
+        let old_slug = slugify_path(slug); // just to be sure
+        let (i, snippet) = self.resolve(|repo| repo.load(&old_slug))?;
+        if i > 0 {
+            return Err(self.read_only(&old_slug, i));
+        }
+        let mut meta = snippet.meta;
+        meta.rename(new_name);
+        let new_slug = meta.get_slug();
+        if new_slug != old_slug && self.load(&new_slug).is_ok() {
+            return Err(NibbError::InvalidSlug(format!("{} is already taken", new_slug)));
+        }
+        Ok(self.tagged_by_top(self.top().rename(&old_slug, new_name)?))
+    }
+    /// Lists the revisions of a snippet in the topmost layer that has it.
+    fn list_revisions(&self, slug: &str) -> NibbResult<Vec<Revision>> {
+        let slug = slugify_path(slug); // just to be sure
+        self.layers[self.layer_of(&slug)?].repo.list_revisions(&slug)
+    }
+    /// Loads a revision of a snippet from the topmost layer that has the snippet.
+    fn load_revision(&self, slug: &str, revision: &str) -> NibbResult<Snippet> {
+        let slug = slugify_path(slug); // just to be sure
+        let layer = &self.layers[self.layer_of(&slug)?];
+        let mut snippet = layer.repo.load_revision(&slug, revision)?;
+        snippet.layer = Some(layer.name.clone());
+        Ok(snippet)
+    }
+    /// Restores a revision of a snippet.
+    ///
+    /// A revision of a lower layer's snippet is copied up into the top layer.
+    fn restore_revision(&self, slug: &str, revision: &str) -> NibbResult<()> {
+        let slug = slugify_path(slug); // just to be sure
+        match self.layer_of(&slug)? {
+            0 => self.top().restore_revision(&slug, revision),
+            _ => {
+                let current = self.load(&slug)?.meta;
+                self.save(&restore_under(&slug, self.load_revision(&slug, revision)?, Some(&current)))
+            }
+        }
+    }
+    /// Lists the trash of the top layer, read-only layers delete nothing.
+    fn list_trash(&self) -> NibbResult<Vec<TrashEntry>> {
+        self.top().list_trash()
+    }
+    /// Restores a snippet from the trash of the top layer.
+    fn restore_trash(&self, slug: &str) -> NibbResult<Snippet> {
+        Ok(self.tagged_by_top(self.top().restore_trash(slug)?))
+    }
+    /// Empties the trash of the top layer.
+    fn empty_trash(&self, older_than: Option<chrono::Duration>) -> NibbResult<usize> {
+        self.top().empty_trash(older_than)
+    }
+}
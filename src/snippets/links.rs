@@ -0,0 +1,138 @@
+use std::fmt;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+use crate::result::{NibbError, NibbResult};
+use crate::snippets::snippet::{slugify_path, Meta};
+
+/// Kinds of relationships between snippets.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LinkKind {
+    /// The target is worth a look as well.
+    SeeAlso,
+    /// The snippet supersedes the target.
+    Replaces,
+    /// The snippet needs the target, e.g. a helper it calls.
+    DependsOn,
+    /// The snippet was adapted from the target.
+    DerivedFrom,
+}
+
+/// A typed reference from one snippet to another, stored in [`Meta::links`].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SnippetLink {
+    pub kind: LinkKind,
+    /// [`Meta::id`] or slug of the target.
+    ///
+    /// Slugs are rewritten when the target is renamed, IDs never change.
+    pub target: String,
+}
+
+/// Whether a [`Relation`] starts or ends at the snippet it was queried for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LinkDirection {
+    /// The queried snippet links to the related one.
+    Outgoing,
+    /// The related snippet links to the queried one.
+    Incoming,
+}
+
+/// A snippet related to another one, see [`SnippetRepository::related()`](crate::SnippetRepository::related).
+#[derive(Debug, Clone, Serialize)]
+pub struct Relation {
+    pub kind: LinkKind,
+    pub direction: LinkDirection,
+    /// Metadata of the related snippet.
+    pub meta: Meta,
+}
+
+/// A link whose target does not exist, e.g. because it was deleted,
+/// see [`SnippetRepository::dangling_links()`](crate::SnippetRepository::dangling_links).
+#[derive(Debug, Clone, Serialize)]
+pub struct DanglingLink {
+    /// Slug of the snippet the link belongs to.
+    pub slug: String,
+    pub link: SnippetLink,
+}
+
+impl SnippetLink {
+    /// Creates a link to the snippet with the ID or slug `target`.
+    ///
+    /// A target that is not an ID is slugified, so a name can be given as well.
+    pub fn new(kind: LinkKind, target: &str) -> Self {
+        let target = match Uuid::parse_str(target) {
+            Ok(id) => id.to_string(),
+            Err(_) => slugify_path(target),
+        };
+        Self { kind, target }
+    }
+    /// Returns `true` if the link refers to the snippet described by `meta`.
+    pub fn points_to(&self, meta: &Meta) -> bool {
+        match Uuid::parse_str(&self.target) {
+            Ok(id) => meta.id == id,
+            Err(_) => slugify_path(&self.target) == meta.get_slug(),
+        }
+    }
+}
+
+impl fmt::Display for LinkKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LinkKind::SeeAlso => write!(f, "see_also"),
+            LinkKind::Replaces => write!(f, "replaces"),
+            LinkKind::DependsOn => write!(f, "depends_on"),
+            LinkKind::DerivedFrom => write!(f, "derived_from"),
+        }
+    }
+}
+
+/// Checks that every link of `meta` points to another snippet among `others`.
+///
+/// `others` may contain the snippet itself, e.g. its previous version, a link to it is refused all the same.
+pub(crate) fn check_links<'a>(meta: &Meta, others: impl IntoIterator<Item = &'a Meta>) -> NibbResult<()> {
+    if meta.links.is_empty() {
+        return Ok(());
+    }
+    let slug = meta.get_slug();
+    let others: Vec<&Meta> = others.into_iter().filter(|other| other.get_slug() != slug).collect();
+    for link in &meta.links {
+        if link.points_to(meta) {
+            return Err(NibbError::InvalidLink(format!("{} links to itself", slug)));
+        }
+        if !others.iter().any(|other| link.points_to(other)) {
+            return Err(NibbError::InvalidLink(format!("{} links to {}, which does not exist", slug, link.target)));
+        }
+    }
+    Ok(())
+}
+
+/// Returns the relations of the snippet described by `meta` among `all`.
+pub(crate) fn find_relations<'a>(meta: &Meta, all: impl IntoIterator<Item = &'a Meta>) -> Vec<Relation> {
+    let mut relations = Vec::new();
+    for other in all {
+        if other.id == meta.id {
+            continue;
+        }
+        for link in meta.links.iter().filter(|link| link.points_to(other)) {
+            relations.push(Relation { kind: link.kind, direction: LinkDirection::Outgoing, meta: other.clone() });
+        }
+        for link in other.links.iter().filter(|link| link.points_to(meta)) {
+            relations.push(Relation { kind: link.kind, direction: LinkDirection::Incoming, meta: other.clone() });
+        }
+    }
+    relations
+}
+
+/// Returns the links among `all` whose target is not in `all`.
+pub(crate) fn find_dangling<'a>(all: impl IntoIterator<Item = &'a Meta> + Clone) -> Vec<DanglingLink> {
+    let mut dangling = Vec::new();
+    for meta in all.clone() {
+        for link in &meta.links {
+            if !all.clone().into_iter().any(|other| link.points_to(other)) {
+                dangling.push(DanglingLink { slug: meta.get_slug(), link: link.clone() });
+            }
+        }
+    }
+    dangling
+}
@@ -0,0 +1,311 @@
+use std::collections::HashMap;
+use std::sync::{RwLock, RwLockReadGuard, RwLockWriteGuard};
+use chrono::Utc;
+use crate::config::config::HistoryConfig;
+use crate::result::{NibbError, NibbResult};
+use crate::snippets::conflict::{check_unmodified, ExpectedVersion};
+use crate::snippets::history::{restore_under, Revision};
+use crate::snippets::links::check_links;
+use crate::snippets::repo::{check_batch_slugs, check_namespace, claim_slug, SnippetRepository};
+use crate::snippets::snippet::{slugify_path, Meta, Snippet};
+use crate::snippets::trash::{trash_cutoff, TrashEntry};
+
+/// Revisions of each snippet, oldest first, keyed by slug.
+type History = HashMap<String, Vec<(Revision, Snippet)>>;
+
+/// Deleted snippets, oldest deletion first.
+type Trash = Vec<(TrashEntry, Snippet)>;
+
+/// In-memory implementation of [`SnippetRepository`].
+///
+/// Snippets are kept in a `HashMap` keyed by slug and are lost when the repository is dropped.
+/// Slug handling, overwrites, history, trash and not-found errors behave like [`FSRepo`](crate::FSRepo),
+/// which makes it a drop-in backend for tests and for tools embedding nibb.
+#[derive(Default)]
+pub struct MemoryRepo {
+    snippets: RwLock<HashMap<String, Snippet>>,
+    history: RwLock<History>,
+    trash: RwLock<Trash>,
+    history_config: HistoryConfig,
+}
+
+impl MemoryRepo {
+    /// Creates a new, empty [`MemoryRepo`].
+    pub fn new() -> Self {
+        Self::default()
+    }
+    fn snippets(&self) -> NibbResult<RwLockReadGuard<'_, HashMap<String, Snippet>>> {
+        self.snippets.read().map_err(|e| NibbError::Other(format!("Memory repository poisoned: {}", e)))
+    }
+    fn snippets_mut(&self) -> NibbResult<RwLockWriteGuard<'_, HashMap<String, Snippet>>> {
+        self.snippets.write().map_err(|e| NibbError::Other(format!("Memory repository poisoned: {}", e)))
+    }
+    fn history(&self) -> NibbResult<RwLockReadGuard<'_, History>> {
+        self.history.read().map_err(|e| NibbError::Other(format!("Memory repository poisoned: {}", e)))
+    }
+    fn history_mut(&self) -> NibbResult<RwLockWriteGuard<'_, History>> {
+        self.history.write().map_err(|e| NibbError::Other(format!("Memory repository poisoned: {}", e)))
+    }
+    fn trash(&self) -> NibbResult<RwLockReadGuard<'_, Trash>> {
+        self.trash.read().map_err(|e| NibbError::Other(format!("Memory repository poisoned: {}", e)))
+    }
+    fn trash_mut(&self) -> NibbResult<RwLockWriteGuard<'_, Trash>> {
+        self.trash.write().map_err(|e| NibbError::Other(format!("Memory repository poisoned: {}", e)))
+    }
+    /// Stores a clone of the snippet under its slug.
+    ///
+    /// With `expected`, the stored version is checked against it first, see [`check_unmodified()`].
+    /// With `linkable`, the links are checked under the same lock, see [`check_links_in()`].
+    fn store(&self, snippet: &Snippet, expected: Option<&ExpectedVersion>, linkable: Option<&[Meta]>) -> NibbResult<()> {
+        let slug = snippet.meta.get_slug();
+        let mut snippets = self.snippets_mut()?;
+        if let Some(expected) = expected {
+            check_unmodified(snippet, snippets.get(&slug).cloned(), expected)?;
+        }
+        if let Some(linkable) = linkable {
+            check_links_in(&snippets, std::slice::from_ref(snippet), linkable)?;
+        }
+        check_namespace_in(&snippets, &slug)?;
+        let snippet = claim_slug(snippet, snippets.get(&slug).map(|s| &s.meta))?;
+        let previous = snippets.insert(slug.clone(), snippet);
+        drop(snippets);
+        if let Some(previous) = previous {
+            self.record_revision(&slug, previous)?;
+        }
+        Ok(())
+    }
+    /// Keeps `previous` as a new revision of `slug`, then prunes old revisions.
+    fn record_revision(&self, slug: &str, previous: Snippet) -> NibbResult<()> {
+        if !self.history_config.enabled {
+            return Ok(());
+        }
+        let mut history = self.history_mut()?;
+        let revisions = history.entry(slug.to_string()).or_default();
+        let mut recorded = Utc::now();
+        // two saves within the clock's resolution must not share a revision
+        while revisions.last().is_some_and(|(r, _)| r.recorded >= recorded) {
+            recorded += chrono::Duration::nanoseconds(1);
+        }
+        revisions.push((Revision::new(slug, recorded), previous));
+
+        let max = self.history_config.max_revisions;
+        if max > 0 && revisions.len() > max {
+            revisions.drain(..revisions.len() - max);
+        }
+        Ok(())
+    }
+}
+
+impl SnippetRepository for MemoryRepo {
+    /// Returns clones of all stored snippets, ordered by slug.
+    fn load_all(&self) -> NibbResult<Vec<Snippet>> {
+        let snippets = self.snippets()?;
+        let mut slugs: Vec<&String> = snippets.keys().collect();
+        slugs.sort();
+        Ok(slugs.into_iter().map(|slug| snippets[slug].clone()).collect())
+    }
+    /// Returns clones of the metadata of all stored snippets, ordered by slug.
+    fn list_meta(&self) -> NibbResult<Vec<(String, Meta)>> {
+        let snippets = self.snippets()?;
+        let mut metas: Vec<(String, Meta)> = snippets.iter()
+            .map(|(slug, snippet)| (slug.clone(), snippet.meta.clone()))
+            .collect();
+        metas.sort_by(|a, b| a.0.cmp(&b.0));
+        Ok(metas)
+    }
+    /// Returns a clone of the snippet stored under `slug`.
+    fn load(&self, slug: &str) -> NibbResult<Snippet> {
+        let slug = slugify_path(slug); // just to be sure
+        self.snippets()?
+            .get(&slug)
+            .cloned()
+            .ok_or(NibbError::NotFound(format!("snippet:{}", slug)))
+    }
+    /// Stores a clone of the snippet under its slug, replacing any previous version.
+    ///
+    /// The replaced version is recorded as a revision.
+    fn save(&self, snippet: &Snippet) -> NibbResult<()> {
+        self.save_linking(snippet, None, &[])
+    }
+    /// Stores a clone of the snippet like [`save()`](Self::save) if the stored version matches `expected`.
+    fn save_if_unmodified(&self, snippet: &Snippet, expected: &ExpectedVersion) -> NibbResult<()> {
+        self.save_linking(snippet, Some(expected), &[])
+    }
+    /// Stores a clone of the snippet like [`save()`](Self::save) or [`save_if_unmodified()`](Self::save_if_unmodified),
+    /// accepting links to `linkable` as well.
+    fn save_linking(&self, snippet: &Snippet, expected: Option<&ExpectedVersion>, linkable: &[Meta]) -> NibbResult<()> {
+        self.store(snippet, expected, Some(linkable))
+    }
+    /// Saves a list of snippets, either all of them or none.
+    ///
+    /// Every snippet is checked like in [`save()`](Self::save) before the first one is stored.
+    /// Links may point to other snippets of the batch.
+    fn save_all(&self, snippets: &[Snippet]) -> NibbResult<()> {
+        self.save_all_linking(snippets, &[])
+    }
+    /// Saves a list of snippets like [`save_all()`](Self::save_all), accepting links to `linkable` as well.
+    fn save_all_linking(&self, snippets: &[Snippet], linkable: &[Meta]) -> NibbResult<()> {
+        check_batch_slugs(snippets)?;
+        let mut stored = self.snippets_mut()?;
+        check_links_in(&stored, snippets, linkable)?;
+        let claimed = snippets.iter()
+            .map(|snippet| {
+                let slug = snippet.meta.get_slug();
+                check_namespace_in(&stored, &slug)?;
+                claim_slug(snippet, stored.get(&slug).map(|s| &s.meta))
+            })
+            .collect::<NibbResult<Vec<_>>>()?;
+        let previous: Vec<(String, Option<Snippet>)> = claimed.into_iter()
+            .map(|snippet| {
+                let slug = snippet.meta.get_slug();
+                let previous = stored.insert(slug.clone(), snippet);
+                (slug, previous)
+            })
+            .collect();
+        drop(stored);
+        for (slug, previous) in previous {
+            if let Some(previous) = previous {
+                self.record_revision(&slug, previous)?;
+            }
+        }
+        Ok(())
+    }
+    /// Moves the snippet stored under `slug` to the trash, recording it as a revision as well.
+    fn delete(&self, slug: &str) -> NibbResult<()> {
+        let slug = slugify_path(slug); // just to be sure
+        let previous = self.snippets_mut()?
+            .remove(&slug)
+            .ok_or(NibbError::NotFound(format!("snippet:{}", slug)))?;
+        self.record_revision(&slug, previous.clone())?;
+        let mut trash = self.trash_mut()?;
+        let mut deleted = Utc::now();
+        // two deletions within the clock's resolution must not share an entry
+        while trash.last().is_some_and(|(e, _)| e.deleted >= deleted) {
+            deleted += chrono::Duration::nanoseconds(1);
+        }
+        trash.push((TrashEntry::new(&slug, deleted), previous));
+        Ok(())
+    }
+    /// Moves the snippet and its revisions from `slug` to the slug of `new_name`,
+    /// and points the links of other snippets to the new slug.
+    ///
+    /// The version before the rename is recorded as a revision.
+    fn rename(&self, slug: &str, new_name: &str) -> NibbResult<Snippet> {
+        let old_slug = slugify_path(slug); // just to be sure
+        let mut snippets = self.snippets_mut()?;
+        let previous = snippets.get(&old_slug)
+            .cloned()
+            .ok_or(NibbError::NotFound(format!("snippet:{}", old_slug)))?;
+        let mut snippet = previous.clone();
+        snippet.meta.rename(new_name);
+        let new_slug = snippet.meta.get_slug();
+        if new_slug.is_empty() {
+            return Err(NibbError::InvalidSlug(format!("'{}' does not produce a slug", new_name)));
+        }
+        let snippet = claim_slug(&snippet, Some(&previous.meta))?;
+        if new_slug != old_slug {
+            if snippets.contains_key(&new_slug) {
+                return Err(NibbError::InvalidSlug(format!("{} is already taken", new_slug)));
+            }
+            check_namespace_in(&snippets, &new_slug)?;
+        }
+        snippets.remove(&old_slug);
+        snippets.insert(new_slug.clone(), snippet.clone());
+        if new_slug != old_slug {
+            for other in snippets.values_mut() {
+                other.meta.retarget_links(&old_slug, &new_slug);
+            }
+        }
+        drop(snippets);
+
+        if new_slug != old_slug {
+            let mut history = self.history_mut()?;
+            if let Some(revisions) = history.remove(&old_slug) {
+                // revisions left behind under the new slug, e.g. by a deleted snippet, are kept
+                let target = history.entry(new_slug.clone()).or_default();
+                for (revision, old) in revisions {
+                    let moved = Revision::first_free(&new_slug, revision.recorded, |id| {
+                        Ok(target.iter().any(|(other, _)| other.id == id))
+                    })?;
+                    target.push((moved, old));
+                }
+                target.sort_by(|a, b| a.0.id.cmp(&b.0.id));
+            }
+        }
+        self.record_revision(&new_slug, previous)?;
+        Ok(snippet)
+    }
+    /// Lists the revisions of a snippet, oldest first.
+    fn list_revisions(&self, slug: &str) -> NibbResult<Vec<Revision>> {
+        let slug = slugify_path(slug); // just to be sure
+        Ok(self.history()?
+            .get(&slug)
+            .map(|revisions| revisions.iter().map(|(r, _)| r.clone()).collect())
+            .unwrap_or_default())
+    }
+    /// Loads a snippet as it was at the given revision.
+    fn load_revision(&self, slug: &str, revision: &str) -> NibbResult<Snippet> {
+        let slug = slugify_path(slug); // just to be sure
+        self.history()?
+            .get(&slug)
+            .and_then(|revisions| revisions.iter().find(|(r, _)| r.id == revision))
+            .map(|(_, snippet)| snippet.clone())
+            .ok_or(NibbError::NotFound(format!("revision {} of {}", revision, slug)))
+    }
+    /// Restores a revision by saving it over the current version of the snippet.
+    ///
+    /// The revision keeps the current name and namespace, see [`restore_under()`].
+    fn restore_revision(&self, slug: &str, revision: &str) -> NibbResult<()> {
+        let slug = slugify_path(slug); // just to be sure
+        let snippet = self.load_revision(&slug, revision)?;
+        let current = self.snippets()?.get(&slug).map(|current| current.meta.clone());
+        self.store(&restore_under(&slug, snippet, current.as_ref()), None, None)
+    }
+    /// Lists the deleted snippets, oldest deletion first.
+    fn list_trash(&self) -> NibbResult<Vec<TrashEntry>> {
+        Ok(self.trash()?.iter().map(|(e, _)| e.clone()).collect())
+    }
+    /// Moves the most recently deleted snippet with the given slug back out of the trash.
+    fn restore_trash(&self, slug: &str) -> NibbResult<Snippet> {
+        let slug = slugify_path(slug); // just to be sure
+        let mut snippets = self.snippets_mut()?;
+        let mut trash = self.trash_mut()?;
+        let position = trash.iter()
+            .rposition(|(e, _)| e.slug == slug)
+            .ok_or(NibbError::NotFound(format!("trash:{}", slug)))?;
+        if snippets.contains_key(&slug) {
+            return Err(NibbError::InvalidSlug(format!("{} is already taken", slug)));
+        }
+        check_namespace_in(&snippets, &slug)?;
+        let (_, snippet) = trash.remove(position);
+        snippets.insert(slug, snippet.clone());
+        Ok(snippet)
+    }
+    /// Drops deleted snippets, optionally only those deleted at least `older_than` ago.
+    fn empty_trash(&self, older_than: Option<chrono::Duration>) -> NibbResult<usize> {
+        let cutoff = trash_cutoff(older_than);
+        let mut trash = self.trash_mut()?;
+        let before = trash.len();
+        trash.retain(|(e, _)| cutoff.is_some_and(|cutoff| e.deleted > cutoff));
+        Ok(before - trash.len())
+    }
+}
+
+/// Checks that the links of every snippet in `batch` point to `stored` snippets, to other snippets in `batch`
+/// or to `linkable`.
+fn check_links_in(stored: &HashMap<String, Snippet>, batch: &[Snippet], linkable: &[Meta]) -> NibbResult<()> {
+    for snippet in batch {
+        check_links(&snippet.meta, stored.values().chain(batch).map(|other| &other.meta).chain(linkable))?;
+    }
+    Ok(())
+}
+
+/// Checks that `slug` can hold a snippet next to the stored `snippets`, see [`check_namespace()`].
+fn check_namespace_in(snippets: &HashMap<String, Snippet>, slug: &str) -> NibbResult<()> {
+    let prefix = format!("{}/", slug);
+    check_namespace(
+        slug,
+        |parent| Ok(snippets.contains_key(parent)),
+        |_| Ok(snippets.keys().any(|other| other.starts_with(&prefix))),
+    )
+}
@@ -1,4 +1,16 @@
 pub mod snippet;
+pub mod links;
+pub mod conflict;
 pub mod repo;
 pub mod utils;
-pub mod file_type;
\ No newline at end of file
+pub mod file_type;
+pub mod history;
+pub mod trash;
+pub(crate) mod index;
+pub mod memory;
+pub mod layered;
+pub mod report;
+pub mod fsck;
+pub mod watch;
+#[cfg(feature = "sqlite")]
+pub mod sqlite;
@@ -1,11 +1,31 @@
+use std::collections::BTreeSet;
 use std::fs;
+use std::io::Write;
 use std::path::{Path, PathBuf};
-use slug::slugify;
+use std::sync::OnceLock;
+use std::time::Duration;
+use chrono::Utc;
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+use walkdir::WalkDir;
 use crate::config::config::Config;
-use crate::get_nibb_dir;
+use crate::fs::copy_dir_all;
+use crate::crypto::{decode_salt, frame_plain, generate_salt, is_encrypted, is_encrypted_str, unframe_plain, Cipher, NIBB_PASSPHRASE_ENV};
 use crate::git::git_integration::GitRepo;
+use crate::lock::RepoLock;
 use crate::result::{NibbError, NibbResult};
-use crate::snippets::snippet::{Meta, Snippet};
+use crate::snippets::conflict::{check_unmodified, ExpectedVersion};
+use crate::migration::{migrate_meta, META_SCHEMA_VERSION};
+use crate::snippets::fsck::{FsckCategory, FsckIssue, FsckReport};
+use crate::snippets::history::{restore_under, Revision, REVISION_ID_FORMAT};
+use crate::snippets::index::{FileStamp, IndexEntry, SnippetIndex};
+use crate::snippets::links::{check_links, find_dangling, find_relations, DanglingLink, Relation};
+use crate::snippets::report::{LoadDiagnostic, LoadReport, MetaReport};
+use crate::snippets::file_type::FileType;
+use crate::snippets::snippet::{is_valid_file_name, slugify_path, BinaryContent, Meta, Snippet, SnippetContent, SnippetFile, Visibility};
+use crate::snippets::trash::{trash_cutoff, TrashEntry};
+use crate::snippets::watch::SnippetWatcher;
 /// Defines the interface for a snippet repository backend.
 ///
 /// Allows loading, saving, and deleting individual or multiple [`Snippet`]s
@@ -13,57 +33,408 @@ use crate::snippets::snippet::{Meta, Snippet};
 pub trait SnippetRepository {
     /// Load all available snippets from the repository.
     fn load_all(&self) -> NibbResult<Vec<Snippet>>;
-    /// Load a single snippet by its slugified name.
+    /// Load all snippets that can be loaded, and report the ones that cannot.
+    ///
+    /// Unlike [`load_all()`](Self::load_all), a single broken snippet does not fail the whole call.
+    /// Backends whose entries cannot break individually can rely on the default implementation.
+    fn load_all_lenient(&self) -> NibbResult<LoadReport> {
+        Ok(LoadReport {
+            snippets: self.load_all()?,
+            diagnostics: Vec::new(),
+        })
+    }
+    /// List the slug and [`Meta`] of every snippet, ordered by slug, without their content.
+    ///
+    /// Meant for list views and pickers. Snippets whose metadata cannot be read are skipped,
+    /// [`list_meta_lenient()`](Self::list_meta_lenient) reports them.
+    /// The default implementation strips the result of [`load_all()`](Self::load_all),
+    /// backends that can read the metadata on its own should override it.
+    fn list_meta(&self) -> NibbResult<Vec<(String, Meta)>> {
+        Ok(self.load_all()?
+            .into_iter()
+            .map(|snippet| (snippet.meta.get_slug(), snippet.meta))
+            .collect())
+    }
+    /// List the slug and [`Meta`] of every snippet that can be read, and report the ones that cannot.
+    ///
+    /// Backends whose entries cannot break individually can rely on the default implementation.
+    fn list_meta_lenient(&self) -> NibbResult<MetaReport> {
+        Ok(MetaReport {
+            metas: self.list_meta()?,
+            diagnostics: Vec::new(),
+        })
+    }
+    /// Iterate over all snippets, ordered by slug, loading each one only when it is reached.
+    ///
+    /// The slugs are listed up front with [`list_meta()`](Self::list_meta). A snippet that is deleted
+    /// in the meantime yields [`NibbError::NotFound`], other snippets are not affected.
+    fn iter(&self) -> NibbResult<Box<dyn Iterator<Item = NibbResult<Snippet>> + '_>> {
+        let slugs = self.list_meta()?.into_iter().map(|(slug, _)| slug);
+        Ok(Box::new(slugs.map(move |slug| self.load(&slug))))
+    }
+    /// Load a single snippet by its slugified name, prefixed by its namespace, e.g. `rust/serde/untagged-enum`.
     fn load(&self, slug: &str) -> NibbResult<Snippet>;
+    /// Load a single snippet by its [`Meta::id`].
+    ///
+    /// The default implementation searches the result of [`load_all()`](Self::load_all).
+    fn load_by_id(&self, id: Uuid) -> NibbResult<Snippet> {
+        self.load_all()?
+            .into_iter()
+            .find(|snippet| snippet.meta.id == id)
+            .ok_or(NibbError::NotFound(format!("snippet:{}", id)))
+    }
+    /// Load a single snippet by its ID if `key` is a UUID, by its slug otherwise.
+    fn load_by_key(&self, key: &str) -> NibbResult<Snippet> {
+        match Uuid::parse_str(key) {
+            Ok(id) => self.load_by_id(id),
+            Err(_) => self.load(key),
+        }
+    }
     /// Save or update a single snippet.
+    ///
+    /// Fails with [`NibbError::InvalidSlug`] if the name does not produce a slug,
+    /// or if a snippet with another ID already uses the slug.
+    /// A snippet without an ID takes over the ID of the snippet it replaces, or gets a new one.
+    /// [`Meta::modified`] is set to the time of the save.
     fn save(&self, snippet: &Snippet) -> NibbResult<()>;
+    /// Save a snippet over its stored version, but only if that is still the version the caller loaded.
+    ///
+    /// Fails with [`NibbError::Conflict`], carrying both versions, if the stored snippet does not match
+    /// `expected` or was deleted in the meantime. Otherwise behaves like [`save()`](Self::save).
+    ///
+    /// The default implementation checks the result of [`load()`](Self::load) before saving,
+    /// so a change made between the two steps goes unnoticed.
+    fn save_if_unmodified(&self, snippet: &Snippet, expected: &ExpectedVersion) -> NibbResult<()> {
+        let current = match self.load(&snippet.meta.get_slug()) {
+            Ok(current) => Some(current),
+            Err(NibbError::NotFound(_)) => None,
+            Err(e) => return Err(e),
+        };
+        check_unmodified(snippet, current, expected)?;
+        self.save(snippet)
+    }
     /// Save or update a batch of snippets.
     fn save_all(&self, snippets: &[Snippet]) -> NibbResult<()>;
+    /// Save a snippet like [`save()`](Self::save), or like [`save_if_unmodified()`](Self::save_if_unmodified)
+    /// with `expected`, but let its links point to the snippets described by `linkable` as well.
+    ///
+    /// Meant for a [`LayeredRepo`](crate::LayeredRepo), whose snippets may link to snippets of read-only layers.
+    /// The default implementation ignores `linkable`, backends that check links should override it.
+    fn save_linking(&self, snippet: &Snippet, expected: Option<&ExpectedVersion>, _linkable: &[Meta]) -> NibbResult<()> {
+        match expected {
+            Some(expected) => self.save_if_unmodified(snippet, expected),
+            None => self.save(snippet),
+        }
+    }
+    /// Save a batch of snippets like [`save_all()`](Self::save_all), but let their links point to the snippets
+    /// described by `linkable` as well, see [`save_linking()`](Self::save_linking).
+    fn save_all_linking(&self, snippets: &[Snippet], _linkable: &[Meta]) -> NibbResult<()> {
+        self.save_all(snippets)
+    }
     /// Delete a snippet by its slug.
+    ///
+    /// The snippet is moved to the trash, from where [`restore_trash()`](Self::restore_trash) brings it back.
+    /// The deleted version is recorded as a revision as well.
     fn delete(&self, slug: &str) -> NibbResult<()>;
+    /// Rename a snippet, moving it from `slug` to the slug of `new_name`.
+    ///
+    /// A `new_name` containing `/` moves the snippet into another namespace as well,
+    /// e.g. `rust/serde/Untagged enum`. A leading `/` moves it to the top level.
+    /// Fails with [`NibbError::InvalidSlug`] if another snippet already uses the new slug.
+    /// Returns the renamed snippet.
+    fn rename(&self, slug: &str, new_name: &str) -> NibbResult<Snippet>;
+    /// List the recorded revisions of a snippet, oldest first.
+    fn list_revisions(&self, slug: &str) -> NibbResult<Vec<Revision>>;
+    /// Load a snippet as it was at the given revision.
+    fn load_revision(&self, slug: &str, revision: &str) -> NibbResult<Snippet>;
+    /// Restore a snippet to the given revision.
+    ///
+    /// The current version, if any, is recorded as a new revision first.
+    fn restore_revision(&self, slug: &str, revision: &str) -> NibbResult<()>;
+    /// List the deleted snippets in the trash, oldest deletion first.
+    fn list_trash(&self) -> NibbResult<Vec<TrashEntry>>;
+    /// Restore the most recently deleted snippet with the given slug from the trash.
+    ///
+    /// Fails with [`NibbError::InvalidSlug`] if another snippet uses the slug by now.
+    /// Returns the restored snippet.
+    fn restore_trash(&self, slug: &str) -> NibbResult<Snippet>;
+    /// Permanently remove deleted snippets from the trash.
+    ///
+    /// With `older_than`, only snippets deleted at least that long ago are removed.
+    /// Returns the number of removed snippets.
+    fn empty_trash(&self, older_than: Option<chrono::Duration>) -> NibbResult<usize>;
+    /// List the snippets linked to or from the snippet with the given ID or slug, see [`load_by_key()`](Self::load_by_key).
+    ///
+    /// The default implementation searches the result of [`list_meta()`](Self::list_meta).
+    fn related(&self, key: &str) -> NibbResult<Vec<Relation>> {
+        let all = self.list_meta()?;
+        let meta = match Uuid::parse_str(key) {
+            Ok(id) => all.iter().find(|(_, meta)| meta.id == id),
+            Err(_) => {
+                let slug = slugify_path(key);
+                all.iter().find(|(other, _)| *other == slug)
+            }
+        };
+        let (_, meta) = meta.ok_or(NibbError::NotFound(format!("snippet:{}", key)))?;
+        Ok(find_relations(meta, all.iter().map(|(_, other)| other)))
+    }
+    /// List the links whose target does not exist, e.g. because it was deleted.
+    ///
+    /// The default implementation searches the result of [`list_meta()`](Self::list_meta).
+    fn dangling_links(&self) -> NibbResult<Vec<DanglingLink>> {
+        let all = self.list_meta()?;
+        Ok(find_dangling(all.iter().map(|(_, meta)| meta)))
+    }
 }
 /// Filesystem-backed implementation of [`SnippetRepository`].
 ///
 /// Snippets are stored under a root `base_dir`, each in a dedicated subdirectory named by slug.
+/// Snippets with a namespace are nested accordingly, e.g. `snippets/rust/serde/untagged-enum/`.
 /// Each snippet directory contains:
 /// - `meta.toml`: metadata (name, tags, language, etc.)
-/// - `content.<ext>`: raw snippet content file
+/// - `content.<ext>`: raw snippet content file, text or binary
+/// - or, for multi-file snippets, `files.toml` listing each file's name and language,
+///   and the files themselves in `files/`
+///
 /// Additionally, `base_dir` contains:
 /// - `snippets/`: all snippet folders
-/// - `history/`: reserved for future versioning/history features
+/// - `history/`: previous versions of each snippet, in `history/<slug>/<revision>/`
+/// - `trash/`: deleted snippets, in `trash/<slug>/<id>/`
+/// - `tmp/`: staging area for saves in progress
+/// - `lost+found/`: entries moved aside by [`fsck()`](FSRepo::fsck), created when needed
+/// - `index.json`: cached metadata of all snippets, see [`list_meta()`](SnippetRepository::list_meta)
+/// - `nibb.lock`: lock file coordinating processes that access the repository
 /// - `config.toml`: configuration file (created if missing)
+///
+/// With `encryption.enabled`, the content files of private snippets are encrypted,
+/// see [`with_passphrase()`](FSRepo::with_passphrase).
 pub struct FSRepo {
     /// Root directory containing all snippet data.
     pub base_dir: PathBuf,
     pub config: Config,
-    pub git_repo: GitRepo,
+    /// Git repository in `base_dir`, opened (and initialized if needed) if `git.enabled` is set.
+    pub git_repo: Option<GitRepo>,
+    passphrase: Option<String>,
+    /// Key derived on first use, `None` if no passphrase or key file is available.
+    cipher: OnceLock<Option<Cipher>>,
+    /// Set by [`open_read_only()`](FSRepo::open_read_only), nothing is written to `base_dir` then.
+    read_only: bool,
 }
 
 impl FSRepo {
     /// Creates a new [`FSRepo`] and ensures the necessary folder structure exists.
     ///
-    /// Will create `snippets/`, `history/`, and `config.toml` if missing.
+    /// Will create `snippets/`, `history/`, `trash/`, `tmp/` and `config.toml` if missing,
+    /// and clean up saves that were interrupted by a crash.
     pub fn new<P: AsRef<Path>>(path: P) -> NibbResult<Self> {
-        let config = Config::load(&path.as_ref().join("config.toml"))?;
-        let git_repo = GitRepo::init_or_open(get_nibb_dir()?)?;
+        fs::create_dir_all(path.as_ref())?;
+        let config_path = path.as_ref().join("config.toml");
+        let mut config = Config::load(&config_path)?;
+        if config.encryption.enabled && config.encryption.salt.is_none() {
+            config.encryption.salt = Some(generate_salt()?);
+            config.save(&config_path)?;
+        }
+        let git_repo = match config.git.enabled {
+            true => Some(GitRepo::init_or_open(path.as_ref())?),
+            false => None,
+        };
         let repo = Self {
             git_repo,
             config,
             base_dir: path.as_ref().to_path_buf(),
+            passphrase: None,
+            cipher: OnceLock::new(),
+            read_only: false,
         };
         repo.ensure_structure()?;
+        {
+            let _lock = repo.write_lock()?;
+            repo.recover_staging()?;
+        }
         Ok(repo)
     }
+    /// Opens an existing [`FSRepo`] without writing anything to it, e.g. a checked-out team library
+    /// mounted as a lower layer of a [`LayeredRepo`](crate::LayeredRepo).
+    ///
+    /// Nothing is created, and nothing is locked for writing. An outdated `config.toml` or `meta.toml` is
+    /// upgraded in memory only, `index.json` is not updated and interrupted saves are not cleaned up.
+    /// Every write fails with [`NibbError::ReadOnly`].
+    pub fn open_read_only<P: AsRef<Path>>(path: P) -> NibbResult<Self> {
+        let base_dir = path.as_ref();
+        if !base_dir.is_dir() {
+            return Err(NibbError::NotFound(format!("repository:{:?}", base_dir)));
+        }
+        Ok(Self {
+            git_repo: None,
+            config: Config::read(&base_dir.join("config.toml"))?,
+            base_dir: base_dir.to_path_buf(),
+            passphrase: None,
+            cipher: OnceLock::new(),
+            read_only: true,
+        })
+    }
+    /// Uses `passphrase` to encrypt and decrypt private snippets.
+    ///
+    /// Without it, the passphrase is taken from `$NIBB_PASSPHRASE`, or the contents of
+    /// `encryption.key_file` are used. Snippets that cannot be decrypted for lack of a key
+    /// are loaded with [`SnippetContent::Locked`].
+    pub fn with_passphrase(mut self, passphrase: &str) -> Self {
+        self.passphrase = Some(passphrase.to_string());
+        self.cipher = OnceLock::new();
+        self
+    }
     /// Returns the path to a snippet's directory based on its slug.
     pub fn snippet_path(&self, slug: &str) -> PathBuf {
         self.snippets_dir().join(slug)
     }
+    /// Returns the path to the directory holding a snippet's revisions.
+    pub fn history_path(&self, slug: &str) -> PathBuf {
+        self.history_dir().join(slug)
+    }
+    /// Returns the path to the directory holding the deleted versions of a snippet.
+    pub fn trash_path(&self, slug: &str) -> PathBuf {
+        self.trash_dir().join(slug)
+    }
+    /// Checks the layout of `snippets/` and reports every inconsistency, see [`FsckCategory`].
+    ///
+    /// With `repair`, the safe ones are fixed. Entries that are moved aside end up in
+    /// `lost+found/<timestamp>/`, under the path they had below `snippets/`.
+    pub fn fsck(&self, repair: bool) -> NibbResult<FsckReport> {
+        let _lock = match repair {
+            true => self.write_lock()?,
+            false => self.read_lock()?,
+        };
+        let lost_found = self.lost_found_dir().join(Utc::now().format(REVISION_ID_FORMAT).to_string());
+        let mut report = FsckReport::default();
+        self.fsck_dir(&self.snippets_dir(), repair, &lost_found, &mut report)?;
+        let (index, _) = self.refreshed_index()?;
+        for dangling in find_dangling(index.entries.values().map(|entry| &entry.meta)) {
+            report.issues.push(FsckIssue::new(
+                FsckCategory::DanglingLink,
+                self.relative(&self.snippet_path(&dangling.slug).join("meta.toml")),
+                format!("{} link to {}, which does not exist", dangling.link.kind, dangling.link.target),
+            ));
+        }
+
+        let repaired = report.issues.iter().filter(|issue| issue.repaired).count();
+        if repaired > 0 {
+            self.refreshed_index()?;
+            self.auto_commit_paths(&[PathBuf::from("snippets")], &format!("fsck: repair {} issue(s)", repaired))?;
+        }
+        Ok(report)
+    }
+    /// Upgrades every `meta.toml` in `snippets/`, `history/` and `trash/` to [`META_SCHEMA_VERSION`].
+    ///
+    /// Reads upgrade outdated files in memory only, they are written here and by [`fsck()`](Self::fsck)
+    /// with `repair`, while `config.toml` is upgraded whenever the repository is opened.
+    /// Fails on the first file that cannot be read. The upgraded snippets are committed when Git is enabled.
+    /// Returns the number of upgraded files.
+    pub fn migrate(&self) -> NibbResult<usize> {
+        let _lock = self.write_lock()?;
+        let mut upgraded = 0;
+        // `snippets/<slug>/meta.toml`, `history/<slug>/<revision>/meta.toml` and `trash/<slug>/<id>/meta.toml`,
+        // where the slug may span several directories
+        for (root, depth) in [(self.snippets_dir(), 2), (self.history_dir(), 3), (self.trash_dir(), 3)] {
+            // the files of a multi-file snippet may be called `meta.toml` as well
+            let walk = WalkDir::new(root).min_depth(depth).sort_by_file_name().into_iter()
+                .filter_entry(|entry| entry.file_name() != "files" || !entry.path().with_file_name("files.toml").exists());
+            for entry in walk {
+                let entry = entry.map_err(|e| NibbError::Io(e.into()))?;
+                if entry.file_name() != "meta.toml" {
+                    continue;
+                }
+                if let Some(dir) = entry.path().parent() && self.upgrade_meta(dir)? {
+                    upgraded += 1;
+                }
+            }
+        }
+        if upgraded > 0 {
+            self.refreshed_index()?;
+            self.auto_commit_paths(&[PathBuf::from("snippets")], &format!("migrate: upgrade {} file(s)", upgraded))?;
+        }
+        Ok(upgraded)
+    }
+    /// Starts watching `snippets/` for changes, see [`SnippetWatcher`].
+    ///
+    /// Notifications are debounced by `debounce`, so a save or a `git pull` is reported once
+    /// per affected snippet.
+    pub fn watch(&self, debounce: Duration) -> NibbResult<SnippetWatcher> {
+        SnippetWatcher::new(&self.snippets_dir(), debounce)
+    }
     fn snippets_dir(&self) -> PathBuf {
         self.base_dir.join("snippets")
     }
     fn history_dir(&self) -> PathBuf {
         self.base_dir.join("history")
     }
+    fn trash_dir(&self) -> PathBuf {
+        self.base_dir.join("trash")
+    }
+    fn lost_found_dir(&self) -> PathBuf {
+        self.base_dir.join("lost+found")
+    }
+    fn tmp_dir(&self) -> PathBuf {
+        self.base_dir.join("tmp")
+    }
+    fn index_path(&self) -> PathBuf {
+        self.base_dir.join("index.json")
+    }
+    /// Returns the key for private snippets, derived on first use.
+    ///
+    /// The secret is the passphrase given to [`with_passphrase()`](Self::with_passphrase),
+    /// `$NIBB_PASSPHRASE` or the contents of `encryption.key_file`, in that order.
+    /// Returns `None` if there is none.
+    fn cipher(&self) -> NibbResult<Option<&Cipher>> {
+        if let Some(cipher) = self.cipher.get() {
+            return Ok(cipher.as_ref());
+        }
+        let passphrase = self.passphrase.clone()
+            .or_else(|| std::env::var(NIBB_PASSPHRASE_ENV).ok().filter(|p| !p.is_empty()));
+        let secret = match (passphrase, &self.config.encryption.key_file) {
+            (Some(passphrase), _) => Some(passphrase.into_bytes()),
+            (None, Some(key_file)) => {
+                let key_path = self.base_dir.join(key_file);
+                Some(fs::read(&key_path).map_err(|e| NibbError::NotFound(format!("{}:{:?}", e, &key_path)))?)
+            }
+            (None, None) => None,
+        };
+        let cipher = match secret {
+            // files carry their own salt, `encryption.salt` is only needed to encrypt
+            Some(secret) => Some(Cipher::new(secret, self.config.encryption.salt.as_deref().map(decode_salt).transpose()?)),
+            None => None,
+        };
+        Ok(self.cipher.get_or_init(|| cipher).as_ref())
+    }
+    /// Returns the key `meta` is written with, or `None` if it is stored in plain text.
+    ///
+    /// Fails if the snippet has to be encrypted, but no key is available.
+    fn sealing_cipher(&self, meta: &Meta) -> NibbResult<Option<&Cipher>> {
+        if !self.config.encryption.enabled || !matches!(meta.visibility, Visibility::Private) {
+            return Ok(None);
+        }
+        self.cipher()?
+            .map(Some)
+            .ok_or(NibbError::Locked(format!("{} is private, but there is no passphrase or key file", meta.name)))
+    }
+    /// Decrypts the contents of an encrypted file, and passes others through.
+    ///
+    /// Returns `None` if the file is encrypted, but no key is available.
+    fn unseal(&self, data: Vec<u8>) -> NibbResult<Option<Vec<u8>>> {
+        if !is_encrypted(&data) {
+            return Ok(Some(unframe_plain(data)));
+        }
+        self.cipher()?.map(|cipher| cipher.decrypt(&data)).transpose()
+    }
+    /// Decrypts the description of `meta`, or replaces it with a placeholder if no key is available.
+    fn unseal_meta(&self, meta: &mut Meta) -> NibbResult<()> {
+        if is_encrypted_str(&meta.description) {
+            meta.description = match self.cipher()? {
+                Some(cipher) => cipher.decrypt_str(&meta.description)?,
+                None => LOCKED_DESCRIPTION.to_string(),
+            };
+        }
+        Ok(())
+    }
     fn config_path(&self) -> PathBuf {
         self.base_dir.join("config.toml")
     }
@@ -71,6 +442,8 @@ impl FSRepo {
         fs::create_dir_all(&self.base_dir)?;
         fs::create_dir_all(self.snippets_dir())?;
         fs::create_dir_all(self.history_dir())?;
+        fs::create_dir_all(self.trash_dir())?;
+        fs::create_dir_all(self.tmp_dir())?;
         let config_path = self.config_path();
         if !config_path.exists() {
             fs::File::create(config_path)?;
@@ -78,119 +451,1265 @@ impl FSRepo {
         Ok(())
     }
     fn auto_commit(&self, snippet: &Snippet) -> NibbResult<()> {
-        if !self.config.git.enabled || !self.config.git.auto_commit {
+        let Some(git_repo) = self.git_repo.as_ref().filter(|_| self.config.git.auto_commit) else {
+            return Ok(());
+        };
+        self.commit_snippet(git_repo, snippet)?;
+        self.auto_push()?;
+        Ok(())
+    }
+    fn commit_snippet(&self, git_repo: &GitRepo, snippet: &Snippet) -> NibbResult<()> {
+        // an encrypted description must not end up in the commit message
+        let encryption = &self.config.encryption;
+        if encryption.enabled && encryption.encrypt_description && matches!(snippet.meta.visibility, Visibility::Private) {
+            let mut redacted = Snippet::new(snippet.meta.clone(), "");
+            redacted.meta.description = "<encrypted>".to_string();
+            git_repo.add_and_commit(&redacted, &self.config)?;
+        } else {
+            git_repo.add_and_commit(snippet, &self.config)?;
+        }
+        Ok(())
+    }
+    /// Commits a batch of saved snippets at once, without pushing, see [`save_all()`](SnippetRepository::save_all).
+    ///
+    /// A single snippet is committed like any other save.
+    fn commit_batch(&self, snippets: &[Snippet]) -> NibbResult<()> {
+        let Some(git_repo) = self.git_repo.as_ref().filter(|_| self.config.git.auto_commit) else {
             return Ok(());
+        };
+        match snippets {
+            [] => Ok(()),
+            [snippet] => self.commit_snippet(git_repo, snippet),
+            _ => {
+                let slugs: Vec<String> = snippets.iter().map(|snippet| snippet.meta.get_slug()).collect();
+                let rel_paths: Vec<PathBuf> = slugs.iter().map(|slug| PathBuf::from("snippets").join(slug)).collect();
+                git_repo.commit_paths(&rel_paths, &format!("update: {} snippets\n\n{}", slugs.len(), slugs.join("\n")), &self.config)?;
+                Ok(())
+            }
         }
-        self.git_repo.add_and_commit(snippet, &self.config)?;
+    }
+    /// Commits a rename, along with the snippets in `retargeted` whose links were rewritten.
+    fn auto_commit_rename(&self, old_slug: &str, snippet: &Snippet, retargeted: &[String]) -> NibbResult<()> {
+        let new_slug = snippet.meta.get_slug();
+        let mut rel_paths = vec![PathBuf::from("snippets").join(old_slug), PathBuf::from("snippets").join(&new_slug)];
+        rel_paths.extend(retargeted.iter().map(|slug| PathBuf::from("snippets").join(slug)));
+        self.auto_commit_paths(&rel_paths, &format!("rename: {} -> {}", old_slug, new_slug))
+    }
+    /// Commits everything below `rel_paths`, for changes that are not about a single snippet.
+    fn auto_commit_paths(&self, rel_paths: &[PathBuf], message: &str) -> NibbResult<()> {
+        let Some(git_repo) = self.git_repo.as_ref().filter(|_| self.config.git.auto_commit) else {
+            return Ok(());
+        };
+        git_repo.commit_paths(rel_paths, message, &self.config)?;
         self.auto_push()?;
         Ok(())
     }
     fn auto_push(&self) -> NibbResult<()> {
-        if !self.config.git.enabled || !self.config.git.push_on_commit {
+        let Some(git_repo) = self.git_repo.as_ref().filter(|_| self.config.git.push_on_commit) else {
             return Ok(());
-        }
+        };
         match &self.config.git.remote {
             Some(remote) => {
-                Ok(self.git_repo.push(remote, &self.config.git.branch)?)
+                Ok(git_repo.push(remote, &self.config.git.branch)?)
             },
             None => Ok(())
         }
     }
-    fn get_content_path(&self, slug: &str, extension: &str) -> PathBuf {
+    /// Acquires a shared lock on the repository, as taken by every read.
+    fn read_lock(&self) -> NibbResult<RepoLock> {
+        match self.read_only {
+            true => RepoLock::shared_read_only(&RepoLock::path(&self.base_dir), self.lock_timeout()),
+            false => RepoLock::shared(&RepoLock::path(&self.base_dir), self.lock_timeout()),
+        }
+    }
+    /// Acquires an exclusive lock on the repository, as taken by every write.
+    ///
+    /// Methods called while holding a lock must use the `*_unlocked` variants,
+    /// since the lock is not reentrant.
+    ///
+    /// Fails with [`NibbError::ReadOnly`] if the repository was opened with [`open_read_only()`](Self::open_read_only).
+    fn write_lock(&self) -> NibbResult<RepoLock> {
+        if self.read_only {
+            return Err(NibbError::ReadOnly(format!("{:?} was opened read-only", self.base_dir)));
+        }
+        RepoLock::exclusive(&RepoLock::path(&self.base_dir), self.lock_timeout())
+    }
+    fn lock_timeout(&self) -> Duration {
+        Duration::from_millis(self.config.lock.timeout_ms)
+    }
+    fn load_unlocked(&self, slug: &str) -> NibbResult<Snippet> {
+        let slug = slugify_path(slug); // just to be sure
+        self.read_snippet(&self.snippet_path(&slug))
+    }
+    fn save_unlocked(&self, snippet: &Snippet) -> NibbResult<()> {
+        let slug = snippet.meta.get_slug();
+        let snippet = self.claim_unlocked(&slug, snippet)?;
+        if let Some(previous) = self.write_snippet_atomic(&slug, &snippet)? {
+            self.record_revision(&slug, &previous)?;
+        }
+        self.update_index(&slug)?;
+
+        // git actions (handles config)
+        self.auto_commit(&snippet)?;
+        Ok(())
+    }
+    /// Checks that `snippet` may be written to `slug`, see [`claim_slug()`], and returns the snippet to write.
+    fn claim_unlocked(&self, slug: &str, snippet: &Snippet) -> NibbResult<Snippet> {
+        let snippet_path = self.snippet_path(slug);
+        if !slug.is_empty() {
+            self.check_namespace(slug)?;
+        }
+        // a snippet with unreadable metadata may be overwritten
+        let existing = match snippet_path.exists() {
+            true => self.read_meta(&snippet_path).ok(),
+            false => None,
+        };
+        claim_slug(snippet, existing.as_ref())
+    }
+    /// Checks that the links of every snippet in `batch` point to stored snippets, to other snippets in `batch`
+    /// or to `linkable`.
+    ///
+    /// The index is refreshed once for the whole batch, and only if any of its snippets has links.
+    fn check_links_unlocked(&self, batch: &[Snippet], linkable: &[Meta]) -> NibbResult<()> {
+        if batch.iter().all(|snippet| snippet.meta.links.is_empty()) {
+            return Ok(());
+        }
+        let (index, _) = self.refreshed_index()?;
+        for snippet in batch {
+            let stored = index.entries.values().map(|entry| &entry.meta);
+            check_links(&snippet.meta, stored.chain(batch.iter().map(|other| &other.meta)).chain(linkable))?;
+        }
+        Ok(())
+    }
+    /// Points the links to `from` at `to` in every snippet, after `from` was renamed.
+    ///
+    /// Only `meta.toml` is rewritten, no revision is recorded.
+    /// Returns the slugs of the snippets whose links were changed.
+    fn retarget_links(&self, from: &str, to: &str) -> NibbResult<Vec<String>> {
+        let (index, _) = self.refreshed_index()?;
+        let mut retargeted = Vec::new();
+        // the index holds the metadata as stored, so encrypted descriptions stay encrypted
+        for (slug, entry) in index.entries {
+            let mut meta = entry.meta;
+            if !meta.retarget_links(from, to) {
+                continue;
+            }
+            let meta_path = self.snippet_path(&slug).join("meta.toml");
+            replace_synced(&meta_path, toml::to_string(&meta)?.as_bytes())
+                .map_err(|e| NibbError::NotFound(format!("{}:{:?}", e, &meta_path)))?;
+            self.update_index(&slug)?;
+            retargeted.push(slug);
+        }
+        Ok(retargeted)
+    }
+    fn list_revisions_unlocked(&self, slug: &str) -> NibbResult<Vec<Revision>> {
+        let slug = slugify_path(slug); // just to be sure
+        let history_path = self.history_path(&slug);
+        if !history_path.exists() {
+            return Ok(Vec::new());
+        }
+        let mut revisions = Vec::new();
+        for entry in fs::read_dir(&history_path)? {
+            let entry = entry?;
+            if let Some(revision) = entry.file_name().to_str().and_then(|id| Revision::from_id(&slug, id)) {
+                revisions.push(revision);
+            }
+        }
+        revisions.sort_by(|a, b| a.id.cmp(&b.id));
+        Ok(revisions)
+    }
+    fn load_revision_unlocked(&self, slug: &str, revision: &str) -> NibbResult<Snippet> {
+        let slug = slugify_path(slug); // just to be sure
+        let revision = Revision::from_id(&slug, revision)
+            .ok_or(NibbError::NotFound(format!("revision {} of {}", revision, slug)))?;
+        self.read_snippet(&self.history_path(&slug).join(&revision.id))
+    }
+    /// Reads a snippet from a directory laid out like `snippets/<slug>/`.
+    fn read_snippet(&self, dir: &Path) -> NibbResult<Snippet> {
+        let meta = self.read_meta(dir)?;
+        self.read_content(dir, meta)
+    }
+    fn read_meta(&self, dir: &Path) -> NibbResult<Meta> {
+        self.read_meta_migrated(dir).map(|(meta, _)| meta)
+    }
+    /// Reads `meta.toml` from `dir`, upgrading it in memory if it was written with an older schema.
+    ///
+    /// The file itself is left alone, see [`upgrade_meta()`](Self::upgrade_meta).
+    /// Returns the metadata and whether the file is outdated.
+    fn read_meta_migrated(&self, dir: &Path) -> NibbResult<(Meta, bool)> {
+        let meta_path = dir.join("meta.toml");
+        let meta_str = fs::read_to_string(&meta_path)
+            .map_err(|e| NibbError::NotFound(format!("{}:{:?}", e, &meta_path)))?;
+        let mut table: toml::Table = toml::from_str(&meta_str)
+            .map_err(|e| NibbError::NotFound(format!("{}:{:?}", e, &meta_path)))?;
+        let migrated = migrate_meta(&mut table, &meta_path)?;
+        let mut meta: Meta = toml::Value::Table(table).try_into()
+            .map_err(|e| NibbError::NotFound(format!("{}:{:?}", e, &meta_path)))?;
+        meta.ensure_id();
+        Ok((meta, migrated))
+    }
+    /// Rewrites `meta.toml` in `dir` with [`META_SCHEMA_VERSION`] if it was written with an older schema.
+    ///
+    /// Must be called while holding the write lock. Returns whether the file was upgraded.
+    fn upgrade_meta(&self, dir: &Path) -> NibbResult<bool> {
+        let (meta, outdated) = self.read_meta_migrated(dir)?;
+        if outdated {
+            // snapshots of the index may still be read, so the file is replaced instead of rewritten
+            let meta_path = dir.join("meta.toml");
+            replace_synced(&meta_path, toml::to_string(&meta)?.as_bytes())
+                .map_err(|e| NibbError::NotFound(format!("{}:{:?}", e, &meta_path)))?;
+        }
+        Ok(outdated)
+    }
+    /// Checks the entries of `snippets/` or one of its namespace directories, see [`fsck()`](Self::fsck).
+    ///
+    /// Without `repair`, nothing is changed, so the entries are checked in parallel
+    /// and their issues merged in the order of the entries.
+    fn fsck_dir(&self, dir: &Path, repair: bool, lost_found: &Path, report: &mut FsckReport) -> NibbResult<()> {
+        let mut paths = fs::read_dir(dir)?
+            .map(|entry| entry.map(|entry| entry.path()))
+            .collect::<Result<Vec<_>, _>>()?;
+        paths.sort();
+        if repair {
+            for path in paths {
+                self.fsck_entry(&path, repair, lost_found, report)?;
+            }
+            return Ok(());
+        }
+        let reports: Vec<NibbResult<FsckReport>> = paths.par_iter()
+            .map(|path| {
+                let mut entry_report = FsckReport::default();
+                self.fsck_entry(path, repair, lost_found, &mut entry_report)?;
+                Ok(entry_report)
+            })
+            .collect();
+        for entry_report in reports {
+            report.issues.extend(entry_report?.issues);
+        }
+        Ok(())
+    }
+    fn fsck_entry(&self, path: &Path, repair: bool, lost_found: &Path, report: &mut FsckReport) -> NibbResult<()> {
+        if is_namespace_dir(path) {
+            self.fsck_dir(path, repair, lost_found, report)
+        } else if path.is_dir() {
+            self.fsck_snippet(path, repair, lost_found, report)
+        } else {
+            let issue = FsckIssue::new(FsckCategory::StrayFile, self.relative(path), "not a snippet directory");
+            record_issue(report, issue, repair, || self.move_aside(path, lost_found))
+        }
+    }
+    /// Checks a single snippet directory, see [`fsck()`](Self::fsck).
+    fn fsck_snippet(&self, dir: &Path, repair: bool, lost_found: &Path, report: &mut FsckReport) -> NibbResult<()> {
+        let manifest_path = dir.join("files.toml");
+        let multi_file = manifest_path.exists();
+        let manifest = match self.read_meta_migrated(dir) {
+            Ok(meta) if multi_file => read_manifest(&manifest_path).map(|manifest| (meta, Some(manifest))),
+            Ok(meta) => Ok((meta, None)),
+            Err(e) => Err(e),
+        };
+        let ((meta, outdated), manifest) = match manifest {
+            Ok(read) => read,
+            // a namespace with a stray `meta.toml`, whose snippets must stay where they are
+            Err(_) if holds_snippets(dir) => return self.fsck_dir(dir, repair, lost_found, report),
+            Err(e) => {
+                // nothing else can be checked without metadata
+                let issue = FsckIssue::new(FsckCategory::UnreadableMeta, self.relative(dir), e.to_string());
+                return record_issue(report, issue, repair, || self.move_aside(dir, lost_found));
+            }
+        };
+        if outdated {
+            let message = format!("written with an older schema, the current one is version {}", META_SCHEMA_VERSION);
+            let issue = FsckIssue::new(FsckCategory::OutdatedSchema, self.relative(&dir.join("meta.toml")), message);
+            record_issue(report, issue, repair, || self.upgrade_meta(dir).map(|_| ()))?;
+        }
+
+        let mut content_files = Vec::new();
+        let mut entries = fs::read_dir(dir)?.collect::<Result<Vec<_>, _>>()?;
+        entries.sort_by_key(|entry| entry.file_name());
+        for entry in entries {
+            let path = entry.path();
+            let name = entry.file_name().to_string_lossy().to_string();
+            match name.as_str() {
+                "meta.toml" => {}
+                "files.toml" | "files" if multi_file => {}
+                _ if !multi_file && name.starts_with("content.") && path.is_file() => content_files.push(name),
+                _ => {
+                    let issue = FsckIssue::new(FsckCategory::StrayFile, self.relative(&path), "not part of the snippet layout");
+                    record_issue(report, issue, repair, || self.move_aside(&path, lost_found))?;
+                }
+            }
+        }
+
+        match manifest {
+            Some(manifest) => {
+                let files_dir = dir.join("files");
+                for entry in &manifest.files {
+                    let file_path = files_dir.join(&entry.name);
+                    if !file_path.is_file() {
+                        let message = "listed in files.toml, but missing";
+                        report.issues.push(FsckIssue::new(FsckCategory::MissingContent, self.relative(&file_path), message));
+                    }
+                }
+                let mut entries = match files_dir.is_dir() {
+                    true => fs::read_dir(&files_dir)?.collect::<Result<Vec<_>, _>>()?,
+                    false => Vec::new(),
+                };
+                entries.sort_by_key(|entry| entry.file_name());
+                for entry in entries {
+                    let path = entry.path();
+                    let name = entry.file_name().to_string_lossy().to_string();
+                    if !manifest.files.iter().any(|file| file.name == name) {
+                        let issue = FsckIssue::new(FsckCategory::StrayFile, self.relative(&path), "not listed in files.toml");
+                        record_issue(report, issue, repair, || self.move_aside(&path, lost_found))?;
+                    }
+                }
+            }
+            None => {
+                let expected = format!("content.{}", meta.get_content_extension());
+                if content_files.contains(&expected) {
+                    for name in content_files.iter().filter(|name| **name != expected) {
+                        let path = dir.join(name);
+                        let message = format!("{} is the content file", expected);
+                        let issue = FsckIssue::new(FsckCategory::DuplicateContent, self.relative(&path), message);
+                        record_issue(report, issue, repair, || Ok(fs::remove_file(&path)?))?;
+                    }
+                } else if let [name] = content_files.as_slice() {
+                    let path = dir.join(name);
+                    let message = format!("expected {} for language {}", expected, meta.language);
+                    let issue = FsckIssue::new(FsckCategory::ExtensionMismatch, self.relative(&path), message);
+                    record_issue(report, issue, repair, || Ok(fs::rename(&path, dir.join(&expected))?))?;
+                } else if content_files.is_empty() {
+                    let message = format!("no content file, expected {}", expected);
+                    report.issues.push(FsckIssue::new(FsckCategory::MissingContent, self.relative(dir), message));
+                } else {
+                    let message = format!("{} content files, none of them is {}", content_files.len(), expected);
+                    report.issues.push(FsckIssue::new(FsckCategory::DuplicateContent, self.relative(dir), message));
+                }
+            }
+        }
+
+        // last, since it moves the directory
+        let slug = meta.get_slug();
+        let current = self.slug_of(dir);
+        if current != slug {
+            let issue = FsckIssue::new(FsckCategory::SlugMismatch, self.relative(dir), format!("expected {}", slug));
+            match slug.is_empty() || self.snippet_path(&slug).exists() || self.check_namespace(&slug).is_err() {
+                true => report.issues.push(FsckIssue { message: format!("{}, which cannot be used", issue.message), ..issue }),
+                false => record_issue(report, issue, repair, || {
+                    self.move_snippet_dir(&current, &slug)?;
+                    self.move_history(&current, &slug)
+                })?,
+            }
+        }
+        Ok(())
+    }
+    /// Returns the slug a directory below `snippets/` stands for, i.e. its path relative to `snippets/`.
+    fn slug_of(&self, dir: &Path) -> String {
+        dir.strip_prefix(self.snippets_dir()).unwrap_or(dir)
+            .components()
+            .map(|component| component.as_os_str().to_string_lossy())
+            .collect::<Vec<_>>()
+            .join("/")
+    }
+    /// Checks that `slug` can hold a snippet on disk, see [`check_namespace()`].
+    fn check_namespace(&self, slug: &str) -> NibbResult<()> {
+        check_namespace(
+            slug,
+            |parent| Ok(self.snippet_path(parent).join("meta.toml").exists()),
+            |slug| Ok(is_namespace_dir(&self.snippet_path(slug))),
+        )
+    }
+    /// Moves `snippets/<from>/` to `snippets/<to>/`, creating and removing namespace directories as needed.
+    fn move_snippet_dir(&self, from: &str, to: &str) -> NibbResult<()> {
+        let target = self.snippet_path(to);
+        if let Some(parent) = target.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::rename(self.snippet_path(from), &target)?;
+        remove_empty_dirs(&self.snippets_dir(), from)?;
+        Ok(())
+    }
+    /// Lists the entries of `snippets/` that should be snippet directories, with their paths relative to it.
+    ///
+    /// Namespace directories are descended into instead of being listed.
+    fn snippet_entries(&self) -> NibbResult<Vec<(String, PathBuf)>> {
+        let mut entries = Vec::new();
+        // a repository opened read-only may lack it, e.g. a clone, since git does not keep empty directories
+        if self.read_only && !self.snippets_dir().exists() {
+            return Ok(entries);
+        }
+        let mut dirs = vec![(String::new(), self.snippets_dir())];
+        while let Some((prefix, dir)) = dirs.pop() {
+            let read = fs::read_dir(&dir).map_err(|e| NibbError::NotFound(format!("{}:{:?}", e, &dir)))?;
+            for entry in read {
+                let entry = entry.map_err(|e| NibbError::NotFound(format!("{}:{:?}", e, &dir)))?;
+                let name = format!("{}{}", prefix, entry.file_name().to_string_lossy());
+                match is_namespace_dir(&entry.path()) {
+                    true => dirs.push((format!("{}/", name), entry.path())),
+                    false => entries.push((name, entry.path())),
+                }
+            }
+        }
+        Ok(entries)
+    }
+    /// Returns `path` relative to the repository root.
+    fn relative(&self, path: &Path) -> PathBuf {
+        path.strip_prefix(&self.base_dir).unwrap_or(path).to_path_buf()
+    }
+    /// Moves an entry of `snippets/` to the same place below `lost_found`.
+    fn move_aside(&self, path: &Path, lost_found: &Path) -> NibbResult<()> {
+        let target = lost_found.join(path.strip_prefix(self.snippets_dir()).unwrap_or(path));
+        if let Some(parent) = target.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::rename(path, &target)?;
+        Ok(())
+    }
+    /// Reads the content belonging to `meta` from `dir`.
+    ///
+    /// Multi-file snippets are recognized by their `files.toml`, all others are read from `content.<ext>`.
+    fn read_content(&self, dir: &Path, mut meta: Meta) -> NibbResult<Snippet> {
+        self.unseal_meta(&mut meta)?;
+        let manifest_path = dir.join("files.toml");
+        if manifest_path.exists() {
+            let mut files = Vec::new();
+            for entry in read_manifest(&manifest_path)?.files {
+                let file_path = dir.join("files").join(&entry.name);
+                let bytes = fs::read(&file_path)
+                    .map_err(|e| NibbError::NotFound(format!("{}:{:?}", e, &file_path)))?;
+                let Some(bytes) = self.unseal(bytes)? else {
+                    return Ok(Snippet::new(meta, SnippetContent::Locked));
+                };
+                let content = String::from_utf8(bytes)
+                    .map_err(|e| NibbError::NotFound(format!("{}:{:?}", e, &file_path)))?;
+                files.push(SnippetFile { name: entry.name, language: entry.language, content });
+            }
+            return Ok(Snippet::new(meta, files));
+        }
+        let content_path = dir.join(format!("content.{}", meta.get_content_extension()));
+        let bytes = fs::read(&content_path)
+            .map_err(|e| NibbError::NotFound(format!("{}:{:?}", e, &content_path)))?;
+        let Some(bytes) = self.unseal(bytes)? else {
+            return Ok(Snippet::new(meta, SnippetContent::Locked));
+        };
+        // content that is not UTF-8 is binary, whatever the language says
+        match meta.language {
+            FileType::Binary => Ok(Snippet::new(meta, BinaryContent::new(bytes))),
+            _ => match String::from_utf8(bytes) {
+                Ok(text) => Ok(Snippet::new(meta, text)),
+                Err(e) => Ok(Snippet::new(meta, BinaryContent::new(e.into_bytes()))),
+            },
+        }
+    }
+    /// Loads `index.json` and brings it up to date with `snippets/`.
+    ///
+    /// Entries whose `meta.toml` changed since they were cached are read again,
+    /// entries of removed snippets are dropped. The index is written back if anything changed,
+    /// and rebuilt from scratch if it is missing or unreadable.
+    ///
+    /// Entries of `snippets/` whose metadata cannot be read are left out of the index
+    /// and returned alongside it, with the name they have on disk.
+    fn refreshed_index(&self) -> NibbResult<(SnippetIndex, Vec<(String, NibbError)>)> {
+        let mut index = SnippetIndex::read(&self.index_path()).unwrap_or_default();
+        let mut changed = false;
+        let mut slugs = BTreeSet::new();
+        let mut failures = Vec::new();
+
+        // stale entries are read in parallel, `Ok(None)` means the cached one is up to date
+        let entries = self.snippet_entries()?;
+        let refreshed: Vec<NibbResult<Option<IndexEntry>>> = entries.par_iter()
+            .map(|(file_name, _)| {
+                let snippet_path = self.snippet_path(&slugify_path(file_name));
+                let meta_path = snippet_path.join("meta.toml");
+                let stamp = FileStamp::of(&meta_path)
+                    .map_err(|e| NibbError::NotFound(format!("{}:{:?}", e, &meta_path)))?;
+                match index.entries.get(&slugify_path(file_name)) {
+                    Some(cached) if cached.stamp == stamp => Ok(None),
+                    _ => Ok(Some(IndexEntry { meta: self.read_meta(&snippet_path)?, stamp })),
+                }
+            })
+            .collect();
+
+        for ((file_name, _), entry) in entries.into_iter().zip(refreshed) {
+            let slug = slugify_path(&file_name);
+            match entry {
+                Ok(None) => {}
+                Ok(Some(entry)) => {
+                    index.entries.insert(slug.clone(), entry);
+                    changed = true;
+                }
+                Err(e) => {
+                    failures.push((file_name, e));
+                    continue;
+                }
+            }
+            slugs.insert(slug);
+        }
+        let before = index.entries.len();
+        index.entries.retain(|slug, _| slugs.contains(slug));
+        changed |= index.entries.len() != before;
+
+        if changed && !self.read_only {
+            index.write(&self.index_path())?;
+        }
+        Ok((index, failures))
+    }
+    /// Like [`refreshed_index()`](Self::refreshed_index), but fails on the first unreadable entry.
+    fn refreshed_index_strict(&self) -> NibbResult<SnippetIndex> {
+        let (index, failures) = self.refreshed_index()?;
+        match failures.into_iter().next() {
+            Some((_, e)) => Err(e),
+            None => Ok(index),
+        }
+    }
+    /// Updates the entry of `slug` in `index.json` after a write, or removes it if the snippet is gone.
+    ///
+    /// The metadata is read back from `meta.toml`, since the index caches it as stored,
+    /// e.g. with an encrypted description. Does nothing if there is no usable index yet,
+    /// the next read builds it.
+    fn update_index(&self, slug: &str) -> NibbResult<()> {
+        let Some(mut index) = SnippetIndex::read(&self.index_path()) else {
+            return Ok(());
+        };
         let snippet_path = self.snippet_path(slug);
-        snippet_path.join(format!("content.{}", extension))
+        match snippet_path.exists() {
+            true => {
+                let stamp = FileStamp::of(&snippet_path.join("meta.toml"))?;
+                let meta = self.read_meta(&snippet_path)?;
+                index.entries.insert(slug.to_string(), IndexEntry { meta, stamp });
+            }
+            false => {
+                index.entries.remove(slug);
+            }
+        }
+        index.write(&self.index_path())
     }
-    fn get_meta_path(&self, slug: &str) -> PathBuf {
+    /// Writes a snippet into `dir`, which must already exist and be empty.
+    ///
+    /// Content files, and the description if `encryption.encrypt_description` is set,
+    /// of private snippets are encrypted if `encryption.enabled` is set.
+    fn write_snippet(&self, dir: &Path, snippet: &Snippet) -> NibbResult<()> {
+        let cipher = self.sealing_cipher(&snippet.meta)?;
+        // plain text is escaped whatever the configuration, since it is always unframed when read
+        let seal = |data: &[u8]| match cipher {
+            Some(cipher) => cipher.encrypt(data),
+            None => Ok(frame_plain(data)),
+        };
+        let mut meta = snippet.meta.clone();
+        if let Some(cipher) = cipher && self.config.encryption.encrypt_description && !meta.description.is_empty() {
+            meta.description = cipher.encrypt_str(&meta.description)?;
+        }
+        let meta_path = dir.join("meta.toml");
+        write_synced(&meta_path, toml::to_string(&meta)?.as_bytes())
+            .map_err(|e| NibbError::NotFound(format!("{}:{:?}", e, &meta_path)))?;
+
+        match &snippet.content {
+            SnippetContent::Text(text) => {
+                let content_path = dir.join(format!("content.{}", snippet.meta.get_content_extension()));
+                write_synced(&content_path, &seal(text.as_bytes())?)
+                    .map_err(|e| NibbError::NotFound(format!("{}:{:?}", e, &content_path)))?;
+            }
+            SnippetContent::Binary(binary) => {
+                let content_path = dir.join(format!("content.{}", snippet.meta.get_content_extension()));
+                write_synced(&content_path, &seal(&binary.data)?)
+                    .map_err(|e| NibbError::NotFound(format!("{}:{:?}", e, &content_path)))?;
+            }
+            SnippetContent::Files(files) => {
+                let files_dir = dir.join("files");
+                fs::create_dir(&files_dir)?;
+                for file in files {
+                    let file_path = files_dir.join(&file.name);
+                    write_synced(&file_path, &seal(file.content.as_bytes())?)
+                        .map_err(|e| NibbError::NotFound(format!("{}:{:?}", e, &file_path)))?;
+                }
+                let manifest = FilesManifest {
+                    files: files.iter()
+                        .map(|file| ManifestEntry { name: file.name.clone(), language: file.language })
+                        .collect(),
+                };
+                let manifest_path = dir.join("files.toml");
+                write_synced(&manifest_path, toml::to_string(&manifest)?.as_bytes())
+                    .map_err(|e| NibbError::NotFound(format!("{}:{:?}", e, &manifest_path)))?;
+            }
+            SnippetContent::Locked => {
+                snippet.content.validate()?;
+            }
+        }
+        Ok(())
+    }
+    /// Replaces `snippets/<slug>/` with a freshly written copy of `snippet`.
+    ///
+    /// The snippet is written to `tmp/<slug>.<random>.new/` first. Only once that succeeded,
+    /// the current directory is moved aside to `tmp/<slug>.<random>.old/` and the staged one
+    /// renamed into its place. If that rename fails, the previous version is moved back.
+    /// A crash in between is repaired by [`recover_staging()`](Self::recover_staging).
+    ///
+    /// Returns the path of the previous version, if there was one.
+    fn write_snippet_atomic(&self, slug: &str, snippet: &Snippet) -> NibbResult<Option<PathBuf>> {
+        let staging = self.stage_snippet(slug, snippet)?;
+        self.swap_in(slug, staging)
+    }
+    /// Writes a snippet to a new `tmp/<slug>.<random>.new/` staging directory.
+    ///
+    /// The directory is removed when the returned [`TempDir`](tempfile::TempDir) is dropped.
+    fn stage_snippet(&self, slug: &str, snippet: &Snippet) -> NibbResult<tempfile::TempDir> {
+        // `tmp/` is flat, so the namespace separators are replaced
+        let staging = tempfile::Builder::new()
+            .prefix(&format!("{}.", slug.replace('/', "+")))
+            .suffix(".new")
+            .tempdir_in(self.tmp_dir())?;
+        self.write_snippet(staging.path(), snippet)?;
+        Ok(staging)
+    }
+    /// Replaces `snippets/<slug>/` with a directory staged by [`stage_snippet()`](Self::stage_snippet),
+    /// see [`write_snippet_atomic()`](Self::write_snippet_atomic).
+    ///
+    /// Returns the path of the previous version, if there was one.
+    fn swap_in(&self, slug: &str, staging: tempfile::TempDir) -> NibbResult<Option<PathBuf>> {
         let snippet_path = self.snippet_path(slug);
-        snippet_path.join("meta.toml")
+        if let Some(parent) = snippet_path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let backup_path = staging.path().with_extension("old");
+        let previous = if snippet_path.exists() {
+            fs::rename(&snippet_path, &backup_path)?;
+            Some(backup_path)
+        } else {
+            None
+        };
+        if let Err(e) = fs::rename(staging.path(), &snippet_path) {
+            if let Some(previous) = &previous {
+                fs::rename(previous, &snippet_path)?;
+            }
+            return Err(NibbError::NotFound(format!("{}:{:?}", e, &snippet_path)));
+        }
+        // the staging directory now is the snippet directory
+        let _ = staging.keep();
+        Ok(previous)
+    }
+    /// Records the previous versions of a swapped-in batch as revisions, updates the index and commits the batch,
+    /// see [`save_all()`](SnippetRepository::save_all).
+    ///
+    /// Previous versions are moved into `history/` without pruning it, and their paths in `swapped` updated,
+    /// so [`roll_back()`](Self::roll_back) can still put them back. Nothing is pushed.
+    fn finish_batch(&self, claimed: &[Snippet], swapped: &mut [(String, Option<PathBuf>)]) -> NibbResult<()> {
+        if self.config.history.enabled {
+            for (slug, previous) in swapped.iter_mut() {
+                if let Some(previous) = previous {
+                    let revision_path = self.next_revision_path(slug)?;
+                    fs::rename(&*previous, &revision_path)?;
+                    *previous = revision_path;
+                }
+            }
+        }
+        for (slug, _) in swapped.iter() {
+            self.update_index(slug)?;
+        }
+        self.commit_batch(claimed)
+    }
+    /// Undoes [`swap_in()`](Self::swap_in) for the snippets of a batch, last one first, after `error`.
+    ///
+    /// Each `(slug, previous)` pair is put back the way it was: the previous version is moved back
+    /// into place, or the snippet directory is removed if there was none. The index is updated accordingly.
+    /// Returns `error`, along with the error of the rollback if that fails as well.
+    fn roll_back(&self, swapped: &[(String, Option<PathBuf>)], error: NibbError) -> NibbError {
+        let roll_back = || -> NibbResult<()> {
+            for (slug, previous) in swapped.iter().rev() {
+                let snippet_path = self.snippet_path(slug);
+                fs::remove_dir_all(&snippet_path)?;
+                match previous {
+                    Some(previous) => {
+                        fs::rename(previous, &snippet_path)?;
+                        // recording the revision may have created `history/<slug>/`
+                        if previous.starts_with(self.history_dir()) {
+                            remove_empty_dirs(&self.history_dir(), slug)?;
+                        }
+                    }
+                    None => remove_empty_dirs(&self.snippets_dir(), slug)?,
+                }
+                self.update_index(slug)?;
+            }
+            Ok(())
+        };
+        match roll_back() {
+            Ok(()) => error,
+            Err(rollback) => NibbError::RollbackFailed { error: Box::new(error), rollback: Box::new(rollback) },
+        }
+    }
+    /// Cleans up saves that were interrupted by a crash.
+    ///
+    /// Leftover `*.new` staging directories are removed. A leftover `*.old` directory is put
+    /// back into `snippets/` if the snippet is missing, or recorded as a revision otherwise.
+    fn recover_staging(&self) -> NibbResult<()> {
+        for entry in fs::read_dir(self.tmp_dir())? {
+            let path = entry?.path();
+            let Some(file_name) = path.file_name().and_then(|n| n.to_str()) else {
+                continue;
+            };
+            let slug = file_name.split('.').next().unwrap_or_default().replace('+', "/");
+            if file_name.ends_with(".new") {
+                fs::remove_dir_all(&path)?;
+            } else if file_name.ends_with(".old") {
+                let snippet_path = self.snippet_path(&slug);
+                if snippet_path.exists() {
+                    self.record_revision(&slug, &path)?;
+                } else {
+                    if let Some(parent) = snippet_path.parent() {
+                        fs::create_dir_all(parent)?;
+                    }
+                    fs::rename(&path, snippet_path)?;
+                }
+            }
+        }
+        Ok(())
+    }
+    /// Moves `dir`, a previous version of a snippet, into `history/<slug>/<revision>/`.
+    ///
+    /// Removes `dir` instead if history is disabled.
+    fn record_revision(&self, slug: &str, dir: &Path) -> NibbResult<()> {
+        if !self.config.history.enabled {
+            fs::remove_dir_all(dir)?;
+            return Ok(());
+        }
+        fs::rename(dir, self.next_revision_path(slug)?)?;
+        self.prune_history(slug)
+    }
+    /// Copies `dir`, the current version of a snippet, into `history/<slug>/<revision>/`.
+    ///
+    /// Does nothing if history is disabled.
+    fn copy_revision(&self, slug: &str, dir: &Path) -> NibbResult<()> {
+        if !self.config.history.enabled {
+            return Ok(());
+        }
+        copy_dir_all(dir, &self.next_revision_path(slug)?)?;
+        self.prune_history(slug)
+    }
+    /// Returns the path of a new revision of `slug`, creating `history/<slug>/` if needed.
+    fn next_revision_path(&self, slug: &str) -> NibbResult<PathBuf> {
+        let mut recorded = Utc::now();
+        let mut revision_path = self.history_path(slug).join(Revision::new(slug, recorded).id);
+        // two saves within the clock's resolution must not share a revision
+        while revision_path.exists() {
+            recorded += chrono::Duration::nanoseconds(1);
+            revision_path = self.history_path(slug).join(Revision::new(slug, recorded).id);
+        }
+        fs::create_dir_all(self.history_path(slug))?;
+        Ok(revision_path)
+    }
+    /// Moves `dir`, a deleted snippet, into `trash/<slug>/<id>/`.
+    fn move_to_trash(&self, slug: &str, dir: &Path) -> NibbResult<()> {
+        let mut deleted = Utc::now();
+        let mut trash_path = self.trash_path(slug).join(TrashEntry::new(slug, deleted).id);
+        // two deletions within the clock's resolution must not share an entry
+        while trash_path.exists() {
+            deleted += chrono::Duration::nanoseconds(1);
+            trash_path = self.trash_path(slug).join(TrashEntry::new(slug, deleted).id);
+        }
+        fs::create_dir_all(self.trash_path(slug))?;
+        fs::rename(dir, &trash_path)?;
+        Ok(())
+    }
+    fn list_trash_unlocked(&self) -> NibbResult<Vec<TrashEntry>> {
+        let mut entries = Vec::new();
+        // a repository opened read-only may lack it, e.g. a clone, since git does not keep empty directories
+        if self.read_only && !self.trash_dir().exists() {
+            return Ok(entries);
+        }
+        // `trash/<slug>/<id>/`, where the slug may span several directories
+        let mut dirs = vec![(String::new(), self.trash_dir())];
+        while let Some((slug, dir)) = dirs.pop() {
+            for entry in fs::read_dir(&dir)? {
+                let entry = entry?;
+                let Some(name) = entry.file_name().to_str().map(|s| s.to_string()) else {
+                    continue;
+                };
+                if !entry.path().is_dir() {
+                    continue;
+                }
+                match TrashEntry::from_id(&slug, &name).filter(|_| !slug.is_empty()) {
+                    Some(trash_entry) => entries.push(trash_entry),
+                    None if slug.is_empty() => dirs.push((name, entry.path())),
+                    None => dirs.push((format!("{}/{}", slug, name), entry.path())),
+                }
+            }
+        }
+        entries.sort_by(|a, b| a.id.cmp(&b.id).then_with(|| a.slug.cmp(&b.slug)));
+        Ok(entries)
+    }
+    /// Moves all revisions of `old_slug` to `new_slug`, so the history follows a renamed snippet.
+    fn move_history(&self, old_slug: &str, new_slug: &str) -> NibbResult<()> {
+        let old_history = self.history_path(old_slug);
+        if !old_history.exists() {
+            return Ok(());
+        }
+        let new_history = self.history_path(new_slug);
+        fs::create_dir_all(&new_history)?;
+        // revisions left behind under the new slug, e.g. by a deleted snippet, are kept
+        for revision in self.list_revisions_unlocked(old_slug)? {
+            let moved = Revision::first_free(new_slug, revision.recorded, |id| Ok(new_history.join(id).exists()))?;
+            fs::rename(old_history.join(&revision.id), new_history.join(&moved.id))?;
+        }
+        // the history of snippets in a namespace of the same name stays
+        remove_empty_dirs(&self.history_dir(), old_slug)?;
+        Ok(())
+    }
+    /// Removes the oldest revisions of a snippet beyond `history.max_revisions`.
+    fn prune_history(&self, slug: &str) -> NibbResult<()> {
+        let max = self.config.history.max_revisions;
+        if max == 0 {
+            return Ok(());
+        }
+        let revisions = self.list_revisions_unlocked(slug)?;
+        if revisions.len() <= max {
+            return Ok(());
+        }
+        for revision in &revisions[..revisions.len() - max] {
+            fs::remove_dir_all(self.history_path(slug).join(&revision.id))?;
+        }
+        Ok(())
     }
 }
 
 impl SnippetRepository for FSRepo {
-    /// Loads all snippets by iterating through the `snippets/` directory
-    /// and deserializing each snippet from `meta.toml` and its content file.
+    /// Loads all snippets in the `snippets/` directory, ordered by slug.
+    ///
+    /// Metadata is taken from `index.json` where it is up to date, content is read from each content file.
+    /// Snippets are read in parallel. If several cannot be read, the error of the first one by slug is returned.
     fn load_all(&self) -> NibbResult<Vec<Snippet>> {
-        let entries = std::fs::read_dir(self.snippets_dir())
-            .map_err(|e| NibbError::NotFound(format!("{}:{:?}", e.to_string(), self.snippets_dir())))?;
-        let mut snippets = Vec::new();
-        for entry in entries {
-            let entry = entry.map_err(|e| NibbError::NotFound(format!("{}:{:?}", e.to_string(), self.snippets_dir())))?;
-            let slug = slugify(entry.file_name().to_str().unwrap());
-            snippets.push(self.load(&slug)?);
+        let _lock = self.read_lock()?;
+        let index = self.refreshed_index_strict()?;
+        let snippets: Vec<NibbResult<Snippet>> = index.entries.into_par_iter()
+            .map(|(slug, entry)| self.read_content(&self.snippet_path(&slug), entry.meta))
+            .collect();
+        // the first error in slug order, as if the snippets had been read one by one
+        snippets.into_iter().collect()
+    }
+    /// Loads all readable snippets in the `snippets/` directory, ordered by slug.
+    ///
+    /// Unparsable or missing `meta.toml` files, missing content files and stray files
+    /// are reported as diagnostics instead of failing the whole call.
+    fn load_all_lenient(&self) -> NibbResult<LoadReport> {
+        let _lock = self.read_lock()?;
+        let (index, failures) = self.refreshed_index()?;
+        let mut report = LoadReport::default();
+        for (slug, e) in failures {
+            report.diagnostics.push(LoadDiagnostic::new(&slug, &e));
         }
-        Ok(snippets)
+        let snippets: Vec<(String, NibbResult<Snippet>)> = index.entries.into_par_iter()
+            .map(|(slug, entry)| {
+                let snippet = self.read_content(&self.snippet_path(&slug), entry.meta);
+                (slug, snippet)
+            })
+            .collect();
+        for (slug, snippet) in snippets {
+            match snippet {
+                Ok(snippet) => report.snippets.push(snippet),
+                Err(e) => report.diagnostics.push(LoadDiagnostic::new(&slug, &e)),
+            }
+        }
+        report.diagnostics.sort_by(|a, b| a.slug.cmp(&b.slug));
+        Ok(report)
+    }
+    /// Returns the slug and [`Meta`] of every readable snippet, ordered by slug.
+    ///
+    /// Served from `index.json`, which is refreshed as needed. No content file is read.
+    fn list_meta(&self) -> NibbResult<Vec<(String, Meta)>> {
+        Ok(self.list_meta_lenient()?.metas)
+    }
+    /// Returns the slug and [`Meta`] of every readable snippet like [`list_meta()`](Self::list_meta),
+    /// along with a diagnostic for each entry whose `meta.toml` cannot be read or decrypted.
+    fn list_meta_lenient(&self) -> NibbResult<MetaReport> {
+        let _lock = self.read_lock()?;
+        let (index, failures) = self.refreshed_index()?;
+        let mut report = MetaReport::default();
+        for (slug, e) in failures {
+            report.diagnostics.push(LoadDiagnostic::new(&slug, &e));
+        }
+        for (slug, mut entry) in index.entries {
+            match self.unseal_meta(&mut entry.meta) {
+                Ok(()) => report.metas.push((slug, entry.meta)),
+                Err(e) => report.diagnostics.push(LoadDiagnostic::new(&slug, &e)),
+            }
+        }
+        report.diagnostics.sort_by(|a, b| a.slug.cmp(&b.slug));
+        Ok(report)
     }
     /// Loads a specific snippet by slug.
     ///
     /// Reads metadata from `meta.toml` and content from `content.<ext>`.
     fn load(&self, slug: &str) -> NibbResult<Snippet> {
-        let slug = slugify(slug); // just to be sure
-        let meta_path = self.get_meta_path(&slug);
-        let meta_str = std::fs::read_to_string(&meta_path)
-            .map_err(|e| NibbError::NotFound(format!("{}:{:?}", e.to_string(), &meta_path)))?;
-        let meta: Meta = toml::from_str(&meta_str)
-            .map_err(|e| NibbError::NotFound(format!("{}:{:?}", e.to_string(), &meta_path)))?;
-
-        let content_path = self.get_content_path(&slug, &meta.get_content_extension());
-        let content = std::fs::read_to_string(&content_path)
-            .map_err(|e| NibbError::NotFound(format!("{}:{:?}", e.to_string(), &content_path)))?;
-        Ok(Snippet {
-            meta,
-            content,
-        })
+        let _lock = self.read_lock()?;
+        self.load_unlocked(slug)
+    }
+    /// Loads a specific snippet by ID, looking up its slug in `index.json`.
+    fn load_by_id(&self, id: Uuid) -> NibbResult<Snippet> {
+        let _lock = self.read_lock()?;
+        let (index, _) = self.refreshed_index()?;
+        let (slug, entry) = index.entries.into_iter()
+            .find(|(_, entry)| entry.meta.id == id)
+            .ok_or(NibbError::NotFound(format!("snippet:{}", id)))?;
+        self.read_content(&self.snippet_path(&slug), entry.meta)
     }
     /// Saves a single snippet to disk.
     ///
-    /// Creates the snippet folder and both metadata/content files if they don't exist.
+    /// The snippet is staged in `tmp/` and then swapped in, so a failed or interrupted save
+    /// leaves the previous version intact. The previous version is recorded in `history/`.
     fn save(&self, snippet: &Snippet) -> NibbResult<()> {
-        let slug = snippet.meta.get_slug();
-        let snippet_path = self.snippet_path(&slug);
+        self.save_linking(snippet, None, &[])
+    }
+    /// Saves a single snippet like [`save()`](Self::save) if the stored version matches `expected`.
+    ///
+    /// The stored version is compared while holding the repository lock, so no other writer can slip in.
+    fn save_if_unmodified(&self, snippet: &Snippet, expected: &ExpectedVersion) -> NibbResult<()> {
+        self.save_linking(snippet, Some(expected), &[])
+    }
+    /// Saves a single snippet like [`save()`](Self::save) or [`save_if_unmodified()`](Self::save_if_unmodified),
+    /// accepting links to `linkable` as well.
+    fn save_linking(&self, snippet: &Snippet, expected: Option<&ExpectedVersion>, linkable: &[Meta]) -> NibbResult<()> {
+        let _lock = self.write_lock()?;
+        if let Some(expected) = expected {
+            let slug = snippet.meta.get_slug();
+            let current = match self.snippet_path(&slug).exists() {
+                true => Some(self.load_unlocked(&slug)?),
+                false => None,
+            };
+            check_unmodified(snippet, current, expected)?;
+        }
+        self.check_links_unlocked(std::slice::from_ref(snippet), linkable)?;
+        self.save_unlocked(snippet)
+    }
+    /// Saves a list of snippets, either all of them or none.
+    ///
+    /// Holds the repository lock for the whole batch. Every snippet is checked like in [`save()`](Self::save)
+    /// and staged in `tmp/` before the first one is swapped in. If a swap, recording the revisions,
+    /// updating the index or committing fails, the snippets swapped so far are put back.
+    /// Links may point to other snippets of the batch.
+    /// The whole batch is committed at once when Git is enabled. A failed push leaves it saved and committed.
+    fn save_all(&self, snippets: &[Snippet]) -> NibbResult<()> {
+        self.save_all_linking(snippets, &[])
+    }
+    /// Saves a list of snippets like [`save_all()`](Self::save_all), accepting links to `linkable` as well.
+    fn save_all_linking(&self, snippets: &[Snippet], linkable: &[Meta]) -> NibbResult<()> {
+        let _lock = self.write_lock()?;
+        check_batch_slugs(snippets)?;
+        self.check_links_unlocked(snippets, linkable)?;
+        let mut claimed = Vec::with_capacity(snippets.len());
+        for snippet in snippets {
+            let slug = snippet.meta.get_slug();
+            claimed.push(self.claim_unlocked(&slug, snippet)?);
+        }
+        let staged = claimed.iter()
+            .map(|snippet| self.stage_snippet(&snippet.meta.get_slug(), snippet))
+            .collect::<NibbResult<Vec<_>>>()?;
 
+        let mut swapped = Vec::with_capacity(staged.len());
+        for (snippet, staging) in claimed.iter().zip(staged) {
+            let slug = snippet.meta.get_slug();
+            match self.swap_in(&slug, staging) {
+                Ok(previous) => swapped.push((slug, previous)),
+                Err(e) => return Err(self.roll_back(&swapped, e)),
+            }
+        }
+        if let Err(e) = self.finish_batch(&claimed, &mut swapped) {
+            return Err(self.roll_back(&swapped, e));
+        }
+        // nothing is rolled back from here on, so the previous versions can go
+        for (slug, previous) in &swapped {
+            match (self.config.history.enabled, previous) {
+                (true, Some(_)) => self.prune_history(slug)?,
+                (false, Some(previous)) => fs::remove_dir_all(previous)?,
+                (_, None) => {}
+            }
+        }
+        match self.git_repo.is_some() && self.config.git.auto_commit {
+            true => self.auto_push(),
+            false => Ok(()),
+        }
+    }
+    /// Moves the snippet directory into `trash/<slug>/`, after copying it into `history/<slug>/`.
+    fn delete(&self, slug: &str) -> NibbResult<()> {
+        let _lock = self.write_lock()?;
+        let slug = slugify_path(slug); // just to be sure
+        let snippet_path = self.snippet_path(&slug);
         if !snippet_path.exists() {
-            fs::create_dir_all(&snippet_path)
-                .map_err(|e| NibbError::NotFound(format!("{}:{:?}", e.to_string(), &snippet_path)))?;
+            return Err(NibbError::NotFound(format!("snippet:{:?}", &snippet_path)));
         }
+        self.copy_revision(&slug, &snippet_path)?;
+        self.move_to_trash(&slug, &snippet_path)?;
+        remove_empty_dirs(&self.snippets_dir(), &slug)?;
+        self.update_index(&slug)
+    }
+    /// Renames a snippet by moving `snippets/<slug>/` and its history to the new slug,
+    /// then rewriting its `meta.toml` and the links of other snippets pointing to the old slug.
+    /// The move is committed when Git is enabled.
+    fn rename(&self, slug: &str, new_name: &str) -> NibbResult<Snippet> {
+        let _lock = self.write_lock()?;
+        let old_slug = slugify_path(slug); // just to be sure
+        let mut snippet = self.load_unlocked(&old_slug)?;
+        let previous = snippet.meta.clone();
+        snippet.meta.rename(new_name);
+        let new_slug = snippet.meta.get_slug();
+        if new_slug.is_empty() {
+            return Err(NibbError::InvalidSlug(format!("'{}' does not produce a slug", new_name)));
+        }
+        // stamped like a save, and locked content cannot be written back, so fail before anything is moved
+        let snippet = claim_slug(&snippet, Some(&previous))?;
+        if new_slug != old_slug {
+            if self.snippet_path(&new_slug).exists() {
+                return Err(NibbError::InvalidSlug(format!("{} is already taken", new_slug)));
+            }
+            self.check_namespace(&new_slug)?;
+            self.move_history(&old_slug, &new_slug)?;
+            self.move_snippet_dir(&old_slug, &new_slug)?;
+            self.update_index(&old_slug)?;
+        }
+        if let Some(previous) = self.write_snippet_atomic(&new_slug, &snippet)? {
+            self.record_revision(&new_slug, &previous)?;
+        }
+        self.update_index(&new_slug)?;
+        let retargeted = match new_slug != old_slug {
+            true => self.retarget_links(&old_slug, &new_slug)?,
+            false => Vec::new(),
+        };
 
-        let meta_path = self.get_meta_path(&slug);
-        fs::write(&meta_path, toml::to_string(&snippet.meta)?)
-            .map_err(|e| NibbError::NotFound(format!("{}:{:?}", e.to_string(), &meta_path)))?;
-
-        for entry in fs::read_dir(&snippet_path)? {
-            let entry = entry?;
-            let path = entry.path();
+        self.auto_commit_rename(&old_slug, &snippet, &retargeted)?;
+        Ok(snippet)
+    }
+    /// Lists the revisions stored in `history/<slug>/`, oldest first.
+    ///
+    /// Returns an empty list if the snippet has no recorded history.
+    fn list_revisions(&self, slug: &str) -> NibbResult<Vec<Revision>> {
+        let _lock = self.read_lock()?;
+        self.list_revisions_unlocked(slug)
+    }
+    /// Loads a revision from `history/<slug>/<revision>/`.
+    fn load_revision(&self, slug: &str, revision: &str) -> NibbResult<Snippet> {
+        let _lock = self.read_lock()?;
+        self.load_revision_unlocked(slug, revision)
+    }
+    /// Restores a revision by saving it over the current version of the snippet.
+    ///
+    /// The revision keeps the current name and namespace, see [`restore_under()`].
+    fn restore_revision(&self, slug: &str, revision: &str) -> NibbResult<()> {
+        let _lock = self.write_lock()?;
+        let slug = slugify_path(slug); // just to be sure
+        let snippet = self.load_revision_unlocked(&slug, revision)?;
+        // a snippet with unreadable metadata is overwritten under its slug
+        let snippet_path = self.snippet_path(&slug);
+        let current = match snippet_path.exists() {
+            true => self.read_meta(&snippet_path).ok(),
+            false => None,
+        };
+        self.save_unlocked(&restore_under(&slug, snippet, current.as_ref()))
+    }
+    /// Lists the entries of `trash/`, oldest deletion first.
+    fn list_trash(&self) -> NibbResult<Vec<TrashEntry>> {
+        let _lock = self.read_lock()?;
+        self.list_trash_unlocked()
+    }
+    /// Moves the most recently deleted version of a snippet from `trash/<slug>/` back into `snippets/`.
+    fn restore_trash(&self, slug: &str) -> NibbResult<Snippet> {
+        let _lock = self.write_lock()?;
+        let slug = slugify_path(slug); // just to be sure
+        let entry = self.list_trash_unlocked()?
+            .into_iter()
+            .rfind(|entry| entry.slug == slug)
+            .ok_or(NibbError::NotFound(format!("trash:{}", slug)))?;
+        let snippet_path = self.snippet_path(&slug);
+        if snippet_path.exists() {
+            return Err(NibbError::InvalidSlug(format!("{} is already taken", slug)));
+        }
+        self.check_namespace(&slug)?;
+        if let Some(parent) = snippet_path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::rename(self.trash_path(&slug).join(&entry.id), &snippet_path)?;
+        remove_empty_dirs(&self.trash_dir(), &slug)?;
+        let snippet = self.read_snippet(&snippet_path)?;
+        self.update_index(&slug)?;
 
-            if let Some(file_name) = path.file_name().and_then(|n| n.to_str()) {
-                if file_name.starts_with("content.") {
-                    fs::remove_file(&path)
-                        .map_err(|e| NibbError::NotFound(format!("Failed to remove old content file {}: {}", path.display(), e)))?;
-                }
+        self.auto_commit(&snippet)?;
+        Ok(snippet)
+    }
+    /// Removes entries of `trash/`, optionally only those deleted at least `older_than` ago.
+    fn empty_trash(&self, older_than: Option<chrono::Duration>) -> NibbResult<usize> {
+        let _lock = self.write_lock()?;
+        let cutoff = trash_cutoff(older_than);
+        let mut removed = 0;
+        for entry in self.list_trash_unlocked()? {
+            if cutoff.is_none_or(|cutoff| entry.deleted <= cutoff) {
+                fs::remove_dir_all(self.trash_path(&entry.slug).join(&entry.id))?;
+                remove_empty_dirs(&self.trash_dir(), &entry.slug)?;
+                removed += 1;
             }
         }
+        Ok(removed)
+    }
+}
+
+/// Layout of `files.toml`, listing the files of a multi-file snippet stored in `files/`.
+#[derive(Serialize, Deserialize)]
+struct FilesManifest {
+    files: Vec<ManifestEntry>,
+}
 
-        let extension = snippet.meta.get_content_extension();
-        let content_path = self.get_content_path(&slug, &extension);
+#[derive(Serialize, Deserialize)]
+struct ManifestEntry {
+    name: String,
+    language: FileType,
+}
 
-        fs::write(&content_path, &snippet.content)
-            .map_err(|e| NibbError::NotFound(format!("{}:{:?}", e.to_string(), &content_path)))?;
+/// Description of a snippet whose description is encrypted, when no key is available.
+const LOCKED_DESCRIPTION: &str = "<locked>";
 
-        // git actions (handles config)
-        self.auto_commit(snippet)?;
-        Ok(())
+/// Reads `files.toml`, rejecting file names that cannot be used in `files/`.
+fn read_manifest(manifest_path: &Path) -> NibbResult<FilesManifest> {
+    let manifest_str = fs::read_to_string(manifest_path)
+        .map_err(|e| NibbError::NotFound(format!("{}:{:?}", e, manifest_path)))?;
+    let manifest: FilesManifest = toml::from_str(&manifest_str)
+        .map_err(|e| NibbError::NotFound(format!("{}:{:?}", e, manifest_path)))?;
+    if let Some(entry) = manifest.files.iter().find(|entry| !is_valid_file_name(&entry.name)) {
+        return Err(NibbError::InvalidFileName(format!("'{}' in {:?}", entry.name, manifest_path)));
     }
-    /// Saves a list of snippets.
-    ///
-    /// Calls [`save()`](Self::save) for each snippet.
-    fn save_all(&self, snippets: &[Snippet]) -> NibbResult<()> {
-        for snippet in snippets {
-            self.save(snippet)?;
+    Ok(manifest)
+}
+
+/// Records `issue` in `report`, fixing it with `fix` first if `repair` is set.
+fn record_issue(
+    report: &mut FsckReport,
+    mut issue: FsckIssue,
+    repair: bool,
+    fix: impl FnOnce() -> NibbResult<()>,
+) -> NibbResult<()> {
+    if repair {
+        fix()?;
+        issue.repaired = true;
+    }
+    report.issues.push(issue);
+    Ok(())
+}
+
+/// Checks that `snippet` may be written under its slug, which currently holds `existing`, if anything.
+///
+/// Returns the snippet to write: a snippet without an ID takes over the ID of the one it replaces,
+/// or gets a new one. Its `modified` timestamp is set to now.
+pub(crate) fn claim_slug(snippet: &Snippet, existing: Option<&Meta>) -> NibbResult<Snippet> {
+    let slug = snippet.meta.get_slug();
+    if slug.is_empty() {
+        return Err(NibbError::InvalidSlug(format!("'{}' does not produce a slug", snippet.meta.name)));
+    }
+    snippet.content.validate()?;
+    let mut snippet = snippet.clone();
+    snippet.meta.schema_version = META_SCHEMA_VERSION;
+    snippet.meta.namespace = slugify_path(&snippet.meta.namespace);
+    snippet.meta.modified = Utc::now();
+    match existing {
+        Some(existing) if snippet.meta.id.is_nil() => snippet.meta.id = existing.id,
+        Some(existing) if existing.id != snippet.meta.id => {
+            return Err(NibbError::InvalidSlug(format!("{} is already used by '{}'", slug, existing.name)));
         }
-        Ok(())
+        Some(_) => {}
+        None if snippet.meta.id.is_nil() => snippet.meta.id = Uuid::new_v4(),
+        None => {}
     }
-    /// Deletes the snippet directory and all its contents.
-    fn delete(&self, slug: &str) -> NibbResult<()> {
-        let slug = slugify(slug); // just to be sure
-        let snippet_path = self.snippet_path(&slug);
-        fs::remove_dir_all(snippet_path)?;
-        Ok(())
+    Ok(snippet)
+}
+
+/// Checks that no two snippets of a batch share a slug, since it is unclear which one should win,
+/// and that none of them is filed under another one, which is not stored yet.
+pub(crate) fn check_batch_slugs(snippets: &[Snippet]) -> NibbResult<()> {
+    let mut slugs = BTreeSet::new();
+    for snippet in snippets {
+        let slug = snippet.meta.get_slug();
+        if !slugs.insert(slug.clone()) {
+            return Err(NibbError::InvalidSlug(format!("{} appears more than once in the batch", slug)));
+        }
     }
+    for slug in &slugs {
+        check_namespace(slug, |parent| Ok(slugs.contains(parent)), |_| Ok(false))?;
+    }
+    Ok(())
+}
+
+/// Checks that `slug` can hold a snippet: none of its namespaces may be a snippet,
+/// and it must not be a namespace itself, i.e. no snippet may be filed below it.
+///
+/// `is_snippet` tells whether a slug holds a snippet, `is_namespace` whether snippets are filed below one.
+/// Every backend uses this, so a snippet can be moved between them without running into it.
+pub(crate) fn check_namespace(
+    slug: &str,
+    mut is_snippet: impl FnMut(&str) -> NibbResult<bool>,
+    is_namespace: impl FnOnce(&str) -> NibbResult<bool>,
+) -> NibbResult<()> {
+    let mut namespace = slug;
+    while let Some((parent, _)) = namespace.rsplit_once('/') {
+        if is_snippet(parent)? {
+            return Err(NibbError::InvalidSlug(format!("{} is a snippet, it cannot hold {}", parent, slug)));
+        }
+        namespace = parent;
+    }
+    if is_namespace(slug)? {
+        return Err(NibbError::InvalidSlug(format!("{} is a namespace", slug)));
+    }
+    Ok(())
+}
+
+/// Removes `dir` if it exists and has no entries left.
+fn remove_dir_if_empty(dir: &Path) -> std::io::Result<()> {
+    if dir.exists() && fs::read_dir(dir)?.next().is_none() {
+        fs::remove_dir(dir)?;
+    }
+    Ok(())
+}
+
+/// Removes `root/<slug>` and the namespace directories above it, as far as they have no entries left.
+fn remove_empty_dirs(root: &Path, slug: &str) -> std::io::Result<()> {
+    for dir in root.join(slug).ancestors().take_while(|dir| *dir != root) {
+        remove_dir_if_empty(dir)?;
+    }
+    Ok(())
+}
+
+/// Returns `true` if `path` is a namespace directory below `snippets/`:
+/// a directory without `meta.toml` or `files.toml` that holds at least one other directory.
+///
+/// Files next to those directories do not turn it into a snippet directory, they are stray entries of the namespace.
+pub(crate) fn is_namespace_dir(path: &Path) -> bool {
+    if !path.is_dir() || path.join("meta.toml").exists() || path.join("files.toml").exists() {
+        return false;
+    }
+    let Ok(entries) = fs::read_dir(path) else {
+        return false;
+    };
+    entries.flatten().any(|entry| entry.path().is_dir())
+}
+
+/// Returns `true` if `dir` holds snippet or namespace directories.
+///
+/// The `files/` directory of a multi-file snippet does not count.
+fn holds_snippets(dir: &Path) -> bool {
+    let multi_file = dir.join("files.toml").exists();
+    let Ok(entries) = fs::read_dir(dir) else {
+        return false;
+    };
+    entries.flatten()
+        .filter(|entry| !(multi_file && entry.file_name() == "files"))
+        .any(|entry| entry.path().join("meta.toml").is_file() || is_namespace_dir(&entry.path()))
+}
+
+/// Writes `contents` to `path` and flushes it to disk before returning.
+fn write_synced(path: &Path, contents: &[u8]) -> std::io::Result<()> {
+    let mut file = fs::File::create(path)?;
+    file.write_all(contents)?;
+    file.sync_all()
+}
+
+/// Like [`write_synced()`], but replaces `path` atomically, so concurrent readers never see a partial file.
+fn replace_synced(path: &Path, contents: &[u8]) -> std::io::Result<()> {
+    let dir = path.parent().unwrap_or(Path::new("."));
+    let mut file = tempfile::NamedTempFile::new_in(dir)?;
+    file.write_all(contents)?;
+    file.as_file().sync_all()?;
+    file.persist(path)?;
+    Ok(())
 }
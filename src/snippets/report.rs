@@ -0,0 +1,50 @@
+use serde::Serialize;
+use crate::result::NibbError;
+use crate::snippets::snippet::{Meta, Snippet};
+
+/// Outcome of [`load_all_lenient()`](crate::SnippetRepository::load_all_lenient).
+///
+/// Holds every snippet that could be loaded, plus a diagnostic for each entry that could not.
+#[derive(Debug, Default, Serialize)]
+pub struct LoadReport {
+    /// All snippets that were loaded successfully.
+    pub snippets: Vec<Snippet>,
+    /// One entry per snippet that failed to load.
+    pub diagnostics: Vec<LoadDiagnostic>,
+}
+
+/// Outcome of [`list_meta_lenient()`](crate::SnippetRepository::list_meta_lenient).
+///
+/// Holds the slug and metadata of every snippet that could be read, plus a diagnostic for each entry that could not.
+#[derive(Debug, Default, Serialize)]
+pub struct MetaReport {
+    /// Slug and metadata of each readable snippet, ordered by slug.
+    pub metas: Vec<(String, Meta)>,
+    /// One entry per snippet whose metadata failed to load.
+    pub diagnostics: Vec<LoadDiagnostic>,
+}
+
+/// Describes why a single entry of a repository could not be loaded.
+#[derive(Debug, Clone, Serialize)]
+pub struct LoadDiagnostic {
+    /// Slug of the broken snippet, or the name of the stray entry as found in the repository.
+    pub slug: String,
+    /// Human-readable description of the problem.
+    pub message: String,
+}
+
+impl LoadDiagnostic {
+    pub fn new(slug: &str, error: &NibbError) -> Self {
+        Self {
+            slug: slug.to_string(),
+            message: error.to_string(),
+        }
+    }
+}
+
+impl LoadReport {
+    pub fn to_json(&self) -> String {
+        serde_json::to_string(self)
+            .unwrap_or_else(|_| "{\"type\":\"Other\",\"message\":\"Serialization failed\"}".to_string())
+    }
+}
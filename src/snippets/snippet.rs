@@ -1,16 +1,37 @@
 use std::fmt;
+use blake2::{Blake2b256, Digest};
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 use slug::slugify;
+use uuid::Uuid;
+use crate::migration::META_SCHEMA_VERSION;
+use crate::result::{NibbError, NibbResult};
 use crate::snippets::file_type::FileType;
+use crate::snippets::links::SnippetLink;
 /// Metadata associated with a snippet.
 ///
 /// Includes name, description, tags, programming language, creation and modification timestamps,
 /// as well as visibility status (e.g., public, private).
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct Meta {
+    /// Version of the `meta.toml` schema, see [`META_SCHEMA_VERSION`].
+    ///
+    /// Always the current version when read from a repository, older files are upgraded in memory when loaded.
+    #[serde(default = "current_schema_version")]
+    pub schema_version: u32,
+    /// Stable unique identifier, generated at creation and kept across renames.
+    ///
+    /// Snippets written before IDs existed get one derived from their slug and `created`
+    /// timestamp when they are read, which is persisted on their next save.
+    #[serde(default)]
+    pub id: Uuid,
     /// Name of the snippet (used for display and slug generation).
     pub name: String,
+    /// Namespace the snippet is filed under, e.g. `rust/serde`. Empty for top-level snippets.
+    ///
+    /// Every segment is slugified when the snippet is saved, see [`slugify_path()`].
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub namespace: String,
     /// Short description of the snippet's purpose or context.
     pub description: String,
     /// Tags associated with the snippet, e.g. `["sql", "orm", "diesel"]`.
@@ -24,6 +45,9 @@ pub struct Meta {
     /// Visibility status (e.g. `Private`, `Public`, `Archived`). Defaults to `Private`.
     #[serde(default = "Visibility::default")]
     pub visibility: Visibility,
+    /// Typed references to other snippets, checked when the snippet is saved.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub links: Vec<SnippetLink>,
 }
 
 impl Meta {
@@ -37,41 +61,110 @@ impl Meta {
         language: FileType,
         visibility: Option<Visibility>) -> Self {
         Self {
+            schema_version: META_SCHEMA_VERSION,
+            id: Uuid::new_v4(),
             name,
+            namespace: String::new(),
             description,
             tags,
             language,
             created: Utc::now(),
             modified: Utc::now(),
             visibility: visibility.unwrap_or_default(),
+            links: Vec::new(),
         }
     }
     /// Returns the standard file extension (without a dot) for the snippet's language.
     pub fn get_content_extension(&self) -> String {
         self.language.extension().to_string()
     }
-    /// Returns a slugified version of the snippet's name.
+    /// Returns a slugified version of the snippet's name, prefixed by its namespace,
+    /// e.g. `rust/serde/untagged-enum`.
+    ///
+    /// Returns an empty string if the name does not produce a slug.
     pub fn get_slug(&self) -> String {
-        slugify(self.name.as_str())
+        let slug = slugify(self.name.as_str());
+        match slugify_path(&self.namespace) {
+            namespace if namespace.is_empty() || slug.is_empty() => slug,
+            namespace => format!("{}/{}", namespace, slug),
+        }
+    }
+    /// Returns `true` if the snippet is filed under `namespace` or one of its sub-namespaces.
+    ///
+    /// Every snippet is in the empty namespace.
+    pub fn is_in(&self, namespace: &str) -> bool {
+        let namespace = slugify_path(namespace);
+        let own = slugify_path(&self.namespace);
+        namespace.is_empty() || own == namespace || own.starts_with(&format!("{}/", namespace))
+    }
+    /// Renames the snippet.
+    ///
+    /// A `new_name` containing `/` moves the snippet as well: everything up to the last `/`
+    /// becomes its namespace, e.g. `rust/serde/Untagged enum`.
+    /// `modified` is left alone, repositories stamp the renamed snippet like a save.
+    pub(crate) fn rename(&mut self, new_name: &str) {
+        match new_name.rsplit_once('/') {
+            Some((namespace, name)) => {
+                self.namespace = slugify_path(namespace);
+                self.name = name.to_string();
+            }
+            None => self.name = new_name.to_string(),
+        }
+    }
+    /// Points the links to the slug `from` at `to` instead, after the target was renamed.
+    ///
+    /// Returns whether any link was changed. Links by ID are left alone.
+    pub(crate) fn retarget_links(&mut self, from: &str, to: &str) -> bool {
+        let mut changed = false;
+        for link in &mut self.links {
+            if Uuid::parse_str(&link.target).is_err() && slugify_path(&link.target) == from {
+                link.target = to.to_string();
+                changed = true;
+            }
+        }
+        changed
+    }
+    /// Fills in the ID of metadata written before IDs existed.
+    ///
+    /// The ID is derived from the slug and `created` timestamp, so it stays the same
+    /// every time such a snippet is read until it is saved with its ID.
+    pub(crate) fn ensure_id(&mut self) {
+        if self.id.is_nil() {
+            self.id = legacy_id(&self.get_slug(), &self.created);
+        }
     }
 }
 
+fn current_schema_version() -> u32 {
+    META_SCHEMA_VERSION
+}
 
+/// Slugifies every segment of a `/` separated path, dropping empty ones.
+///
+/// The result can be joined to a repository directory safely: segments like `..` slugify to nothing.
+pub fn slugify_path(path: &str) -> String {
+    path.split(['/', '\\'])
+        .map(slugify)
+        .filter(|segment| !segment.is_empty())
+        .collect::<Vec<_>>()
+        .join("/")
+}
 
-
-impl Default for Visibility {
-    fn default() -> Self {
-        Visibility::Private
-    }
+/// Derives the ID of a snippet written before IDs existed, see [`Meta::ensure_id()`].
+pub(crate) fn legacy_id(slug: &str, created: &DateTime<Utc>) -> Uuid {
+    let key = format!("{}@{}", slug, created.to_rfc3339());
+    Uuid::new_v5(&Uuid::NAMESPACE_OID, key.as_bytes())
 }
 
+
 /// Defines visibility levels for a snippet.
 ///
 /// Used to control whether a snippet is accessible to the public or not.
-#[derive(Debug, Serialize, Deserialize, Clone)]
+#[derive(Debug, Default, Serialize, Deserialize, Clone)]
 #[serde(rename_all = "lowercase")]
 pub enum Visibility {
     /// Visible only to the user.
+    #[default]
     Private,
     /// Publicly visible and accessible.
     Public,
@@ -94,28 +187,201 @@ impl From<&str> for Visibility {
 }
 /// Represents a complete code snippet, including metadata and the actual content.
 ///
-/// Combines [`Meta`] with the snippet's textual content.
+/// Combines [`Meta`] with the snippet's [`SnippetContent`].
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Snippet {
     /// Associated metadata (name, language, visibility, etc.).
     pub meta: Meta,
-    /// Raw code content of the snippet.
-    pub content: String,
+    /// Content of the snippet, either a single text or several named files.
+    pub content: SnippetContent,
+    /// Name of the layer the snippet was loaded from, set by [`LayeredRepo`](crate::LayeredRepo). Never stored.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub layer: Option<String>,
 }
 
 impl Snippet {
     /// Creates a new snippet from the given metadata and content.
-    pub fn new(meta: Meta, content: String) -> Self {
-        Self { meta, content }
+    ///
+    /// Accepts a `String` or `&str` for single-content snippets.
+    pub fn new<C: Into<SnippetContent>>(meta: Meta, content: C) -> Self {
+        Self { meta, content: content.into(), layer: None }
     }
 
     pub fn to_json(&self) -> String {
         serde_json::to_string(self)
             .unwrap_or_else(|_| "{\"type\":\"Other\",\"message\":\"Serialization failed\"}".to_string())
     }
+    /// Returns a hash of the metadata and content, to pass as [`ExpectedVersion::Hash`](crate::ExpectedVersion)
+    /// to [`save_if_unmodified()`](crate::SnippetRepository::save_if_unmodified).
+    ///
+    /// Hex-encoded BLAKE2b-256 of their JSON representation. [`layer`](Self::layer) is not part of it.
+    pub fn version_hash(&self) -> String {
+        let json = serde_json::to_vec(&(&self.meta, &self.content)).unwrap_or_default();
+        Blake2b256::digest(json).iter().map(|byte| format!("{:02x}", byte)).collect()
+    }
+}
+
+/// Content of a [`Snippet`].
+///
+/// Serialized untagged: a single text is a JSON string, several files are a JSON array,
+/// binary content is a JSON object, see [`BinaryContent`], and locked content is `null`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum SnippetContent {
+    /// A single text in the language of [`Meta::language`].
+    Text(String),
+    /// Several named files, e.g. a `Dockerfile` and an `entrypoint.sh`.
+    Files(Vec<SnippetFile>),
+    /// Raw bytes, e.g. an icon or a small test fixture.
+    Binary(BinaryContent),
+    /// Encrypted content that could not be decrypted, because no key is available.
+    /// Serialized as `null`. Cannot be saved.
+    Locked,
+}
+
+/// Content of a binary snippet.
+///
+/// Serialized as `{"mime": "image/png", "data": "<base64>"}`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BinaryContent {
+    /// MIME type detected from the data, `application/octet-stream` if it is unknown.
+    pub mime: String,
+    /// Raw bytes, base64 encoded in JSON.
+    #[serde(with = "base64_data")]
+    pub data: Vec<u8>,
+}
+
+impl BinaryContent {
+    /// Creates binary content, detecting its MIME type with `infer`.
+    pub fn new(data: Vec<u8>) -> Self {
+        let mime = infer::get(&data)
+            .map_or("application/octet-stream", |kind| kind.mime_type())
+            .to_string();
+        Self { mime, data }
+    }
+    /// Describes the content in place of the raw bytes, e.g. `<binary data: image/png, 1024 bytes>`.
+    pub fn placeholder(&self) -> String {
+        format!("<binary data: {}, {} bytes>", self.mime, self.data.len())
+    }
+}
+
+/// (De)serializes bytes as a standard base64 string.
+mod base64_data {
+    use base64::Engine;
+    use base64::engine::general_purpose::STANDARD;
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(data: &[u8], serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&STANDARD.encode(data))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<u8>, D::Error> {
+        let encoded = String::deserialize(deserializer)?;
+        STANDARD.decode(encoded).map_err(serde::de::Error::custom)
+    }
+}
+
+/// A named file of a multi-file snippet.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SnippetFile {
+    /// File name, e.g. `entrypoint.sh`. Must not contain path separators.
+    pub name: String,
+    /// Language or file type of this file.
+    pub language: FileType,
+    /// Raw content of the file.
+    pub content: String,
+}
+
+impl SnippetFile {
+    /// Creates a new file, detecting its language from the extension of `name`.
+    pub fn new(name: &str, content: String) -> Self {
+        let extension = name.rsplit_once('.').map_or(name, |(_, ext)| ext);
+        Self {
+            name: name.to_string(),
+            language: FileType::from_extension(extension),
+            content,
+        }
+    }
+}
+
+impl SnippetContent {
+    /// Returns the text of a single-content snippet.
+    pub fn as_text(&self) -> Option<&str> {
+        match self {
+            SnippetContent::Text(text) => Some(text),
+            SnippetContent::Files(_) | SnippetContent::Binary(_) | SnippetContent::Locked => None,
+        }
+    }
+    /// Checks that the content can be stored by every repository backend.
+    ///
+    /// File names have to be unique, non-empty and usable as a single path component,
+    /// and a multi-file snippet needs at least one file. Locked content cannot be stored at all.
+    pub fn validate(&self) -> NibbResult<()> {
+        let files = match self {
+            SnippetContent::Files(files) => files,
+            SnippetContent::Locked => {
+                return Err(NibbError::Locked("its content is encrypted and no key is available".to_string()));
+            }
+            _ => return Ok(()),
+        };
+        if files.is_empty() {
+            return Err(NibbError::InvalidFileName("a multi-file snippet needs at least one file".to_string()));
+        }
+        for (i, file) in files.iter().enumerate() {
+            if !is_valid_file_name(&file.name) {
+                return Err(NibbError::InvalidFileName(format!("'{}'", file.name)));
+            }
+            if files[..i].iter().any(|other| other.name == file.name) {
+                return Err(NibbError::InvalidFileName(format!("'{}' is used twice", file.name)));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Returns whether `name` can be used as the name of a file in a snippet directory.
+pub(crate) fn is_valid_file_name(name: &str) -> bool {
+    !name.is_empty() && name != "." && name != ".." && !name.contains(['/', '\\', '\0'])
+}
+
+impl From<String> for SnippetContent {
+    fn from(text: String) -> Self {
+        SnippetContent::Text(text)
+    }
+}
+
+impl From<&str> for SnippetContent {
+    fn from(text: &str) -> Self {
+        SnippetContent::Text(text.to_string())
+    }
+}
+
+impl From<BinaryContent> for SnippetContent {
+    fn from(binary: BinaryContent) -> Self {
+        SnippetContent::Binary(binary)
+    }
+}
+
+impl From<Vec<SnippetFile>> for SnippetContent {
+    fn from(files: Vec<SnippetFile>) -> Self {
+        SnippetContent::Files(files)
+    }
+}
 
+impl PartialEq<str> for SnippetContent {
+    fn eq(&self, other: &str) -> bool {
+        self.as_text() == Some(other)
+    }
+}
+
+impl PartialEq<&str> for SnippetContent {
+    fn eq(&self, other: &&str) -> bool {
+        self.as_text() == Some(*other)
+    }
 }
 
+/// Shown in place of locked content.
+const LOCKED_PLACEHOLDER: &str = "<locked: encrypted, set NIBB_PASSPHRASE or encryption.key_file to unlock>";
 
 #[cfg(feature = "ansi")]
 use colored::*;
@@ -124,25 +390,39 @@ impl fmt::Display for Meta {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         #[cfg(feature = "ansi")]
         {
+            writeln!(f, "{}: {}", "ID".bold().cyan(), self.id)?;
             writeln!(f, "{}: {}", "Name".bold().cyan(), self.name)?;
+            if !self.namespace.is_empty() {
+                writeln!(f, "{}: {}", "Namespace".bold().cyan(), self.namespace)?;
+            }
             writeln!(f, "{}: {}", "Description".bold().cyan(), self.description)?;
             writeln!(f, "{}: {}", "Tags".bold().cyan(), self.tags.join(", "))?;
             writeln!(f, "{}: {}", "Language".bold().cyan(), self.language)?;
             writeln!(f, "{}: {}", "Created".bold().cyan(), self.created)?;
             writeln!(f, "{}: {}", "Modified".bold().cyan(), self.modified)?;
-            writeln!(f, "{}: {}", "Visibility".bold().cyan(), format!("{:?}", self.visibility))?;
+            writeln!(f, "{}: {:?}", "Visibility".bold().cyan(), self.visibility)?;
+            for link in &self.links {
+                writeln!(f, "{}: {} {}", "Link".bold().cyan(), link.kind, link.target)?;
+            }
             Ok(())
         }
 
         #[cfg(not(feature = "ansi"))]
         {
+            writeln!(f, "ID: {}", self.id)?;
             writeln!(f, "Name: {}", self.name)?;
+            if !self.namespace.is_empty() {
+                writeln!(f, "Namespace: {}", self.namespace)?;
+            }
             writeln!(f, "Description: {}", self.description)?;
             writeln!(f, "Tags: {}", self.tags.join(", "))?;
             writeln!(f, "Language: {}", self.language)?;
             writeln!(f, "Created: {}", self.created)?;
             writeln!(f, "Modified: {}", self.modified)?;
             writeln!(f, "Visibility: {:?}", self.visibility)?;
+            for link in &self.links {
+                writeln!(f, "Link: {} {}", link.kind, link.target)?;
+            }
             Ok(())
         }
     }
@@ -154,13 +434,35 @@ impl fmt::Display for Snippet {
         #[cfg(feature = "ansi")]
         {
             writeln!(f, "{}\n", self.meta)?;
-            writeln!(f, "{}\n{}", "Content:".bold().green(), self.content)
+            match &self.content {
+                SnippetContent::Text(text) => writeln!(f, "{}\n{}", "Content:".bold().green(), text),
+                SnippetContent::Files(files) => {
+                    writeln!(f, "{}", "Files:".bold().green())?;
+                    for file in files {
+                        writeln!(f, "{} ({})\n{}", file.name.bold().cyan(), file.language, file.content)?;
+                    }
+                    Ok(())
+                }
+                SnippetContent::Binary(binary) => writeln!(f, "{}\n{}", "Content:".bold().green(), binary.placeholder().italic()),
+                SnippetContent::Locked => writeln!(f, "{}\n{}", "Content:".bold().green(), LOCKED_PLACEHOLDER.italic().red()),
+            }
         }
 
         #[cfg(not(feature = "ansi"))]
         {
             writeln!(f, "{}\n", self.meta)?;
-            writeln!(f, "Content:\n{}", self.content)
+            match &self.content {
+                SnippetContent::Text(text) => writeln!(f, "Content:\n{}", text),
+                SnippetContent::Files(files) => {
+                    writeln!(f, "Files:")?;
+                    for file in files {
+                        writeln!(f, "{} ({})\n{}", file.name, file.language, file.content)?;
+                    }
+                    Ok(())
+                }
+                SnippetContent::Binary(binary) => writeln!(f, "Content:\n{}", binary.placeholder()),
+                SnippetContent::Locked => writeln!(f, "Content:\n{}", LOCKED_PLACEHOLDER),
+            }
         }
     }
 }
\ No newline at end of file
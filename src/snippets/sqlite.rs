@@ -0,0 +1,702 @@
+use std::path::Path;
+use std::sync::{Mutex, MutexGuard};
+use chrono::Utc;
+use rusqlite::{params, Connection, OptionalExtension, Row, Transaction};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use uuid::Uuid;
+use crate::config::config::{Config, HistoryConfig};
+use crate::migration::META_SCHEMA_VERSION;
+use crate::result::{NibbError, NibbResult};
+use crate::snippets::history::{restore_under, Revision};
+use crate::snippets::links::check_links;
+use crate::snippets::conflict::{check_unmodified, ExpectedVersion};
+use crate::snippets::repo::{check_batch_slugs, check_namespace, claim_slug, SnippetRepository};
+use crate::snippets::report::{LoadDiagnostic, LoadReport, MetaReport};
+use crate::snippets::snippet::{slugify_path, BinaryContent, Meta, Snippet, SnippetContent, SnippetFile};
+use crate::snippets::trash::{trash_cutoff, TrashEntry};
+
+/// Columns shared by the `snippets` and `revisions` tables, in the order read by [`snippet_from_row`].
+const SNIPPET_COLUMNS: &str = "name, description, tags, language, created, modified, visibility, content, uuid, files, data, namespace, links";
+
+const SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS snippets (
+    slug        TEXT PRIMARY KEY,
+    name        TEXT NOT NULL,
+    description TEXT NOT NULL,
+    tags        TEXT NOT NULL,
+    language    TEXT NOT NULL,
+    created     TEXT NOT NULL,
+    modified    TEXT NOT NULL,
+    visibility  TEXT NOT NULL,
+    content     TEXT NOT NULL,
+    uuid        TEXT NOT NULL,
+    files       TEXT,
+    data        BLOB,
+    namespace   TEXT NOT NULL,
+    links       TEXT NOT NULL
+);
+CREATE TABLE IF NOT EXISTS revisions (
+    slug        TEXT NOT NULL,
+    id          TEXT NOT NULL,
+    name        TEXT NOT NULL,
+    description TEXT NOT NULL,
+    tags        TEXT NOT NULL,
+    language    TEXT NOT NULL,
+    created     TEXT NOT NULL,
+    modified    TEXT NOT NULL,
+    visibility  TEXT NOT NULL,
+    content     TEXT NOT NULL,
+    uuid        TEXT NOT NULL,
+    files       TEXT,
+    data        BLOB,
+    namespace   TEXT NOT NULL,
+    links       TEXT NOT NULL,
+    PRIMARY KEY (slug, id)
+);
+CREATE TABLE IF NOT EXISTS trash (
+    slug        TEXT NOT NULL,
+    id          TEXT NOT NULL,
+    name        TEXT NOT NULL,
+    description TEXT NOT NULL,
+    tags        TEXT NOT NULL,
+    language    TEXT NOT NULL,
+    created     TEXT NOT NULL,
+    modified    TEXT NOT NULL,
+    visibility  TEXT NOT NULL,
+    content     TEXT NOT NULL,
+    uuid        TEXT NOT NULL,
+    files       TEXT,
+    data        BLOB,
+    namespace   TEXT NOT NULL,
+    links       TEXT NOT NULL,
+    PRIMARY KEY (slug, id)
+);
+";
+
+/// Version of [`SCHEMA`], stored in `PRAGMA user_version`.
+const SCHEMA_VERSION: i64 = 1;
+
+/// SQLite-backed implementation of [`SnippetRepository`].
+///
+/// All snippets live in a single database file:
+/// - `snippets`: one row per snippet, keyed by slug. [`Meta`] fields are stored as columns,
+///   `tags` as a JSON array and timestamps as RFC 3339 strings. Single-content snippets are stored
+///   in `content`, multi-file snippets in `files` as a JSON array and binary snippets in `data`.
+/// - `revisions`: previous versions of each snippet, mirroring the `history/` directory of [`FSRepo`](crate::FSRepo).
+/// - `trash`: deleted snippets, mirroring the `trash/` directory of [`FSRepo`](crate::FSRepo).
+///
+/// Every write runs in its own transaction.
+pub struct SqliteRepo {
+    conn: Mutex<Connection>,
+    history: HistoryConfig,
+}
+
+impl SqliteRepo {
+    /// Opens the database at `path`, creating it and its tables if missing.
+    ///
+    /// The `history` settings are taken from the `config.toml` next to the database, like [`FSRepo`](crate::FSRepo)
+    /// takes them from its directory. Without one, the defaults apply, and no file is created.
+    pub fn open<P: AsRef<Path>>(path: P) -> NibbResult<Self> {
+        let config_path = path.as_ref().with_file_name("config.toml");
+        let config = Config::read(&config_path)?;
+        Self::with_connection(Connection::open(path)?, config.history)
+    }
+    /// Opens a new database that only lives in memory, with the default `history` settings.
+    pub fn open_in_memory() -> NibbResult<Self> {
+        Self::with_connection(Connection::open_in_memory()?, HistoryConfig::default())
+    }
+    fn with_connection(mut conn: Connection, history: HistoryConfig) -> NibbResult<Self> {
+        create_schema(&mut conn)?;
+        Ok(Self {
+            conn: Mutex::new(conn),
+            history,
+        })
+    }
+    /// Copies every snippet and its revisions from `source` into this database.
+    ///
+    /// Used to migrate an existing `~/.nibb/snippets` tree:
+    /// ```rust,no_run
+    /// use nibb_core::{get_nibb_dir, FSRepo, SqliteRepo};
+    ///
+    /// let fs_repo = FSRepo::new(get_nibb_dir().unwrap()).unwrap();
+    /// let sqlite_repo = SqliteRepo::open("snippets.db").unwrap();
+    /// let report = sqlite_repo.import_from(&fs_repo).unwrap();
+    /// println!("imported {} snippets", report.snippets.len());
+    /// for diagnostic in &report.diagnostics {
+    ///     eprintln!("skipped {}: {}", diagnostic.slug, diagnostic.message);
+    /// }
+    /// ```
+    /// The import runs in a single transaction, so either everything or nothing is copied.
+    /// Each snippet claims its slug like in [`save()`](SnippetRepository::save): a snippet already present
+    /// in the database with the same ID is overwritten, one with another ID fails the import with
+    /// [`NibbError::InvalidSlug`]. Timestamps are kept as they are in `source`.
+    /// Snippets of `source` that cannot be loaded are skipped and reported, see
+    /// [`load_all_lenient()`](SnippetRepository::load_all_lenient), and so are revisions that cannot be loaded.
+    /// Returns the imported snippets along with these diagnostics.
+    pub fn import_from<R: SnippetRepository>(&self, source: &R) -> NibbResult<LoadReport> {
+        let mut report = source.load_all_lenient()?;
+        let mut conn = self.lock()?;
+        let tx = conn.transaction()?;
+        for snippet in &report.snippets {
+            let slug = snippet.meta.get_slug();
+            // a row that cannot be decoded may be overwritten
+            let existing = select_snippet(&tx, &slug).ok().flatten();
+            check_namespace_in(&tx, &slug)?;
+            let mut claimed = claim_slug(snippet, existing.as_ref().map(|s| &s.meta))?;
+            claimed.meta.modified = snippet.meta.modified;
+            let revisions = match source.list_revisions(&slug) {
+                Ok(revisions) => revisions,
+                Err(e) => {
+                    report.diagnostics.push(LoadDiagnostic::new(&slug, &e));
+                    Vec::new()
+                }
+            };
+            for revision in revisions {
+                match source.load_revision(&slug, &revision.id) {
+                    Ok(old) => insert_revision(&tx, &slug, &revision.id, &old)?,
+                    Err(e) => report.diagnostics.push(LoadDiagnostic {
+                        slug: slug.clone(),
+                        message: format!("revision {}: {}", revision.id, e),
+                    }),
+                }
+            }
+            upsert_snippet(&tx, &slug, &claimed)?;
+        }
+        tx.commit()?;
+        Ok(report)
+    }
+    fn lock(&self) -> NibbResult<MutexGuard<'_, Connection>> {
+        self.conn.lock()
+            .map_err(|e| NibbError::Other(format!("SQLite connection poisoned: {}", e)))
+    }
+    /// Writes a snippet within `tx`, recording the version it replaces.
+    fn save_in(&self, tx: &Transaction, snippet: &Snippet) -> NibbResult<()> {
+        let slug = snippet.meta.get_slug();
+        // a row that cannot be decoded may be overwritten
+        let existing = select_snippet(tx, &slug).ok().flatten();
+        check_namespace_in(tx, &slug)?;
+        let snippet = claim_slug(snippet, existing.as_ref().map(|s| &s.meta))?;
+        self.record_revision(tx, &slug)?;
+        upsert_snippet(tx, &slug, &snippet)
+    }
+    /// Copies the current row of `slug` into `revisions`, then prunes old revisions.
+    ///
+    /// Does nothing if history is disabled or the snippet does not exist yet.
+    fn record_revision(&self, tx: &Transaction, slug: &str) -> NibbResult<()> {
+        if !self.history.enabled {
+            return Ok(());
+        }
+        let Some(current) = select_snippet(tx, slug)? else {
+            return Ok(());
+        };
+        let mut recorded = Utc::now();
+        let mut id = Revision::new(slug, recorded).id;
+        // two saves within the clock's resolution must not share a revision
+        while row_exists(tx, "revisions", slug, &id)? {
+            recorded += chrono::Duration::nanoseconds(1);
+            id = Revision::new(slug, recorded).id;
+        }
+        insert_revision(tx, slug, &id, &current)?;
+
+        let max = self.history.max_revisions;
+        if max > 0 {
+            tx.execute(
+                "DELETE FROM revisions WHERE slug = ?1 AND id NOT IN
+                 (SELECT id FROM revisions WHERE slug = ?1 ORDER BY id DESC LIMIT ?2)",
+                params![slug, max as i64],
+            )?;
+        }
+        Ok(())
+    }
+}
+
+impl SnippetRepository for SqliteRepo {
+    /// Loads all snippets, ordered by slug.
+    fn load_all(&self) -> NibbResult<Vec<Snippet>> {
+        let conn = self.lock()?;
+        select_all(&conn)
+    }
+    /// Loads all snippets whose rows can be decoded, ordered by slug.
+    ///
+    /// Rows with unparsable tags, language, visibility or timestamps are reported as diagnostics.
+    fn load_all_lenient(&self) -> NibbResult<LoadReport> {
+        let conn = self.lock()?;
+        let mut stmt = conn.prepare(&format!("SELECT {}, slug FROM snippets ORDER BY slug", SNIPPET_COLUMNS))?;
+        let rows = stmt.query_map([], |row| Ok((row.get::<_, String>(13)?, snippet_from_row(row)?)))?;
+        let mut report = LoadReport::default();
+        for row in rows {
+            match row? {
+                (_, Ok(snippet)) => report.snippets.push(snippet),
+                (slug, Err(e)) => report.diagnostics.push(LoadDiagnostic::new(&slug, &e)),
+            }
+        }
+        Ok(report)
+    }
+    /// Lists the metadata of all snippets whose rows can be decoded, ordered by slug.
+    /// The content columns are not decoded.
+    fn list_meta(&self) -> NibbResult<Vec<(String, Meta)>> {
+        Ok(self.list_meta_lenient()?.metas)
+    }
+    /// Lists the metadata like [`list_meta()`](Self::list_meta), reporting rows that cannot be decoded as diagnostics.
+    fn list_meta_lenient(&self) -> NibbResult<MetaReport> {
+        let conn = self.lock()?;
+        let mut stmt = conn.prepare(&format!("SELECT {}, slug FROM snippets ORDER BY slug", SNIPPET_COLUMNS))?;
+        let rows = stmt.query_map([], |row| Ok((row.get::<_, String>(13)?, meta_from_row(row)?)))?;
+        let mut report = MetaReport::default();
+        for row in rows {
+            match row? {
+                (slug, Ok(meta)) => report.metas.push((slug, meta)),
+                (slug, Err(e)) => report.diagnostics.push(LoadDiagnostic::new(&slug, &e)),
+            }
+        }
+        Ok(report)
+    }
+    /// Loads a specific snippet by slug.
+    fn load(&self, slug: &str) -> NibbResult<Snippet> {
+        let slug = slugify_path(slug); // just to be sure
+        let conn = self.lock()?;
+        select_snippet(&conn, &slug)?
+            .ok_or(NibbError::NotFound(format!("snippet:{}", slug)))
+    }
+    /// Loads a specific snippet by ID.
+    fn load_by_id(&self, id: Uuid) -> NibbResult<Snippet> {
+        let conn = self.lock()?;
+        conn.query_row(
+            &format!("SELECT {} FROM snippets WHERE uuid = ?1", SNIPPET_COLUMNS),
+            params![id.to_string()],
+            snippet_from_row,
+        )
+            .optional()?
+            .ok_or(NibbError::NotFound(format!("snippet:{}", id)))?
+    }
+    /// Inserts or replaces a single snippet.
+    ///
+    /// If the snippet already exists, its previous version is recorded in `revisions` first.
+    fn save(&self, snippet: &Snippet) -> NibbResult<()> {
+        self.save_linking(snippet, None, &[])
+    }
+    /// Inserts or replaces a single snippet like [`save()`](Self::save) if the stored version matches `expected`.
+    ///
+    /// The stored version is compared within the same transaction.
+    fn save_if_unmodified(&self, snippet: &Snippet, expected: &ExpectedVersion) -> NibbResult<()> {
+        self.save_linking(snippet, Some(expected), &[])
+    }
+    /// Inserts or replaces a single snippet like [`save()`](Self::save) or [`save_if_unmodified()`](Self::save_if_unmodified),
+    /// accepting links to `linkable` as well.
+    fn save_linking(&self, snippet: &Snippet, expected: Option<&ExpectedVersion>, linkable: &[Meta]) -> NibbResult<()> {
+        let mut conn = self.lock()?;
+        let tx = conn.transaction()?;
+        if let Some(expected) = expected {
+            check_unmodified(snippet, select_snippet(&tx, &snippet.meta.get_slug())?, expected)?;
+        }
+        check_links_in(&tx, std::slice::from_ref(snippet), linkable)?;
+        self.save_in(&tx, snippet)?;
+        tx.commit()?;
+        Ok(())
+    }
+    /// Saves a list of snippets in a single transaction.
+    ///
+    /// If one of them fails, none of them are written. Links may point to other snippets of the batch.
+    fn save_all(&self, snippets: &[Snippet]) -> NibbResult<()> {
+        self.save_all_linking(snippets, &[])
+    }
+    /// Saves a list of snippets like [`save_all()`](Self::save_all), accepting links to `linkable` as well.
+    fn save_all_linking(&self, snippets: &[Snippet], linkable: &[Meta]) -> NibbResult<()> {
+        check_batch_slugs(snippets)?;
+        let mut conn = self.lock()?;
+        let tx = conn.transaction()?;
+        check_links_in(&tx, snippets, linkable)?;
+        for snippet in snippets {
+            self.save_in(&tx, snippet)?;
+        }
+        tx.commit()?;
+        Ok(())
+    }
+    /// Deletes a snippet by slug, copying its row to `revisions` and moving it to `trash`.
+    fn delete(&self, slug: &str) -> NibbResult<()> {
+        let slug = slugify_path(slug); // just to be sure
+        let mut conn = self.lock()?;
+        let tx = conn.transaction()?;
+        let mut deleted = Utc::now();
+        let mut id = TrashEntry::new(&slug, deleted).id;
+        // two deletions within the clock's resolution must not share an entry
+        while row_exists(&tx, "trash", &slug, &id)? {
+            deleted += chrono::Duration::nanoseconds(1);
+            id = TrashEntry::new(&slug, deleted).id;
+        }
+        self.record_revision(&tx, &slug)?;
+        let moved = tx.execute(
+            &format!("INSERT INTO trash (slug, id, {0}) SELECT slug, ?2, {0} FROM snippets WHERE slug = ?1", SNIPPET_COLUMNS),
+            params![slug, id],
+        )?;
+        if moved == 0 {
+            return Err(NibbError::NotFound(format!("snippet:{}", slug)));
+        }
+        tx.execute("DELETE FROM snippets WHERE slug = ?1", params![slug])?;
+        tx.commit()?;
+        Ok(())
+    }
+    /// Moves the snippet and its revisions from `slug` to the slug of `new_name` in a single transaction,
+    /// and points the links of other snippets to the new slug.
+    ///
+    /// The version before the rename is recorded as a revision.
+    fn rename(&self, slug: &str, new_name: &str) -> NibbResult<Snippet> {
+        let old_slug = slugify_path(slug); // just to be sure
+        let mut conn = self.lock()?;
+        let tx = conn.transaction()?;
+        let mut snippet = select_snippet(&tx, &old_slug)?
+            .ok_or(NibbError::NotFound(format!("snippet:{}", old_slug)))?;
+        let previous = snippet.meta.clone();
+        snippet.meta.rename(new_name);
+        let new_slug = snippet.meta.get_slug();
+        if new_slug.is_empty() {
+            return Err(NibbError::InvalidSlug(format!("'{}' does not produce a slug", new_name)));
+        }
+        let snippet = claim_slug(&snippet, Some(&previous))?;
+        if new_slug != old_slug {
+            if select_snippet(&tx, &new_slug)?.is_some() {
+                return Err(NibbError::InvalidSlug(format!("{} is already taken", new_slug)));
+            }
+            check_namespace_in(&tx, &new_slug)?;
+        }
+        move_revisions(&tx, &old_slug, &new_slug)?;
+        tx.execute("UPDATE snippets SET slug = ?2 WHERE slug = ?1", params![old_slug, new_slug])?;
+        self.record_revision(&tx, &new_slug)?;
+        upsert_snippet(&tx, &new_slug, &snippet)?;
+        if new_slug != old_slug {
+            for mut other in select_all(&tx)? {
+                if other.meta.retarget_links(&old_slug, &new_slug) {
+                    upsert_snippet(&tx, &other.meta.get_slug(), &other)?;
+                }
+            }
+        }
+        tx.commit()?;
+        Ok(snippet)
+    }
+    /// Lists the revisions of a snippet, oldest first.
+    fn list_revisions(&self, slug: &str) -> NibbResult<Vec<Revision>> {
+        let slug = slugify_path(slug); // just to be sure
+        let conn = self.lock()?;
+        let mut stmt = conn.prepare("SELECT id FROM revisions WHERE slug = ?1 ORDER BY id")?;
+        let ids = stmt.query_map(params![slug], |row| row.get::<_, String>(0))?;
+        let mut revisions = Vec::new();
+        for id in ids {
+            if let Some(revision) = Revision::from_id(&slug, &id?) {
+                revisions.push(revision);
+            }
+        }
+        Ok(revisions)
+    }
+    /// Loads a snippet as it was at the given revision.
+    fn load_revision(&self, slug: &str, revision: &str) -> NibbResult<Snippet> {
+        let slug = slugify_path(slug); // just to be sure
+        let conn = self.lock()?;
+        conn.query_row(
+            &format!("SELECT {} FROM revisions WHERE slug = ?1 AND id = ?2", SNIPPET_COLUMNS),
+            params![slug, revision],
+            snippet_from_row,
+        )
+            .optional()?
+            .ok_or(NibbError::NotFound(format!("revision {} of {}", revision, slug)))?
+    }
+    /// Restores a revision by saving it over the current version of the snippet.
+    ///
+    /// The revision keeps the current name and namespace, see [`restore_under()`].
+    fn restore_revision(&self, slug: &str, revision: &str) -> NibbResult<()> {
+        let slug = slugify_path(slug); // just to be sure
+        let snippet = self.load_revision(&slug, revision)?;
+        let current = match self.load(&slug) {
+            Ok(current) => Some(current.meta),
+            Err(NibbError::NotFound(_)) => None,
+            Err(e) => return Err(e),
+        };
+        self.save(&restore_under(&slug, snippet, current.as_ref()))
+    }
+    /// Lists the rows of `trash`, oldest deletion first.
+    fn list_trash(&self) -> NibbResult<Vec<TrashEntry>> {
+        let conn = self.lock()?;
+        let mut stmt = conn.prepare("SELECT slug, id FROM trash ORDER BY id, slug")?;
+        let rows = stmt.query_map([], |row| Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?)))?;
+        let mut entries = Vec::new();
+        for row in rows {
+            let (slug, id) = row?;
+            if let Some(entry) = TrashEntry::from_id(&slug, &id) {
+                entries.push(entry);
+            }
+        }
+        Ok(entries)
+    }
+    /// Moves the most recently deleted snippet with the given slug from `trash` back to `snippets`.
+    fn restore_trash(&self, slug: &str) -> NibbResult<Snippet> {
+        let slug = slugify_path(slug); // just to be sure
+        let mut conn = self.lock()?;
+        let tx = conn.transaction()?;
+        let id: String = tx.query_row(
+            "SELECT id FROM trash WHERE slug = ?1 ORDER BY id DESC LIMIT 1",
+            params![slug],
+            |row| row.get(0),
+        )
+            .optional()?
+            .ok_or(NibbError::NotFound(format!("trash:{}", slug)))?;
+        if snippet_exists(&tx, &slug)? {
+            return Err(NibbError::InvalidSlug(format!("{} is already taken", slug)));
+        }
+        check_namespace_in(&tx, &slug)?;
+        tx.execute(
+            &format!("INSERT INTO snippets (slug, {0}) SELECT slug, {0} FROM trash WHERE slug = ?1 AND id = ?2", SNIPPET_COLUMNS),
+            params![slug, id],
+        )?;
+        tx.execute("DELETE FROM trash WHERE slug = ?1 AND id = ?2", params![slug, id])?;
+        let snippet = select_snippet(&tx, &slug)?
+            .ok_or(NibbError::NotFound(format!("snippet:{}", slug)))?;
+        tx.commit()?;
+        Ok(snippet)
+    }
+    /// Deletes rows of `trash`, optionally only those deleted at least `older_than` ago.
+    fn empty_trash(&self, older_than: Option<chrono::Duration>) -> NibbResult<usize> {
+        let conn = self.lock()?;
+        let removed = match trash_cutoff(older_than) {
+            // identifiers sort in the order the snippets were deleted
+            Some(cutoff) => conn.execute(
+                "DELETE FROM trash WHERE id <= ?1",
+                params![TrashEntry::new("", cutoff).id],
+            )?,
+            None => conn.execute("DELETE FROM trash", [])?,
+        };
+        Ok(removed)
+    }
+}
+
+/// Creates the tables of a new database.
+fn create_schema(conn: &mut Connection) -> NibbResult<()> {
+    let tx = conn.transaction()?;
+    let version: i64 = tx.query_row("PRAGMA user_version", [], |row| row.get(0))?;
+    if version < SCHEMA_VERSION {
+        tx.execute_batch(SCHEMA)?;
+        tx.execute_batch(&format!("PRAGMA user_version = {}", SCHEMA_VERSION))?;
+    }
+    tx.commit()?;
+    Ok(())
+}
+
+fn select_all(conn: &Connection) -> NibbResult<Vec<Snippet>> {
+    let mut stmt = conn.prepare(&format!("SELECT {} FROM snippets ORDER BY slug", SNIPPET_COLUMNS))?;
+    let rows = stmt.query_map([], snippet_from_row)?;
+    let mut snippets = Vec::new();
+    for row in rows {
+        snippets.push(row??);
+    }
+    Ok(snippets)
+}
+
+/// Checks that the links of every snippet in `batch` point to stored snippets, to other snippets in `batch`
+/// or to `linkable`.
+fn check_links_in(conn: &Connection, batch: &[Snippet], linkable: &[Meta]) -> NibbResult<()> {
+    if batch.iter().all(|snippet| snippet.meta.links.is_empty()) {
+        return Ok(());
+    }
+    let stored = select_all(conn)?;
+    for snippet in batch {
+        check_links(&snippet.meta, stored.iter().chain(batch).map(|other| &other.meta).chain(linkable))?;
+    }
+    Ok(())
+}
+
+/// Checks that `slug` can hold a snippet next to the stored ones, see [`check_namespace()`].
+fn check_namespace_in(conn: &Connection, slug: &str) -> NibbResult<()> {
+    let prefix = format!("{}/", slug);
+    check_namespace(
+        slug,
+        |parent| snippet_exists(conn, parent),
+        |_| Ok(conn.query_row(
+            "SELECT 1 FROM snippets WHERE substr(slug, 1, ?2) = ?1 LIMIT 1",
+            params![prefix, prefix.chars().count() as i64],
+            |_| Ok(()),
+        ).optional()?.is_some()),
+    )
+}
+
+/// Checks whether `snippets` has a row for `slug`.
+fn snippet_exists(conn: &Connection, slug: &str) -> NibbResult<bool> {
+    Ok(conn.query_row("SELECT 1 FROM snippets WHERE slug = ?1", params![slug], |_| Ok(()))
+        .optional()?
+        .is_some())
+}
+
+fn select_snippet(conn: &Connection, slug: &str) -> NibbResult<Option<Snippet>> {
+    conn.query_row(
+        &format!("SELECT {} FROM snippets WHERE slug = ?1", SNIPPET_COLUMNS),
+        params![slug],
+        snippet_from_row,
+    )
+        .optional()?
+        .transpose()
+}
+
+/// Moves the revisions of `old_slug` to `new_slug`.
+///
+/// Revisions left behind under the new slug, e.g. by a deleted snippet, are kept.
+/// A moved revision whose identifier is taken there gets the next free one.
+fn move_revisions(conn: &Connection, old_slug: &str, new_slug: &str) -> NibbResult<()> {
+    let ids = {
+        let mut stmt = conn.prepare("SELECT id FROM revisions WHERE slug = ?1 ORDER BY id")?;
+        let ids = stmt.query_map(params![old_slug], |row| row.get::<_, String>(0))?;
+        ids.collect::<Result<Vec<_>, _>>()?
+    };
+    for id in ids {
+        let recorded = Revision::from_id(old_slug, &id)
+            .ok_or(NibbError::Other(format!("invalid revision {} of {}", id, old_slug)))?
+            .recorded;
+        let moved = Revision::first_free(new_slug, recorded, |id| row_exists(conn, "revisions", new_slug, id))?;
+        conn.execute(
+            "UPDATE revisions SET slug = ?3, id = ?4 WHERE slug = ?1 AND id = ?2",
+            params![old_slug, id, new_slug, moved.id],
+        )?;
+    }
+    Ok(())
+}
+
+/// Checks whether `table`, `revisions` or `trash`, has a row for `slug` and `id`.
+fn row_exists(conn: &Connection, table: &str, slug: &str, id: &str) -> NibbResult<bool> {
+    Ok(conn.query_row(
+        &format!("SELECT 1 FROM {} WHERE slug = ?1 AND id = ?2", table),
+        params![slug, id],
+        |_| Ok(()),
+    ).optional()?.is_some())
+}
+
+fn upsert_snippet(conn: &Connection, slug: &str, snippet: &Snippet) -> NibbResult<()> {
+    let meta = &snippet.meta;
+    let (content, files, data) = content_columns(&snippet.content)?;
+    conn.execute(
+        &format!("INSERT OR REPLACE INTO snippets (slug, {}) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14)", SNIPPET_COLUMNS),
+        params![
+            slug,
+            meta.name,
+            meta.description,
+            serde_json::to_string(&meta.tags)?,
+            to_column(&meta.language)?,
+            meta.created.to_rfc3339(),
+            meta.modified.to_rfc3339(),
+            to_column(&meta.visibility)?,
+            content,
+            meta.id.to_string(),
+            files,
+            data,
+            meta.namespace,
+            serde_json::to_string(&meta.links)?,
+        ],
+    )?;
+    Ok(())
+}
+
+fn insert_revision(conn: &Connection, slug: &str, id: &str, snippet: &Snippet) -> NibbResult<()> {
+    let meta = &snippet.meta;
+    let (content, files, data) = content_columns(&snippet.content)?;
+    conn.execute(
+        &format!("INSERT OR REPLACE INTO revisions (slug, id, {}) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15)", SNIPPET_COLUMNS),
+        params![
+            slug,
+            id,
+            meta.name,
+            meta.description,
+            serde_json::to_string(&meta.tags)?,
+            to_column(&meta.language)?,
+            meta.created.to_rfc3339(),
+            meta.modified.to_rfc3339(),
+            to_column(&meta.visibility)?,
+            content,
+            meta.id.to_string(),
+            files,
+            data,
+            meta.namespace,
+            serde_json::to_string(&meta.links)?,
+        ],
+    )?;
+    Ok(())
+}
+
+/// Reads a row selected with [`SNIPPET_COLUMNS`].
+///
+/// Column decoding errors are returned as the inner result,
+/// so they can be reported as [`NibbError`]s instead of SQLite errors.
+fn snippet_from_row(row: &Row) -> rusqlite::Result<NibbResult<Snippet>> {
+    let meta = meta_from_row(row)?;
+    let content: String = row.get(7)?;
+    let files: Option<String> = row.get(9)?;
+    let data: Option<Vec<u8>> = row.get(10)?;
+    Ok((|| {
+        let meta = meta?;
+        match (files, data) {
+            (Some(files), _) => Ok(Snippet::new(meta, serde_json::from_str::<Vec<SnippetFile>>(&files)?)),
+            (None, Some(data)) => Ok(Snippet::new(meta, BinaryContent::new(data))),
+            (None, None) => Ok(Snippet::new(meta, content)),
+        }
+    })())
+}
+
+/// Reads the metadata columns of a row selected with [`SNIPPET_COLUMNS`], like [`snippet_from_row()`].
+fn meta_from_row(row: &Row) -> rusqlite::Result<NibbResult<Meta>> {
+    let name: String = row.get(0)?;
+    let description: String = row.get(1)?;
+    let tags: String = row.get(2)?;
+    let language: String = row.get(3)?;
+    let created: String = row.get(4)?;
+    let modified: String = row.get(5)?;
+    let visibility: String = row.get(6)?;
+    let id: String = row.get(8)?;
+    let namespace: String = row.get(11)?;
+    let links: String = row.get(12)?;
+    Ok((|| {
+        let mut meta = Meta {
+            schema_version: META_SCHEMA_VERSION,
+            id: match id.as_str() {
+                "" => Uuid::nil(),
+                id => Uuid::parse_str(id).map_err(|e| NibbError::Other(format!("Invalid id {}: {}", id, e)))?,
+            },
+            name,
+            namespace,
+            description,
+            tags: serde_json::from_str(&tags)?,
+            language: from_column(language)?,
+            created: parse_timestamp(&created)?,
+            modified: parse_timestamp(&modified)?,
+            visibility: from_column(visibility)?,
+            links: serde_json::from_str(&links)?,
+        };
+        meta.ensure_id();
+        Ok(meta)
+    })())
+}
+
+/// Values of the `content`, `files` and `data` columns.
+type ContentColumns<'a> = (&'a str, Option<String>, Option<&'a [u8]>);
+
+/// Splits content into the `content`, `files` and `data` columns.
+fn content_columns(content: &SnippetContent) -> NibbResult<ContentColumns<'_>> {
+    match content {
+        SnippetContent::Text(text) => Ok((text, None, None)),
+        SnippetContent::Files(files) => Ok(("", Some(serde_json::to_string(files)?), None)),
+        SnippetContent::Binary(binary) => Ok(("", None, Some(&binary.data))),
+        SnippetContent::Locked => Err(NibbError::Locked("locked content cannot be stored".to_string())),
+    }
+}
+
+/// Serializes a unit enum like [`FileType`](crate::FileType) to the string used in `meta.toml`.
+fn to_column<T: Serialize>(value: &T) -> NibbResult<String> {
+    match serde_json::to_value(value)? {
+        serde_json::Value::String(s) => Ok(s),
+        other => Err(NibbError::Other(format!("Expected a string column, got {}", other))),
+    }
+}
+
+fn from_column<T: DeserializeOwned>(value: String) -> NibbResult<T> {
+    Ok(serde_json::from_value(serde_json::Value::String(value))?)
+}
+
+fn parse_timestamp(value: &str) -> NibbResult<chrono::DateTime<Utc>> {
+    chrono::DateTime::parse_from_rfc3339(value)
+        .map(|t| t.with_timezone(&Utc))
+        .map_err(|e| NibbError::Other(format!("Invalid timestamp {}: {}", value, e)))
+}
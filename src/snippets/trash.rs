@@ -0,0 +1,43 @@
+use chrono::{DateTime, Duration, NaiveDateTime, Utc};
+use serde::{Deserialize, Serialize};
+use crate::snippets::history::REVISION_ID_FORMAT;
+
+/// A deleted snippet, kept in the `trash/` directory of a repository until it is restored
+/// or the trash is emptied.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TrashEntry {
+    /// Identifier of the entry, formatted like a [`Revision`](crate::Revision) identifier.
+    pub id: String,
+    /// Slug the snippet had when it was deleted.
+    pub slug: String,
+    /// Timestamp indicating when the snippet was deleted (UTC).
+    pub deleted: DateTime<Utc>,
+}
+
+impl TrashEntry {
+    /// Creates a new entry for `slug`, deleted at `deleted`.
+    pub fn new(slug: &str, deleted: DateTime<Utc>) -> Self {
+        Self {
+            id: deleted.format(REVISION_ID_FORMAT).to_string(),
+            slug: slug.to_string(),
+            deleted,
+        }
+    }
+    /// Reconstructs an entry from its identifier.
+    ///
+    /// Returns `None` if `id` is not a valid identifier.
+    pub fn from_id(slug: &str, id: &str) -> Option<Self> {
+        let deleted = NaiveDateTime::parse_from_str(id, REVISION_ID_FORMAT).ok()?.and_utc();
+        Some(Self {
+            id: id.to_string(),
+            slug: slug.to_string(),
+            deleted,
+        })
+    }
+}
+
+/// Returns the latest deletion time removed by `empty_trash(older_than)`,
+/// or `None` if everything is removed.
+pub(crate) fn trash_cutoff(older_than: Option<Duration>) -> Option<DateTime<Utc>> {
+    older_than.map(|age| Utc::now().checked_sub_signed(age).unwrap_or(DateTime::<Utc>::MIN_UTC))
+}
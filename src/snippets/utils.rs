@@ -1,32 +1,36 @@
 use chrono::NaiveDate;
-use crate::snippets::snippet::Snippet;
+use crate::snippets::snippet::{Meta, Snippet};
 
+/// Filters snippets with [`matches_filter()`].
 pub fn filter_snippets(snippets: Vec<Snippet>, filter: &str) -> Vec<Snippet> {
+    snippets.into_iter()
+        .filter(|snippet| matches_filter(&snippet.meta, filter))
+        .collect()
+}
+
+/// Returns whether a snippet with this metadata passes `filter`.
+///
+/// An empty filter, `"all"` and `"a"` match everything. Otherwise the filter has to be contained in
+/// the language, name or description, or be one of the tags. If the filter is a date (`%Y-%m-%d`),
+/// snippets created on or before that date match as well.
+pub fn matches_filter(meta: &Meta, filter: &str) -> bool {
     if filter.trim().is_empty() || filter == "all" || filter == "a"{
-        return snippets;
+        return true;
     }
 
-    let mut filtered = Vec::new();
-
     let parsed_date = NaiveDate::parse_from_str(filter, "%Y-%m-%d").ok();
 
-    for snippet in snippets {
-        let meta = &snippet.meta;
-        let matches_text =
-            meta.language.to_string().contains(filter)
-            || meta.name.contains(filter)
-            || meta.description.contains(filter)
-            || meta.tags.contains(&filter.to_string());
+    let matches_text =
+        meta.language.to_string().contains(filter)
+        || meta.name.contains(filter)
+        || meta.description.contains(filter)
+        || meta.tags.contains(&filter.to_string());
 
-        let matches_date = if let Some(date) = parsed_date {
-            meta.created.date_naive() <= date
-        }
-        else {
-            false
-        };
-        if matches_text || matches_date {
-            filtered.push(snippet);
-        }
+    let matches_date = if let Some(date) = parsed_date {
+        meta.created.date_naive() <= date
     }
-    filtered
-}
\ No newline at end of file
+    else {
+        false
+    };
+    matches_text || matches_date
+}
@@ -0,0 +1,256 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, Sender};
+use std::thread;
+use std::time::Duration;
+use notify::{EventKind, RecommendedWatcher, RecursiveMode, Watcher};
+use serde::Serialize;
+use uuid::Uuid;
+use walkdir::WalkDir;
+use crate::result::NibbResult;
+use crate::snippets::index::FileStamp;
+use crate::snippets::repo::is_namespace_dir;
+
+/// A change to the snippets of a repository, as reported by a [`SnippetWatcher`].
+///
+/// Serialized with an `event` tag, e.g. `{"event": "renamed", "from": "foo", "to": "bar"}`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "event", rename_all = "lowercase")]
+pub enum SnippetEvent {
+    /// A snippet appeared under `slug`.
+    Created { slug: String },
+    /// The snippet under `slug` was changed.
+    Modified { slug: String },
+    /// The snippet under `slug` disappeared, e.g. because it was moved to the trash.
+    Deleted { slug: String },
+    /// A snippet moved from one slug to another, keeping its ID.
+    Renamed { from: String, to: String },
+}
+
+impl SnippetEvent {
+    pub fn to_json(&self) -> String {
+        serde_json::to_string(self)
+            .unwrap_or_else(|_| "{\"type\":\"Other\",\"message\":\"Serialization failed\"}".to_string())
+    }
+}
+
+/// Watches the `snippets/` directory of an [`FSRepo`](crate::FSRepo), see [`FSRepo::watch()`](crate::FSRepo::watch).
+///
+/// Raw filesystem notifications are collected until none arrived for the debounce interval,
+/// then turned into [`SnippetEvent`]s by comparing the affected snippet directories with their
+/// previous state. A snippet whose files kept their stamps is not reported as modified.
+/// Iterating blocks until the next event. Watching stops when the watcher is dropped.
+pub struct SnippetWatcher {
+    // dropping it ends the notifications, which stops the debouncing thread
+    _watcher: RecommendedWatcher,
+    events: Receiver<SnippetEvent>,
+}
+
+impl SnippetWatcher {
+    pub(crate) fn new(snippets_dir: &Path, debounce: Duration) -> NibbResult<Self> {
+        // notifications carry canonical paths on some platforms
+        let snippets_dir = snippets_dir.canonicalize()?;
+        let (raw_tx, raw_rx) = mpsc::channel();
+        let mut watcher = notify::recommended_watcher(move |event| {
+            let _ = raw_tx.send(event);
+        })?;
+        watcher.watch(&snippets_dir, RecursiveMode::Recursive)?;
+
+        let (tx, events) = mpsc::channel();
+        let state = SnippetState::scan(&snippets_dir);
+        thread::spawn(move || debounce_events(snippets_dir, state, raw_rx, tx, debounce));
+        Ok(Self { _watcher: watcher, events })
+    }
+    /// Waits up to `timeout` for the next event.
+    ///
+    /// Returns `None` if no event arrived in time.
+    pub fn recv_timeout(&self, timeout: Duration) -> Option<SnippetEvent> {
+        self.events.recv_timeout(timeout).ok()
+    }
+    /// Returns the next event if one is pending, without blocking.
+    pub fn try_recv(&self) -> Option<SnippetEvent> {
+        self.events.try_recv().ok()
+    }
+}
+
+impl Iterator for SnippetWatcher {
+    type Item = SnippetEvent;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.events.recv().ok()
+    }
+}
+
+/// Collects raw notifications into batches and sends the resulting events, until either side hangs up.
+fn debounce_events(
+    snippets_dir: PathBuf,
+    mut state: SnippetState,
+    raw: Receiver<notify::Result<notify::Event>>,
+    events: Sender<SnippetEvent>,
+    debounce: Duration,
+) {
+    while let Ok(first) = raw.recv() {
+        let mut dirty = Dirty::default();
+        dirty.add(&snippets_dir, first);
+        loop {
+            match raw.recv_timeout(debounce) {
+                Ok(event) => dirty.add(&snippets_dir, event),
+                Err(RecvTimeoutError::Timeout) => break,
+                Err(RecvTimeoutError::Disconnected) => return,
+            }
+        }
+        for event in state.update(&snippets_dir, dirty) {
+            if events.send(event).is_err() {
+                return;
+            }
+        }
+    }
+}
+
+/// Slugs touched by a batch of notifications.
+#[derive(Default)]
+struct Dirty {
+    slugs: BTreeSet<String>,
+    /// Set if a notification could not be attributed to a snippet, so all of them are compared.
+    rescan: bool,
+}
+
+impl Dirty {
+    fn add(&mut self, snippets_dir: &Path, event: notify::Result<notify::Event>) {
+        let event = match event {
+            Ok(event) => event,
+            Err(_) => {
+                self.rescan = true;
+                return;
+            }
+        };
+        // reading a snippet, e.g. by the watcher itself, changes nothing
+        if matches!(event.kind, EventKind::Access(_)) {
+            return;
+        }
+        if event.need_rescan() {
+            self.rescan = true;
+        }
+        for path in &event.paths {
+            let Some(rel) = path.strip_prefix(snippets_dir).ok().filter(|rel| rel.components().next().is_some()) else {
+                self.rescan = true;
+                continue;
+            };
+            // the snippet may be nested in namespaces, so every directory on the way is a candidate
+            let mut slug = String::new();
+            for component in rel.components() {
+                if !slug.is_empty() {
+                    slug.push('/');
+                }
+                slug.push_str(&component.as_os_str().to_string_lossy());
+                self.slugs.insert(slug.clone());
+            }
+        }
+    }
+}
+
+/// Last known state of a snippet directory.
+#[derive(PartialEq)]
+struct KnownSnippet {
+    /// ID of the snippet, if it can be read.
+    id: Option<Uuid>,
+    /// Stamps of the files in the directory, by their path inside it.
+    files: BTreeMap<PathBuf, FileStamp>,
+}
+
+/// Last known snippets of a `snippets/` directory.
+struct SnippetState(BTreeMap<String, KnownSnippet>);
+
+impl SnippetState {
+    fn scan(snippets_dir: &Path) -> Self {
+        Self(list_slugs(snippets_dir).into_iter()
+            .filter_map(|slug| read_snippet(snippets_dir, &slug).map(|known| (slug, known)))
+            .collect())
+    }
+    /// Compares the slugs in `dirty` with their last known state and returns what changed.
+    ///
+    /// A snippet that disappeared under one slug and appeared under another with the same ID was renamed.
+    fn update(&mut self, snippets_dir: &Path, mut dirty: Dirty) -> Vec<SnippetEvent> {
+        if dirty.rescan {
+            dirty.slugs.extend(self.0.keys().cloned());
+            dirty.slugs.extend(list_slugs(snippets_dir));
+        }
+        let mut modified = Vec::new();
+        let mut deleted = Vec::new();
+        let mut created = Vec::new();
+        for slug in dirty.slugs {
+            match (self.0.remove(&slug), read_snippet(snippets_dir, &slug)) {
+                (Some(previous), Some(known)) => {
+                    if previous != known {
+                        modified.push(SnippetEvent::Modified { slug: slug.clone() });
+                    }
+                    self.0.insert(slug, known);
+                }
+                (Some(previous), None) => deleted.push((slug, previous.id)),
+                (None, Some(known)) => {
+                    created.push((slug.clone(), known.id));
+                    self.0.insert(slug, known);
+                }
+                (None, None) => {}
+            }
+        }
+
+        let mut events = Vec::new();
+        for (from, id) in deleted {
+            let renamed_to = id.and_then(|id| created.iter().position(|(_, other)| *other == Some(id)));
+            match renamed_to {
+                Some(i) => events.push(SnippetEvent::Renamed { from, to: created.remove(i).0 }),
+                None => events.push(SnippetEvent::Deleted { slug: from }),
+            }
+        }
+        events.extend(created.into_iter().map(|(slug, _)| SnippetEvent::Created { slug }));
+        events.extend(modified);
+        events
+    }
+}
+
+/// Returns the slugs of the snippet directories in `snippets_dir`, descending into namespace directories.
+fn list_slugs(snippets_dir: &Path) -> Vec<String> {
+    let mut slugs = Vec::new();
+    let mut dirs = vec![(String::new(), snippets_dir.to_path_buf())];
+    while let Some((prefix, dir)) = dirs.pop() {
+        for entry in fs::read_dir(&dir).into_iter().flatten().flatten() {
+            let path = entry.path();
+            let slug = format!("{}{}", prefix, entry.file_name().to_string_lossy());
+            if is_namespace_dir(&path) {
+                dirs.push((format!("{}/", slug), path));
+            } else if path.is_dir() {
+                slugs.push(slug);
+            }
+        }
+    }
+    slugs
+}
+
+/// Returns `None` if there is no snippet directory `slug`.
+///
+/// A directory counts as a snippet directory once it has a `meta.toml`, so namespace directories
+/// and the directories inside a snippet are not reported. The `files/` directory of a multi-file
+/// snippet is skipped as well, as one of its files may be named `meta.toml`.
+fn read_snippet(snippets_dir: &Path, slug: &str) -> Option<KnownSnippet> {
+    let dir = snippets_dir.join(slug);
+    if dir.file_name().is_some_and(|name| name == "files") && dir.with_file_name("files.toml").exists() {
+        return None;
+    }
+    let meta_path = dir.join("meta.toml");
+    if !meta_path.is_file() {
+        return None;
+    }
+    let id = fs::read_to_string(meta_path).ok()
+        .and_then(|meta| toml::from_str::<toml::Table>(&meta).ok())
+        .and_then(|meta| meta.get("id")?.as_str().and_then(|id| Uuid::parse_str(id).ok()));
+    let files = WalkDir::new(&dir).min_depth(1).into_iter().flatten()
+        .filter(|entry| entry.file_type().is_file())
+        .filter_map(|entry| {
+            let stamp = FileStamp::of(entry.path()).ok()?;
+            Some((entry.path().strip_prefix(&dir).ok()?.to_path_buf(), stamp))
+        })
+        .collect();
+    Some(KnownSnippet { id, files })
+}
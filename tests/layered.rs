@@ -0,0 +1,187 @@
+use nibb_core::{ExpectedVersion, FSRepo, FileType, LayeredRepo, LinkKind, MemoryRepo, Meta, NibbError, Snippet, SnippetLink, SnippetRepository};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use tempfile::TempDir;
+
+fn get_test_snippet(name: &str) -> Snippet {
+    let meta = Meta::new(
+        name.to_string(),
+        "test-desc".to_string(),
+        vec!["test-tag".to_string()],
+        FileType::Rust,
+        None,
+    );
+    Snippet::new(meta, "main() {\n println!(\"Hello world\"); \n}".to_string())
+}
+
+/// A personal repository on disk over a team library with `Shared` and `Team Only`.
+fn get_test_repo() -> (TempDir, LayeredRepo) {
+    let temp_dir = TempDir::new().unwrap();
+    let personal = FSRepo::new(temp_dir.path().join(".nibb")).unwrap();
+    personal.save(&get_test_snippet("Personal")).unwrap();
+    let team = MemoryRepo::new();
+    team.save_all(&[get_test_snippet("Shared"), get_test_snippet("Team Only")]).unwrap();
+    (temp_dir, LayeredRepo::new("personal", personal).with_layer("team", team))
+}
+
+#[test]
+fn test_load_resolves_from_the_top() {
+    let (_temp_dir, repo) = get_test_repo();
+    assert_eq!(repo.layer_names(), ["personal", "team"]);
+    assert_eq!(repo.load("personal").unwrap().layer.as_deref(), Some("personal"));
+    assert_eq!(repo.load("Team Only").unwrap().layer.as_deref(), Some("team"));
+    assert!(matches!(repo.load("missing"), Err(NibbError::NotFound(_))));
+
+    let layers: Vec<_> = repo.load_all().unwrap().into_iter()
+        .map(|snippet| (snippet.meta.get_slug(), snippet.layer.unwrap()))
+        .collect();
+    assert_eq!(layers, [
+        ("personal".to_string(), "personal".to_string()),
+        ("shared".to_string(), "team".to_string()),
+        ("team-only".to_string(), "team".to_string()),
+    ]);
+    let slugs: Vec<_> = repo.list_meta().unwrap().into_iter().map(|(slug, _)| slug).collect();
+    assert_eq!(slugs, ["personal", "shared", "team-only"]);
+    assert_eq!(repo.iter().unwrap().nth(1).unwrap().unwrap().layer.as_deref(), Some("team"));
+    // the layer is not stored, but shows up in the JSON representation
+    assert!(repo.load("shared").unwrap().to_json().contains("\"layer\":\"team\""));
+    assert!(!get_test_snippet("Shared").to_json().contains("\"layer\""));
+}
+
+#[test]
+fn test_save_copies_up() {
+    let (temp_dir, repo) = get_test_repo();
+    let mut shared = repo.load("shared").unwrap();
+    let id = shared.meta.id;
+    shared.content = "personal version".into();
+    repo.save(&shared).unwrap();
+
+    let loaded = repo.load("shared").unwrap();
+    assert_eq!(loaded.layer.as_deref(), Some("personal"));
+    assert_eq!(loaded.meta.id, id);
+    assert_eq!(loaded.content, "personal version");
+    assert!(temp_dir.path().join(".nibb/snippets/shared/meta.toml").is_file());
+    assert_eq!(repo.load_all().unwrap().len(), 3);
+
+    // deleting the override reveals the team version again
+    repo.delete("shared").unwrap();
+    assert_eq!(repo.load("shared").unwrap().layer.as_deref(), Some("team"));
+    assert_ne!(repo.load("shared").unwrap().content, "personal version");
+}
+
+#[test]
+fn test_override_keeps_links_to_lower_layers() {
+    let temp_dir = TempDir::new().unwrap();
+    let personal = FSRepo::new(temp_dir.path().join(".nibb")).unwrap();
+    let team = MemoryRepo::new();
+    let mut main = get_test_snippet("Main");
+    main.meta.links.push(SnippetLink::new(LinkKind::DependsOn, "helper"));
+    team.save_all(&[get_test_snippet("Helper"), main]).unwrap();
+    let repo = LayeredRepo::new("personal", personal).with_layer("team", team);
+
+    // the override links to a snippet that only the team layer has
+    let mut main = repo.load("main").unwrap();
+    main.content = "personal version".into();
+    repo.save(&main).unwrap();
+    assert_eq!(repo.load("main").unwrap().layer.as_deref(), Some("personal"));
+    let expected = ExpectedVersion::Hash(repo.load("main").unwrap().version_hash());
+    repo.save_if_unmodified(&main, &expected).unwrap();
+    let mut new = get_test_snippet("New");
+    new.meta.links.push(SnippetLink::new(LinkKind::SeeAlso, "helper"));
+    repo.save_all(&[new]).unwrap();
+
+    let mut broken = get_test_snippet("Broken");
+    broken.meta.links.push(SnippetLink::new(LinkKind::SeeAlso, "missing"));
+    assert!(matches!(repo.save(&broken), Err(NibbError::InvalidLink(_))));
+}
+
+#[test]
+fn test_lower_layers_are_read_only() {
+    let (_temp_dir, repo) = get_test_repo();
+    assert!(matches!(repo.delete("team-only"), Err(NibbError::ReadOnly(_))));
+    assert!(matches!(repo.rename("team-only", "Renamed"), Err(NibbError::ReadOnly(_))));
+    assert!(matches!(repo.rename("personal", "Shared"), Err(NibbError::InvalidSlug(_))));
+    assert_eq!(repo.load("team-only").unwrap().layer.as_deref(), Some("team"));
+
+    let renamed = repo.rename("personal", "Mine").unwrap();
+    assert_eq!(renamed.layer.as_deref(), Some("personal"));
+    assert!(repo.load("mine").is_ok());
+}
+
+#[test]
+fn test_save_if_unmodified_compares_the_visible_version() {
+    let (_temp_dir, repo) = get_test_repo();
+    let shared = repo.load("shared").unwrap();
+    let expected = ExpectedVersion::Hash(shared.version_hash());
+    repo.save_if_unmodified(&shared, &expected).unwrap();
+    assert_eq!(repo.load("shared").unwrap().layer.as_deref(), Some("personal"));
+    // the personal copy now shadows the team version that was expected
+    match repo.save_if_unmodified(&shared, &expected) {
+        Err(NibbError::Conflict(conflict)) => assert_eq!(conflict.current.unwrap().layer.as_deref(), Some("personal")),
+        other => panic!("expected a conflict, got {:?}", other),
+    }
+}
+
+/// Returns every path below `dir` with the contents of the files, to tell whether anything was written.
+fn snapshot(dir: &Path) -> BTreeMap<PathBuf, Vec<u8>> {
+    let mut entries = BTreeMap::new();
+    for entry in std::fs::read_dir(dir).unwrap() {
+        let path = entry.unwrap().path();
+        if path.is_dir() {
+            entries.extend(snapshot(&path));
+            entries.insert(path, Vec::new());
+        } else {
+            entries.insert(path.clone(), std::fs::read(&path).unwrap());
+        }
+    }
+    entries
+}
+
+fn set_read_only(dir: &Path, read_only: bool) {
+    for path in snapshot(dir).into_keys().chain([dir.to_path_buf()]) {
+        let mut permissions = std::fs::metadata(&path).unwrap().permissions();
+        permissions.set_readonly(read_only);
+        std::fs::set_permissions(&path, permissions).unwrap();
+    }
+}
+
+#[test]
+fn test_lower_layer_is_opened_read_only() {
+    let temp_dir = TempDir::new().unwrap();
+    let team_dir = temp_dir.path().join("team");
+    let team = FSRepo::new(&team_dir).unwrap();
+    team.save_all(&[get_test_snippet("Shared"), get_test_snippet("Team Only")]).unwrap();
+    drop(team);
+    // a fresh clone: no lock, index or empty directories, and a snippet of an older schema
+    std::fs::remove_file(team_dir.join("nibb.lock")).unwrap();
+    assert!(!team_dir.join("index.json").exists());
+    for dir in ["history", "trash", "tmp"] {
+        std::fs::remove_dir_all(team_dir.join(dir)).unwrap();
+    }
+    let meta_path = team_dir.join("snippets/shared/meta.toml");
+    let meta = std::fs::read_to_string(&meta_path).unwrap();
+    let old_schema: String = meta.lines().filter(|line| !line.starts_with("schema_version")).map(|line| format!("{}\n", line)).collect();
+    std::fs::write(&meta_path, old_schema).unwrap();
+    let before = snapshot(&team_dir);
+    set_read_only(&team_dir, true);
+
+    let personal = FSRepo::new(temp_dir.path().join(".nibb")).unwrap();
+    let repo = LayeredRepo::new("personal", personal)
+        .with_layer("team", FSRepo::open_read_only(&team_dir).unwrap());
+    assert_eq!(repo.load("team-only").unwrap().layer.as_deref(), Some("team"));
+    assert_eq!(repo.list_meta().unwrap().len(), 2);
+    assert_eq!(repo.load_all().unwrap().len(), 2);
+    assert!(repo.list_trash().unwrap().is_empty());
+    let mut shared = repo.load("shared").unwrap();
+    shared.content = "personal version".into();
+    repo.save(&shared).unwrap();
+    assert_eq!(repo.load("shared").unwrap().layer.as_deref(), Some("personal"));
+
+    let team = FSRepo::open_read_only(&team_dir).unwrap();
+    assert!(matches!(team.save(&get_test_snippet("New")), Err(NibbError::ReadOnly(_))));
+    assert!(matches!(team.delete("shared"), Err(NibbError::ReadOnly(_))));
+    assert!(matches!(FSRepo::open_read_only(temp_dir.path().join("missing")), Err(NibbError::NotFound(_))));
+
+    set_read_only(&team_dir, false);
+    assert_eq!(snapshot(&team_dir), before);
+}
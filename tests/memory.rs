@@ -0,0 +1,258 @@
+use nibb_core::{ExpectedVersion, FSRepo, FileType, LinkKind, MemoryRepo, Meta, NibbError, Snippet, SnippetFile, SnippetLink, SnippetRepository};
+use tempfile::TempDir;
+
+fn get_test_snippet(name: &str) -> Snippet {
+    let meta = Meta::new(
+        name.to_string(),
+        "test-desc".to_string(),
+        vec!["test-tag".to_string()],
+        FileType::Rust,
+        None,
+    );
+    Snippet::new(meta, "main() {\n println!(\"Hello world\"); \n}".to_string())
+}
+
+/// Runs `check` against an [`FSRepo`] in a fresh directory and against a [`MemoryRepo`], which should behave alike.
+fn on_both(check: impl Fn(&dyn SnippetRepository)) {
+    let temp_dir = TempDir::new().unwrap();
+    check(&FSRepo::new(temp_dir.path().join(".nibb")).unwrap());
+    check(&MemoryRepo::new());
+}
+
+#[test]
+fn test_lookups_are_slugified() {
+    on_both(|repo| {
+        repo.save(&get_test_snippet("Foo Bar")).unwrap();
+        assert_eq!(repo.load("Foo Bar").unwrap().meta.name, "Foo Bar");
+        assert_eq!(repo.load("foo-bar").unwrap().meta.name, "Foo Bar");
+        assert_eq!(repo.list_meta().unwrap()[0].0, "foo-bar");
+    });
+}
+
+#[test]
+fn test_save_overwrites() {
+    on_both(|repo| {
+        let mut snippet = get_test_snippet("Foo Bar");
+        repo.save(&snippet).unwrap();
+        snippet.content = "second version".into();
+        repo.save(&snippet).unwrap();
+        assert_eq!(repo.load_all().unwrap().len(), 1);
+        assert_eq!(repo.iter().unwrap().next().unwrap().unwrap().content, "second version");
+        assert_eq!(repo.load("foo-bar").unwrap().content, "second version");
+        assert_eq!(repo.list_revisions("foo-bar").unwrap().len(), 1);
+    });
+}
+
+#[test]
+fn test_save_refuses_taken_or_empty_slugs() {
+    on_both(|repo| {
+        repo.save(&get_test_snippet("Foo Bar")).unwrap();
+        assert!(matches!(repo.save(&get_test_snippet("foo bar!")), Err(NibbError::InvalidSlug(_))));
+        assert!(matches!(repo.save(&get_test_snippet("!!!")), Err(NibbError::InvalidSlug(_))));
+    });
+}
+
+#[test]
+fn test_rename() {
+    on_both(|repo| {
+        let mut snippet = get_test_snippet("Foo Bar");
+        repo.save(&snippet).unwrap();
+        snippet.content = "second version".into();
+        repo.save(&snippet).unwrap();
+
+        // renames move the snippet and its history, and refuse taken slugs
+        repo.save(&get_test_snippet("Taken")).unwrap();
+        assert!(repo.rename("foo-bar", "taken").is_err());
+        repo.rename("foo-bar", "Baz").unwrap();
+        // the ID survives the rename
+        assert_eq!(repo.load_by_id(snippet.meta.id).unwrap().meta.name, "Baz");
+        assert_eq!(repo.load_by_key(&snippet.meta.id.to_string()).unwrap().meta.name, "Baz");
+        assert_eq!(repo.load_by_key("baz").unwrap().meta.id, snippet.meta.id);
+        assert!(repo.load("foo-bar").is_err());
+        assert_eq!(repo.load("baz").unwrap().content, "second version");
+        assert_eq!(repo.list_revisions("baz").unwrap().len(), 2);
+    });
+}
+
+#[test]
+fn test_restore_revision_after_rename() {
+    on_both(|repo| {
+        let mut snippet = get_test_snippet("Old");
+        repo.save(&snippet).unwrap();
+        snippet.content = "second version".into();
+        repo.save(&snippet).unwrap();
+        repo.rename("old", "rust/New").unwrap();
+
+        // the revision still says "Old", but is restored under the new name
+        let revisions = repo.list_revisions("rust/new").unwrap();
+        assert_eq!(repo.load_revision("rust/new", &revisions[0].id).unwrap().meta.name, "Old");
+        repo.restore_revision("rust/new", &revisions[0].id).unwrap();
+        let slugs: Vec<_> = repo.list_meta().unwrap().into_iter().map(|(slug, _)| slug).collect();
+        assert_eq!(slugs, ["rust/new"]);
+        let restored = repo.load("rust/new").unwrap();
+        assert_eq!(restored.meta.name, "New");
+        assert_eq!(restored.meta.id, snippet.meta.id);
+        assert_eq!(restored.content, "main() {\n println!(\"Hello world\"); \n}");
+    });
+}
+
+#[test]
+fn test_trash() {
+    on_both(|repo| {
+        let mut snippet = get_test_snippet("Foo Bar");
+        repo.save(&snippet).unwrap();
+        snippet.content = "second version".into();
+        repo.save(&snippet).unwrap();
+        repo.rename("foo-bar", "Baz").unwrap();
+        repo.save(&get_test_snippet("Taken")).unwrap();
+        repo.delete("taken").unwrap();
+
+        // deletions go to the trash and can be restored from there
+        repo.delete("baz").unwrap();
+        assert!(repo.load_all().unwrap().is_empty());
+        assert_eq!(repo.list_revisions("baz").unwrap().len(), 3);
+        let trash = repo.list_trash().unwrap();
+        assert_eq!(trash.iter().map(|e| e.slug.as_str()).collect::<Vec<_>>(), vec!["taken", "baz"]);
+        assert_eq!(repo.restore_trash("baz").unwrap().content, "second version");
+        assert!(repo.restore_trash("baz").is_err());
+        assert_eq!(repo.empty_trash(Some(chrono::Duration::days(1))).unwrap(), 0);
+        assert_eq!(repo.empty_trash(None).unwrap(), 1);
+        assert!(repo.list_trash().unwrap().is_empty());
+    });
+}
+
+#[test]
+fn test_multi_file_snippet() {
+    on_both(|repo| {
+        let mut multi = get_test_snippet("Multi");
+        multi.content = vec![
+            SnippetFile::new("main.rs", "fn main() {}".to_string()),
+            SnippetFile::new("Cargo.toml", "[package]".to_string()),
+        ].into();
+        repo.save(&multi).unwrap();
+        assert_eq!(repo.load("multi").unwrap().content, multi.content);
+        multi.content = Vec::<SnippetFile>::new().into();
+        assert!(matches!(repo.save(&multi), Err(NibbError::InvalidFileName(_))));
+    });
+}
+
+#[test]
+fn test_namespaces() {
+    on_both(|repo| {
+        // namespaces are part of the slug, and renaming to a path moves the snippet
+        let mut nested = get_test_snippet("Untagged Enum");
+        nested.meta.namespace = "Rust/../serde".to_string();
+        repo.save(&nested).unwrap();
+        assert_eq!(repo.load("rust/serde/untagged-enum").unwrap().meta.namespace, "rust/serde");
+        let moved = repo.rename("rust/serde/untagged-enum", "json/Untagged Enum").unwrap();
+        assert_eq!(moved.meta.get_slug(), "json/untagged-enum");
+        assert!(repo.load("rust/serde/untagged-enum").is_err());
+        assert_eq!(repo.load("json/untagged-enum").unwrap().meta.id, moved.meta.id);
+    });
+}
+
+#[test]
+fn test_snippet_cannot_hold_snippets() {
+    on_both(|repo| {
+        let nested = |namespace: &str, name: &str| {
+            let mut snippet = get_test_snippet(name);
+            snippet.meta.namespace = namespace.to_string();
+            snippet
+        };
+        repo.save(&get_test_snippet("A")).unwrap();
+        assert!(matches!(repo.save(&nested("a", "B")), Err(NibbError::InvalidSlug(_))));
+        assert!(matches!(repo.save(&nested("a/b", "C")), Err(NibbError::InvalidSlug(_))));
+        repo.save(&nested("c", "D")).unwrap();
+        assert!(matches!(repo.save(&get_test_snippet("C")), Err(NibbError::InvalidSlug(_))));
+        assert!(matches!(repo.rename("a", "c"), Err(NibbError::InvalidSlug(_))));
+        assert!(matches!(repo.rename("a", "c/d/A"), Err(NibbError::InvalidSlug(_))));
+        assert!(matches!(repo.save_all(&[nested("e", "F"), get_test_snippet("E")]), Err(NibbError::InvalidSlug(_))));
+        assert!(repo.load("e/f").is_err());
+
+        // a deleted snippet cannot come back once its slug has become a namespace
+        repo.delete("a").unwrap();
+        repo.save(&nested("a", "B")).unwrap();
+        assert!(matches!(repo.restore_trash("a"), Err(NibbError::InvalidSlug(_))));
+    });
+}
+
+#[test]
+fn test_links() {
+    on_both(|repo| {
+        let mut target = get_test_snippet("Untagged Enum");
+        target.meta.namespace = "json".to_string();
+        repo.save(&target).unwrap();
+
+        // links are checked on save, follow renames and dangle once the target is deleted
+        let mut linking = get_test_snippet("Linking");
+        linking.meta.links.push(SnippetLink::new(LinkKind::SeeAlso, "missing"));
+        assert!(matches!(repo.save(&linking), Err(NibbError::InvalidLink(_))));
+        linking.meta.links = vec![SnippetLink::new(LinkKind::SeeAlso, "json/untagged-enum")];
+        repo.save(&linking).unwrap();
+        repo.rename("json/untagged-enum", "/Untagged Enum").unwrap();
+        assert_eq!(repo.load("linking").unwrap().meta.links[0].target, "untagged-enum");
+        assert_eq!(repo.related("untagged-enum").unwrap()[0].meta.name, "Linking");
+        repo.delete("untagged-enum").unwrap();
+        assert_eq!(repo.dangling_links().unwrap()[0].slug, "linking");
+    });
+}
+
+#[test]
+fn test_save_if_unmodified() {
+    on_both(|repo| {
+        // saving over a version that was changed in the meantime is refused
+        repo.save(&get_test_snippet("Concurrent")).unwrap();
+        let loaded = repo.load("concurrent").unwrap();
+        let mut elsewhere = loaded.clone();
+        elsewhere.content = "changed elsewhere".into();
+        repo.save(&elsewhere).unwrap();
+        assert!(repo.load("concurrent").unwrap().meta.modified > loaded.meta.modified);
+        let mut mine = loaded.clone();
+        mine.content = "my change".into();
+        for expected in [ExpectedVersion::Modified(loaded.meta.modified), ExpectedVersion::Hash(loaded.version_hash())] {
+            match repo.save_if_unmodified(&mine, &expected) {
+                Err(NibbError::Conflict(conflict)) => {
+                    assert_eq!(conflict.current.unwrap().content, elsewhere.content);
+                    assert_eq!(conflict.attempted.content, mine.content);
+                }
+                other => panic!("expected a conflict, got {:?}", other),
+            }
+        }
+        let current = repo.load("concurrent").unwrap();
+        repo.save_if_unmodified(&mine, &ExpectedVersion::Hash(current.version_hash())).unwrap();
+        assert_eq!(repo.load("concurrent").unwrap().content, mine.content);
+        repo.delete("concurrent").unwrap();
+        assert!(matches!(
+            repo.save_if_unmodified(&mine, &ExpectedVersion::Modified(current.meta.modified)),
+            Err(NibbError::Conflict(conflict)) if conflict.current.is_none()
+        ));
+    });
+}
+
+#[test]
+fn test_save_all_is_all_or_nothing() {
+    on_both(|repo| {
+        let batch = [get_test_snippet("First"), get_test_snippet("Second"), get_test_snippet("!!!")];
+        assert!(matches!(repo.save_all(&batch), Err(NibbError::InvalidSlug(_))));
+        assert!(repo.load("first").is_err());
+        assert!(matches!(repo.save_all(&[get_test_snippet("First"), get_test_snippet("first")]), Err(NibbError::InvalidSlug(_))));
+        repo.save_all(&batch[..2]).unwrap();
+        assert!(repo.load("second").is_ok());
+    });
+}
+
+#[test]
+fn test_not_found() {
+    let repo = MemoryRepo::new();
+    assert!(matches!(repo.load("missing"), Err(e) if e.to_string().starts_with("Not found")));
+    assert!(matches!(repo.delete("missing"), Err(e) if e.to_string().starts_with("Not found")));
+    assert!(repo.load_revision("missing", "20250101T000000.000000000Z").is_err());
+}
+
+#[test]
+fn test_load_all_is_sorted_by_slug() {
+    let repo = MemoryRepo::new();
+    repo.save_all(&[get_test_snippet("b"), get_test_snippet("a"), get_test_snippet("c")]).unwrap();
+    let names: Vec<String> = repo.load_all().unwrap().into_iter().map(|s| s.meta.name).collect();
+    assert_eq!(names, vec!["a", "b", "c"]);
+}
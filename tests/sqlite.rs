@@ -0,0 +1,331 @@
+#![cfg(feature = "sqlite")]
+
+use nibb_core::{BinaryContent, ExpectedVersion, FSRepo, FileType, LinkKind, Meta, NibbError, Snippet, SnippetFile, SnippetLink, SnippetRepository, SqliteRepo};
+use tempfile::TempDir;
+
+fn get_test_snippet(name: &str) -> Snippet {
+    let meta = Meta::new(
+        name.to_string(),
+        "test-desc".to_string(),
+        vec!["test-tag".to_string(), "another-tag".to_string()],
+        FileType::Rust,
+        None,
+    );
+    Snippet::new(meta, "main() {\n println!(\"Hello world\"); \n}".to_string())
+}
+
+#[test]
+fn test_save_and_load() {
+    let repo = SqliteRepo::open_in_memory().unwrap();
+    let snippet = get_test_snippet("Test Snippet");
+    repo.save(&snippet).unwrap();
+
+    let loaded = repo.load("test-snippet").unwrap();
+    assert_eq!(loaded.meta.name, "Test Snippet");
+    assert_eq!(loaded.meta.language, FileType::Rust);
+    assert_eq!(loaded.meta.tags, snippet.meta.tags);
+    assert_eq!(loaded.meta.created, snippet.meta.created);
+    assert_eq!(loaded.content, snippet.content);
+}
+
+#[test]
+fn test_override_records_revision() {
+    let repo = SqliteRepo::open_in_memory().unwrap();
+    let mut snippet = get_test_snippet("Test Snippet");
+    repo.save(&snippet).unwrap();
+    snippet.meta.language = FileType::Python;
+    repo.save(&snippet).unwrap();
+
+    assert_eq!(repo.load_all().unwrap().len(), 1);
+    assert_eq!(repo.load("test-snippet").unwrap().meta.language, FileType::Python);
+    let revisions = repo.list_revisions("test-snippet").unwrap();
+    assert_eq!(revisions.len(), 1);
+    repo.restore_revision("test-snippet", &revisions[0].id).unwrap();
+    assert_eq!(repo.load("test-snippet").unwrap().meta.language, FileType::Rust);
+}
+
+#[test]
+fn test_delete() {
+    let repo = SqliteRepo::open_in_memory().unwrap();
+    let snippet = get_test_snippet("Test Snippet");
+    repo.save(&snippet).unwrap();
+    repo.delete("test-snippet").unwrap();
+    assert!(repo.load("test-snippet").is_err());
+    assert!(repo.delete("test-snippet").is_err());
+    assert_eq!(repo.list_revisions("test-snippet").unwrap().len(), 1);
+
+    assert_eq!(repo.list_trash().unwrap().len(), 1);
+    assert_eq!(repo.restore_trash("test-snippet").unwrap().meta.id, snippet.meta.id);
+    assert!(repo.list_trash().unwrap().is_empty());
+    repo.delete("test-snippet").unwrap();
+    assert_eq!(repo.empty_trash(Some(chrono::Duration::days(1))).unwrap(), 0);
+    assert_eq!(repo.empty_trash(None).unwrap(), 1);
+}
+
+#[test]
+fn test_persists_to_file() {
+    let temp_dir = TempDir::new().unwrap();
+    let db_path = temp_dir.path().join("snippets.db");
+    SqliteRepo::open(&db_path).unwrap()
+        .save_all(&[get_test_snippet("First"), get_test_snippet("Second")])
+        .unwrap();
+
+    let repo = SqliteRepo::open(&db_path).unwrap();
+    let names: Vec<String> = repo.load_all().unwrap().into_iter().map(|s| s.meta.name).collect();
+    assert_eq!(names, vec!["First", "Second"]);
+}
+
+#[test]
+fn test_import_from_fs_repo() {
+    let temp_dir = TempDir::new().unwrap();
+    let fs_repo = FSRepo::new(temp_dir.path().join(".nibb")).unwrap();
+    let mut snippet = get_test_snippet("Test Snippet");
+    fs_repo.save(&snippet).unwrap();
+    snippet.content = "second version".into();
+    fs_repo.save(&snippet).unwrap();
+    let other = get_test_snippet("Other Snippet");
+    fs_repo.save(&other).unwrap();
+    // a broken snippet or revision is reported instead of failing the import
+    fs_repo.save(&get_test_snippet("Broken")).unwrap();
+    std::fs::write(fs_repo.snippet_path("broken").join("meta.toml"), "name = ").unwrap();
+    fs_repo.save(&other).unwrap();
+    let broken_revision = &fs_repo.list_revisions("other-snippet").unwrap()[0].id;
+    let revision_dir = temp_dir.path().join(".nibb").join("history").join("other-snippet").join(broken_revision);
+    std::fs::write(revision_dir.join("meta.toml"), "name = ").unwrap();
+
+    let repo = SqliteRepo::open_in_memory().unwrap();
+    let report = repo.import_from(&fs_repo).unwrap();
+    assert_eq!(report.snippets.len(), 2);
+    let broken: Vec<&str> = report.diagnostics.iter().map(|d| d.slug.as_str()).collect();
+    assert_eq!(broken, vec!["broken", "other-snippet"]);
+    assert!(report.diagnostics[1].message.contains(broken_revision.as_str()));
+    assert!(repo.load("broken").is_err());
+    assert!(repo.list_revisions("other-snippet").unwrap().is_empty());
+    assert_eq!(repo.load("test-snippet").unwrap().content, "second version");
+    assert_eq!(repo.list_revisions("test-snippet").unwrap().len(), 1);
+    assert!(repo.load("other-snippet").is_ok());
+    assert_eq!(repo.load("other-snippet").unwrap().meta.modified, fs_repo.load("other-snippet").unwrap().meta.modified);
+}
+
+#[test]
+fn test_import_refuses_slug_of_another_snippet() {
+    let temp_dir = TempDir::new().unwrap();
+    let fs_repo = FSRepo::new(temp_dir.path().join(".nibb")).unwrap();
+    fs_repo.save(&get_test_snippet("Test Snippet")).unwrap();
+    fs_repo.save(&get_test_snippet("Other Snippet")).unwrap();
+
+    let repo = SqliteRepo::open_in_memory().unwrap();
+    let mut existing = get_test_snippet("Test Snippet");
+    existing.content = "already here".into();
+    repo.save(&existing).unwrap();
+    assert!(matches!(repo.import_from(&fs_repo), Err(NibbError::InvalidSlug(_))));
+    // nothing was imported
+    assert_eq!(repo.load("test-snippet").unwrap().content, "already here");
+    assert!(repo.load("other-snippet").is_err());
+
+    // the same snippet, by ID, is overwritten
+    repo.delete("test-snippet").unwrap();
+    repo.save(&fs_repo.load("test-snippet").unwrap()).unwrap();
+    assert_eq!(repo.import_from(&fs_repo).unwrap().snippets.len(), 2);
+}
+
+#[test]
+fn test_rename() {
+    let repo = SqliteRepo::open_in_memory().unwrap();
+    repo.save(&get_test_snippet("Test Snippet")).unwrap();
+    repo.save(&get_test_snippet("Other")).unwrap();
+    assert!(repo.rename("test-snippet", "other").is_err());
+
+    repo.rename("test-snippet", "Renamed").unwrap();
+    assert!(repo.load("test-snippet").is_err());
+    assert_eq!(repo.load("renamed").unwrap().meta.name, "Renamed");
+    assert_eq!(repo.list_revisions("renamed").unwrap().len(), 1);
+}
+
+#[test]
+fn test_restore_revision_after_rename() {
+    let repo = SqliteRepo::open_in_memory().unwrap();
+    let mut snippet = get_test_snippet("Old");
+    repo.save(&snippet).unwrap();
+    snippet.content = "second version".into();
+    repo.save(&snippet).unwrap();
+    repo.rename("old", "rust/New").unwrap();
+
+    // the revision still says "Old", but is restored under the new name
+    let revisions = repo.list_revisions("rust/new").unwrap();
+    repo.restore_revision("rust/new", &revisions[0].id).unwrap();
+    let slugs: Vec<_> = repo.list_meta().unwrap().into_iter().map(|(slug, _)| slug).collect();
+    assert_eq!(slugs, ["rust/new"]);
+    let restored = repo.load("rust/new").unwrap();
+    assert_eq!(restored.meta.name, "New");
+    assert_eq!(restored.meta.id, snippet.meta.id);
+    assert_eq!(restored.content, get_test_snippet("Old").content);
+}
+
+#[test]
+fn test_ids() {
+    let repo = SqliteRepo::open_in_memory().unwrap();
+    let snippet = get_test_snippet("Test Snippet");
+    repo.save(&snippet).unwrap();
+    assert_eq!(repo.load("test-snippet").unwrap().meta.id, snippet.meta.id);
+    assert!(matches!(repo.save(&get_test_snippet("test snippet!")), Err(NibbError::InvalidSlug(_))));
+
+    repo.rename("test-snippet", "Renamed").unwrap();
+    assert_eq!(repo.load_by_id(snippet.meta.id).unwrap().meta.name, "Renamed");
+}
+
+#[test]
+fn test_multi_file_snippet() {
+    let repo = SqliteRepo::open_in_memory().unwrap();
+    let mut snippet = get_test_snippet("Test Snippet");
+    repo.save(&snippet).unwrap();
+    snippet.content = vec![
+        SnippetFile::new("Dockerfile", "FROM alpine".to_string()),
+        SnippetFile::new("entrypoint.sh", "#!/bin/sh".to_string()),
+    ].into();
+    repo.save(&snippet).unwrap();
+
+    assert_eq!(repo.load("test-snippet").unwrap().content, snippet.content);
+    let revisions = repo.list_revisions("test-snippet").unwrap();
+    assert_eq!(repo.load_revision("test-snippet", &revisions[0].id).unwrap().content.as_text(),
+               Some("main() {\n println!(\"Hello world\"); \n}"));
+}
+
+#[test]
+fn test_binary_snippet() {
+    let repo = SqliteRepo::open_in_memory().unwrap();
+    let mut snippet = get_test_snippet("Test Snippet");
+    snippet.meta.language = FileType::Binary;
+    snippet.content = BinaryContent::new(vec![0x89, b'P', b'N', b'G', 0x0D, 0x0A, 0x1A, 0x0A]).into();
+    repo.save(&snippet).unwrap();
+    assert_eq!(repo.load("test-snippet").unwrap().content, snippet.content);
+}
+
+#[test]
+fn test_namespace() {
+    let repo = SqliteRepo::open_in_memory().unwrap();
+    let mut snippet = get_test_snippet("Untagged Enum");
+    snippet.meta.namespace = "rust/serde".to_string();
+    repo.save(&snippet).unwrap();
+    let loaded = repo.load("rust/serde/untagged-enum").unwrap();
+    assert_eq!(loaded.meta.namespace, "rust/serde");
+    assert!(loaded.meta.is_in("rust"));
+}
+
+#[test]
+fn test_snippet_cannot_hold_snippets() {
+    let repo = SqliteRepo::open_in_memory().unwrap();
+    let nested = |namespace: &str, name: &str| {
+        let mut snippet = get_test_snippet(name);
+        snippet.meta.namespace = namespace.to_string();
+        snippet
+    };
+    repo.save(&get_test_snippet("A")).unwrap();
+    assert!(matches!(repo.save(&nested("a", "B")), Err(NibbError::InvalidSlug(_))));
+    assert!(matches!(repo.save(&nested("a/b", "C")), Err(NibbError::InvalidSlug(_))));
+    repo.save(&nested("c", "D")).unwrap();
+    assert!(matches!(repo.save(&get_test_snippet("C")), Err(NibbError::InvalidSlug(_))));
+    assert!(matches!(repo.rename("a", "c"), Err(NibbError::InvalidSlug(_))));
+    assert!(matches!(repo.rename("a", "c/d/A"), Err(NibbError::InvalidSlug(_))));
+    assert!(matches!(repo.save_all(&[nested("e", "F"), get_test_snippet("E")]), Err(NibbError::InvalidSlug(_))));
+    assert!(repo.load("e/f").is_err());
+
+    // a deleted snippet cannot come back once its slug has become a namespace
+    repo.delete("a").unwrap();
+    repo.save(&nested("a", "B")).unwrap();
+    assert!(matches!(repo.restore_trash("a"), Err(NibbError::InvalidSlug(_))));
+}
+
+#[test]
+fn test_list_meta() {
+    let repo = SqliteRepo::open_in_memory().unwrap();
+    repo.save_all(&[get_test_snippet("b"), get_test_snippet("a")]).unwrap();
+    let metas = repo.list_meta().unwrap();
+    assert_eq!(metas.iter().map(|(slug, _)| slug.as_str()).collect::<Vec<_>>(), ["a", "b"]);
+    assert_eq!(metas[0].1.name, "a");
+    let names: Vec<_> = repo.iter().unwrap().map(|snippet| snippet.unwrap().meta.name).collect();
+    assert_eq!(names, ["a", "b"]);
+}
+
+#[test]
+fn test_links() {
+    let repo = SqliteRepo::open_in_memory().unwrap();
+    let mut linking = get_test_snippet("Linking");
+    linking.meta.links.push(SnippetLink::new(LinkKind::DependsOn, "helper"));
+    assert!(matches!(repo.save(&linking), Err(NibbError::InvalidLink(_))));
+    repo.save_all(&[linking, get_test_snippet("Helper")]).unwrap();
+    assert_eq!(repo.load("linking").unwrap().meta.links[0].kind, LinkKind::DependsOn);
+
+    repo.rename("helper", "util/Helper").unwrap();
+    assert_eq!(repo.load("linking").unwrap().meta.links[0].target, "util/helper");
+    assert_eq!(repo.related("util/helper").unwrap()[0].meta.name, "Linking");
+    repo.delete("util/helper").unwrap();
+    assert_eq!(repo.dangling_links().unwrap().len(), 1);
+}
+
+#[test]
+fn test_save_if_unmodified() {
+    let repo = SqliteRepo::open_in_memory().unwrap();
+    repo.save(&get_test_snippet("Concurrent")).unwrap();
+    let loaded = repo.load("concurrent").unwrap();
+    let expected = ExpectedVersion::Modified(loaded.meta.modified);
+    let mut mine = loaded.clone();
+    mine.content = "my change".into();
+    repo.save_if_unmodified(&mine, &expected).unwrap();
+    // the timestamp survives the round trip through the database
+    let saved = repo.load("concurrent").unwrap();
+    assert!(saved.meta.modified > loaded.meta.modified);
+    assert!(matches!(repo.save_if_unmodified(&mine, &expected), Err(NibbError::Conflict(_))));
+    repo.save_if_unmodified(&mine, &ExpectedVersion::Modified(saved.meta.modified)).unwrap();
+}
+
+#[test]
+fn test_rename_keeps_history_of_new_slug() {
+    let temp_dir = TempDir::new().unwrap();
+    let path = temp_dir.path().join("snippets.db");
+    let repo = SqliteRepo::open(&path).unwrap();
+    let mut target = get_test_snippet("Target");
+    repo.save(&target).unwrap();
+    target.content = "target v2".into();
+    repo.save(&target).unwrap();
+    repo.delete("target").unwrap();
+    let mut source = get_test_snippet("Source");
+    repo.save(&source).unwrap();
+    source.content = "source v2".into();
+    repo.save(&source).unwrap();
+
+    // the revision of the source takes the identifier of the first one of the target
+    let taken = repo.list_revisions("target").unwrap()[0].id.clone();
+    {
+        let conn = rusqlite::Connection::open(&path).unwrap();
+        conn.execute("UPDATE revisions SET id = ?1 WHERE slug = 'source'", [&taken]).unwrap();
+    }
+    repo.rename("source", "Target").unwrap();
+    assert!(repo.list_revisions("source").unwrap().is_empty());
+    // two of the deleted target, the one of the source and the version before the rename
+    let revisions = repo.list_revisions("target").unwrap();
+    assert_eq!(revisions.len(), 4);
+    assert_eq!(revisions[0].id, taken);
+    assert_eq!(repo.load_revision("target", &revisions[0].id).unwrap().meta.name, "Target");
+    assert_eq!(repo.load_revision("target", &revisions[1].id).unwrap().meta.name, "Source");
+}
+
+#[test]
+fn test_history_config_next_to_database() {
+    let temp_dir = TempDir::new().unwrap();
+    std::fs::write(temp_dir.path().join("config.toml"), "[history]\nmax_revisions = 1\n").unwrap();
+    let repo = SqliteRepo::open(temp_dir.path().join("snippets.db")).unwrap();
+    let mut snippet = get_test_snippet("Test Snippet");
+    for content in ["first", "second", "third"] {
+        snippet.content = content.into();
+        repo.save(&snippet).unwrap();
+    }
+    let revisions = repo.list_revisions("test-snippet").unwrap();
+    assert_eq!(revisions.len(), 1);
+    assert_eq!(repo.load_revision("test-snippet", &revisions[0].id).unwrap().content, "second");
+
+    // without a config.toml, none is created
+    let other_dir = TempDir::new().unwrap();
+    SqliteRepo::open(other_dir.path().join("snippets.db")).unwrap();
+    assert!(!other_dir.path().join("config.toml").exists());
+}
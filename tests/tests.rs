@@ -1,11 +1,15 @@
 
 
-use nibb_core::{FSRepo, FileType, Meta, Snippet};
+use nibb_core::{find_project_dir, resolve_nibb_dir, BinaryContent, ExpectedVersion, LinkDirection, LinkKind, SnippetEvent, SnippetLink, CONFIG_SCHEMA_VERSION, META_SCHEMA_VERSION, FSRepo, FsckCategory, FileType, Meta, Snippet, SnippetContent, SnippetFile, Visibility};
 use tempfile::TempDir;
 
-fn get_test_repo() -> FSRepo {
+/// Returns a repository in a fresh temporary directory.
+///
+/// The directory is removed when the returned [`TempDir`] is dropped, so keep it alive.
+fn get_test_repo() -> (TempDir, FSRepo) {
     let temp_dir = TempDir::new().unwrap();
-    FSRepo::new(temp_dir.path().join(".nibb")).unwrap()
+    let repo = FSRepo::new(temp_dir.path().join(".nibb")).unwrap();
+    (temp_dir, repo)
 }
 
 fn get_test_snippet() -> Snippet {
@@ -19,14 +23,29 @@ fn get_test_snippet() -> Snippet {
     Snippet::new(meta, "main() {\n println!(\"Hello world\"); \n}".to_string())
 }
 
+/// Copies the directory `from` with everything in it to `to`.
+fn copy_dir(from: &std::path::Path, to: &std::path::Path) {
+    std::fs::create_dir_all(to).unwrap();
+    for entry in std::fs::read_dir(from).unwrap() {
+        let path = entry.unwrap().path();
+        let target = to.join(path.file_name().unwrap());
+        match path.is_dir() {
+            true => copy_dir(&path, &target),
+            false => {
+                std::fs::copy(&path, &target).unwrap();
+            }
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
-    use nibb_core::{SnippetRepository};
+    use nibb_core::{NibbError, SnippetRepository};
     use super::*;
 
     #[test]
     fn test_new_and_delete() {
-        let repo = get_test_repo();
+        let (_temp_dir, repo) = get_test_repo();
         let snippet = get_test_snippet();
         repo.save(&snippet).unwrap();
 
@@ -44,7 +63,7 @@ mod tests {
 
     #[test]
     fn test_override() {
-        let repo = get_test_repo();
+        let (_temp_dir, repo) = get_test_repo();
         let mut snippet = get_test_snippet();
         repo.save(&snippet).unwrap();
         snippet.meta.language = FileType::Python;
@@ -56,7 +75,7 @@ mod tests {
 
     #[test]
     fn test_load() {
-        let repo = get_test_repo();
+        let (_temp_dir, repo) = get_test_repo();
         let snippet = get_test_snippet();
         repo.save(&snippet).unwrap();
         let snippet = repo.load(&snippet.meta.get_slug()).unwrap();
@@ -66,4 +85,1032 @@ mod tests {
         assert_eq!(snippet.meta.description, "test-desc");
         assert_eq!(snippet.meta.tags, vec!["test-tag, another-tag".to_string()]);
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_history_records_overwrite() {
+        let (_temp_dir, repo) = get_test_repo();
+        let mut snippet = get_test_snippet();
+        let slug = snippet.meta.get_slug();
+        repo.save(&snippet).unwrap();
+        assert!(repo.list_revisions(&slug).unwrap().is_empty());
+
+        snippet.content = "second version".into();
+        repo.save(&snippet).unwrap();
+        let revisions = repo.list_revisions(&slug).unwrap();
+        assert_eq!(revisions.len(), 1);
+        let previous = repo.load_revision(&slug, &revisions[0].id).unwrap();
+        assert_eq!(previous.content, "main() {\n println!(\"Hello world\"); \n}");
+
+        // deleting moves the snippet to the trash and records the deleted version
+        repo.delete(&slug).unwrap();
+        let revisions = repo.list_revisions(&slug).unwrap();
+        assert_eq!(revisions.len(), 2);
+        let deleted = repo.load_revision(&slug, &revisions[1].id).unwrap();
+        assert_eq!(deleted.content, SnippetContent::from("second version"));
+    }
+
+    #[test]
+    fn test_rename_keeps_history_of_new_slug() {
+        let (_temp_dir, repo) = get_test_repo();
+        let mut target = get_test_snippet();
+        target.meta.name = "Target".to_string();
+        repo.save(&target).unwrap();
+        repo.delete("target").unwrap();
+        let mut source = get_test_snippet();
+        repo.save(&source).unwrap();
+        source.content = "second version".into();
+        repo.save(&source).unwrap();
+
+        // the revision of the source takes the identifier of the one of the target
+        let taken = repo.list_revisions("target").unwrap()[0].id.clone();
+        let old = repo.list_revisions("test-snippet").unwrap()[0].id.clone();
+        let history = repo.history_path("test-snippet");
+        std::fs::rename(history.join(&old), history.join(&taken)).unwrap();
+
+        repo.rename("test-snippet", "Target").unwrap();
+        assert!(repo.list_revisions("test-snippet").unwrap().is_empty());
+        let revisions = repo.list_revisions("target").unwrap();
+        assert_eq!(revisions.len(), 3);
+        assert_eq!(repo.load_revision("target", &revisions[0].id).unwrap().meta.name, "Target");
+        assert_eq!(repo.load_revision("target", &revisions[1].id).unwrap().meta.name, "Test Snippet");
+    }
+
+    #[test]
+    fn test_restore_revision() {
+        let (_temp_dir, repo) = get_test_repo();
+        let mut snippet = get_test_snippet();
+        let slug = snippet.meta.get_slug();
+        repo.save(&snippet).unwrap();
+        snippet.meta.language = FileType::Python;
+        snippet.content = "print(\"Hello world\")".into();
+        repo.save(&snippet).unwrap();
+
+        let revision = repo.list_revisions(&slug).unwrap().remove(0);
+        repo.restore_revision(&slug, &revision.id).unwrap();
+        let restored = repo.load(&slug).unwrap();
+        assert_eq!(restored.meta.language, FileType::Rust);
+        assert_eq!(restored.content, "main() {\n println!(\"Hello world\"); \n}");
+        assert!(!repo.snippet_path(&slug).join("content.py").exists());
+        // the replaced version is kept as well
+        assert_eq!(repo.list_revisions(&slug).unwrap().len(), 2);
+    }
+
+    #[test]
+    fn test_load_revision_rejects_invalid_id() {
+        let (_temp_dir, repo) = get_test_repo();
+        let snippet = get_test_snippet();
+        repo.save(&snippet).unwrap();
+        assert!(repo.load_revision(&snippet.meta.get_slug(), "../../snippets").is_err());
+    }
+
+    #[test]
+    fn test_save_leaves_no_staging_dirs() {
+        let (_temp_dir, repo) = get_test_repo();
+        let mut snippet = get_test_snippet();
+        repo.save(&snippet).unwrap();
+        snippet.content = "second version".into();
+        repo.save(&snippet).unwrap();
+        assert_eq!(std::fs::read_dir(repo.base_dir.join("tmp")).unwrap().count(), 0);
+        assert_eq!(repo.load(&snippet.meta.get_slug()).unwrap().content, "second version");
+    }
+
+    #[test]
+    fn test_recover_interrupted_save() {
+        let (_temp_dir, repo) = get_test_repo();
+        let snippet = get_test_snippet();
+        let slug = snippet.meta.get_slug();
+        repo.save(&snippet).unwrap();
+
+        // simulate a crash after the old version was moved aside,
+        // but before the staged version was moved in
+        let tmp_dir = repo.base_dir.join("tmp");
+        std::fs::rename(repo.snippet_path(&slug), tmp_dir.join(format!("{}.abc123.old", slug))).unwrap();
+        std::fs::create_dir(tmp_dir.join(format!("{}.abc123.new", slug))).unwrap();
+
+        let repo = FSRepo::new(&repo.base_dir).unwrap();
+        assert_eq!(repo.load(&slug).unwrap().content, snippet.content);
+        assert_eq!(std::fs::read_dir(tmp_dir).unwrap().count(), 0);
+    }
+
+    #[test]
+    fn test_lock_timeout() {
+        let (_temp_dir, mut repo) = get_test_repo();
+        repo.config.lock.timeout_ms = 50;
+        let snippet = get_test_snippet();
+        repo.save(&snippet).unwrap();
+
+        // another process holding the exclusive lock
+        let lock_file = std::fs::File::create(repo.base_dir.join("nibb.lock")).unwrap();
+        lock_file.lock().unwrap();
+        assert!(matches!(repo.save(&snippet), Err(NibbError::LockTimeout(_))));
+        assert!(matches!(repo.load(&snippet.meta.get_slug()), Err(NibbError::LockTimeout(_))));
+        lock_file.unlock().unwrap();
+        assert!(repo.load(&snippet.meta.get_slug()).is_ok());
+
+        // readers do not block each other
+        lock_file.lock_shared().unwrap();
+        assert!(repo.load(&snippet.meta.get_slug()).is_ok());
+        assert!(matches!(repo.delete(&snippet.meta.get_slug()), Err(NibbError::LockTimeout(_))));
+    }
+
+    #[test]
+    fn test_index_tracks_changes() {
+        let (_temp_dir, repo) = get_test_repo();
+        let snippet = get_test_snippet();
+        let slug = snippet.meta.get_slug();
+        repo.save(&snippet).unwrap();
+        assert_eq!(repo.list_meta().unwrap().len(), 1);
+        assert!(repo.base_dir.join("index.json").exists());
+
+        // saves and deletes keep the index up to date
+        let mut other = get_test_snippet();
+        other.meta.name = "Other Snippet".to_string();
+        repo.save(&other).unwrap();
+        repo.delete(&slug).unwrap();
+        let metas = repo.list_meta().unwrap();
+        assert_eq!(metas.len(), 1);
+        assert_eq!(metas[0].0, "other-snippet");
+
+        // edits behind the repository's back are picked up
+        let meta_path = repo.snippet_path("other-snippet").join("meta.toml");
+        let meta = std::fs::read_to_string(&meta_path).unwrap()
+            .replace("test-desc", "edited by hand");
+        std::fs::write(&meta_path, meta).unwrap();
+        assert_eq!(repo.list_meta().unwrap()[0].1.description, "edited by hand");
+        assert_eq!(repo.load_all().unwrap()[0].meta.description, "edited by hand");
+    }
+
+    #[test]
+    fn test_list_meta_skips_content() {
+        let (_temp_dir, repo) = get_test_repo();
+        let snippet = get_test_snippet();
+        repo.save(&snippet).unwrap();
+        std::fs::remove_file(repo.snippet_path(&snippet.meta.get_slug()).join("content.rs")).unwrap();
+        assert_eq!(repo.list_meta().unwrap().len(), 1);
+        assert!(repo.load_all().is_err());
+    }
+
+    #[test]
+    fn test_list_meta_skips_broken_snippets() {
+        let (_temp_dir, repo) = get_test_repo();
+        for name in ["A", "Broken", "C"] {
+            let mut snippet = get_test_snippet();
+            snippet.meta.name = name.to_string();
+            repo.save(&snippet).unwrap();
+        }
+        std::fs::write(repo.snippet_path("broken").join("meta.toml"), "name = ").unwrap();
+
+        let slugs: Vec<_> = repo.list_meta().unwrap().into_iter().map(|(slug, _)| slug).collect();
+        assert_eq!(slugs, ["a", "c"]);
+        let report = repo.list_meta_lenient().unwrap();
+        assert_eq!(report.metas.len(), 2);
+        let broken: Vec<_> = report.diagnostics.iter().map(|d| d.slug.as_str()).collect();
+        assert_eq!(broken, ["broken"]);
+        let names: Vec<_> = repo.iter().unwrap().map(|snippet| snippet.unwrap().meta.name).collect();
+        assert_eq!(names, ["A", "C"]);
+    }
+
+    #[test]
+    fn test_parallel_load_is_deterministic() {
+        let (_temp_dir, repo) = get_test_repo();
+        let snippets: Vec<_> = (0..64).rev()
+            .map(|i| {
+                let mut snippet = get_test_snippet();
+                snippet.meta.name = format!("snippet {:02}", i);
+                snippet
+            })
+            .collect();
+        repo.save_all(&snippets).unwrap();
+        let names: Vec<_> = repo.load_all().unwrap().into_iter().map(|snippet| snippet.meta.name).collect();
+        let expected: Vec<_> = (0..64).map(|i| format!("snippet {:02}", i)).collect();
+        assert_eq!(names, expected);
+
+        // the error of the first broken snippet by slug is returned
+        for slug in ["snippet-50", "snippet-10", "snippet-30"] {
+            std::fs::remove_file(repo.snippet_path(slug).join("content.rs")).unwrap();
+        }
+        for _ in 0..4 {
+            assert!(repo.load_all().unwrap_err().to_string().contains("snippet-10"));
+        }
+        let report = repo.load_all_lenient().unwrap();
+        assert_eq!(report.snippets.len(), 61);
+        let broken: Vec<_> = report.diagnostics.iter().map(|d| d.slug.as_str()).collect();
+        assert_eq!(broken, ["snippet-10", "snippet-30", "snippet-50"]);
+        let issues: Vec<_> = repo.fsck(false).unwrap().issues.into_iter().map(|issue| issue.path).collect();
+        assert_eq!(issues, ["snippets/snippet-10", "snippets/snippet-30", "snippets/snippet-50"].map(std::path::PathBuf::from));
+    }
+
+    #[test]
+    fn test_iter_loads_lazily() {
+        let (_temp_dir, repo) = get_test_repo();
+        let mut snippets = Vec::new();
+        for name in ["b", "a", "c"] {
+            let mut snippet = get_test_snippet();
+            snippet.meta.name = name.to_string();
+            snippets.push(snippet);
+        }
+        repo.save_all(&snippets).unwrap();
+        // a broken snippet only fails when it is reached
+        std::fs::remove_file(repo.snippet_path("c").join("content.rs")).unwrap();
+        let mut iter = repo.iter().unwrap();
+        assert_eq!(iter.next().unwrap().unwrap().meta.name, "a");
+        assert_eq!(iter.next().unwrap().unwrap().meta.name, "b");
+        assert!(iter.next().unwrap().is_err());
+        assert!(iter.next().is_none());
+    }
+
+    #[test]
+    fn test_load_all_lenient_reports_broken_snippets() {
+        let (_temp_dir, repo) = get_test_repo();
+        let snippet = get_test_snippet();
+        repo.save(&snippet).unwrap();
+        for name in ["Broken Meta", "Missing Content"] {
+            let mut other = get_test_snippet();
+            other.meta.name = name.to_string();
+            repo.save(&other).unwrap();
+        }
+        std::fs::write(repo.snippet_path("broken-meta").join("meta.toml"), "name = ").unwrap();
+        std::fs::remove_file(repo.snippet_path("missing-content").join("content.rs")).unwrap();
+        std::fs::write(repo.base_dir.join("snippets").join("stray.txt"), "").unwrap();
+
+        assert!(repo.load_all().is_err());
+        let report = repo.load_all_lenient().unwrap();
+        assert_eq!(report.snippets.len(), 1);
+        assert_eq!(report.snippets[0].meta.name, "Test Snippet");
+        let broken: Vec<&str> = report.diagnostics.iter().map(|d| d.slug.as_str()).collect();
+        assert_eq!(broken, vec!["broken-meta", "missing-content", "stray.txt"]);
+    }
+
+    #[test]
+    fn test_stray_file_in_namespace() {
+        let (_temp_dir, repo) = get_test_repo();
+        let mut snippet = get_test_snippet();
+        snippet.meta.namespace = "rust".to_string();
+        repo.save(&snippet).unwrap();
+        std::fs::write(repo.base_dir.join("snippets/rust/.DS_Store"), "").unwrap();
+
+        // the stray file is reported on its own, the snippets of the namespace still load
+        let report = repo.load_all_lenient().unwrap();
+        assert_eq!(report.snippets.len(), 1);
+        assert_eq!(report.snippets[0].meta.get_slug(), "rust/test-snippet");
+        let broken: Vec<&str> = report.diagnostics.iter().map(|d| d.slug.as_str()).collect();
+        assert_eq!(broken, vec!["rust/.DS_Store"]);
+        assert_eq!(repo.load("rust/test-snippet").unwrap().content, snippet.content);
+        let mut nested = get_test_snippet();
+        nested.meta.namespace = "rust/test-snippet".to_string();
+        assert!(repo.save(&nested).is_err());
+        let mut sibling = get_test_snippet();
+        sibling.meta.namespace = "rust".to_string();
+        sibling.meta.name = "Sibling".to_string();
+        repo.save(&sibling).unwrap();
+        assert_eq!(repo.list_meta_lenient().unwrap().metas.len(), 2);
+    }
+
+    #[test]
+    fn test_rename() {
+        let (_temp_dir, repo) = get_test_repo();
+        let mut snippet = get_test_snippet();
+        repo.save(&snippet).unwrap();
+        snippet.content = "second version".into();
+        repo.save(&snippet).unwrap();
+
+        let renamed = repo.rename("test-snippet", "Renamed Snippet").unwrap();
+        assert_eq!(renamed.meta.name, "Renamed Snippet");
+        assert!(!repo.snippet_path("test-snippet").exists());
+        assert!(repo.load("test-snippet").is_err());
+        assert_eq!(repo.load("renamed-snippet").unwrap().content, "second version");
+        assert_eq!(repo.load_all().unwrap().len(), 1);
+        // the history moves along, including the version from right before the rename
+        assert_eq!(repo.list_revisions("renamed-snippet").unwrap().len(), 2);
+        assert!(repo.list_revisions("test-snippet").unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_rename_to_taken_slug() {
+        let (_temp_dir, repo) = get_test_repo();
+        let snippet = get_test_snippet();
+        repo.save(&snippet).unwrap();
+        let mut other = get_test_snippet();
+        other.meta.name = "Other".to_string();
+        repo.save(&other).unwrap();
+
+        assert!(matches!(repo.rename("other", "test snippet!"), Err(NibbError::InvalidSlug(_))));
+        assert_eq!(repo.load("other").unwrap().meta.name, "Other");
+        // changing only the spelling keeps the slug
+        assert_eq!(repo.rename("test-snippet", "TEST SNIPPET").unwrap().meta.name, "TEST SNIPPET");
+        assert_eq!(repo.load_all().unwrap().len(), 2);
+    }
+
+    #[test]
+    fn test_rename_stamps_modified() {
+        let (_temp_dir, repo) = get_test_repo();
+        repo.save(&get_test_snippet()).unwrap();
+        let loaded = repo.load("test-snippet").unwrap();
+
+        let renamed = repo.rename("test-snippet", "Renamed").unwrap();
+        assert!(renamed.meta.modified > loaded.meta.modified);
+        assert_eq!(repo.load("renamed").unwrap().meta.modified, renamed.meta.modified);
+        // an edit based on the version from before the rename is refused
+        let mut stale = loaded.clone();
+        stale.meta.name = "Renamed".to_string();
+        let expected = ExpectedVersion::Modified(loaded.meta.modified);
+        assert!(matches!(repo.save_if_unmodified(&stale, &expected), Err(NibbError::Conflict(_))));
+    }
+
+    #[test]
+    fn test_save_if_unmodified() {
+        let (temp_dir, repo) = get_test_repo();
+        // another process working on the same repository
+        let other = FSRepo::new(temp_dir.path().join(".nibb")).unwrap();
+        repo.save(&get_test_snippet()).unwrap();
+        let loaded = repo.load("test-snippet").unwrap();
+        let mut elsewhere = other.load("test-snippet").unwrap();
+        elsewhere.content = "changed elsewhere".into();
+        other.save(&elsewhere).unwrap();
+
+        let mut mine = loaded.clone();
+        mine.content = "my change".into();
+        let stale = [
+            ExpectedVersion::Hash(loaded.version_hash()),
+            ExpectedVersion::Modified(loaded.meta.modified),
+        ];
+        for expected in &stale {
+            match repo.save_if_unmodified(&mine, expected) {
+                Err(NibbError::Conflict(conflict)) => {
+                    assert_eq!(conflict.slug, "test-snippet");
+                    assert_eq!(conflict.current.unwrap().content, elsewhere.content);
+                    assert_eq!(conflict.attempted.content, mine.content);
+                }
+                other => panic!("expected a conflict, got {:?}", other),
+            }
+        }
+        // nothing was written, not even a revision
+        assert_eq!(repo.load("test-snippet").unwrap().content, elsewhere.content);
+        assert_eq!(repo.list_revisions("test-snippet").unwrap().len(), 1);
+
+        let current = repo.load("test-snippet").unwrap();
+        repo.save_if_unmodified(&mine, &ExpectedVersion::Modified(current.meta.modified)).unwrap();
+        assert_eq!(other.load("test-snippet").unwrap().content, mine.content);
+
+        // deleted in between
+        let current = repo.load("test-snippet").unwrap();
+        other.delete("test-snippet").unwrap();
+        match repo.save_if_unmodified(&mine, &ExpectedVersion::Hash(current.version_hash())) {
+            Err(NibbError::Conflict(conflict)) => assert!(conflict.current.is_none()),
+            other => panic!("expected a conflict, got {:?}", other),
+        }
+        assert!(repo.load("test-snippet").is_err());
+    }
+
+    #[test]
+    fn test_save_refuses_slug_collision() {
+        let (_temp_dir, repo) = get_test_repo();
+        let snippet = get_test_snippet();
+        repo.save(&snippet).unwrap();
+        let mut other = get_test_snippet();
+        other.meta.name = "test snippet!".to_string();
+
+        assert!(matches!(repo.save(&other), Err(NibbError::InvalidSlug(_))));
+        assert_eq!(repo.load("test-snippet").unwrap().meta.id, snippet.meta.id);
+        other.meta.name = "???".to_string();
+        assert!(matches!(repo.save(&other), Err(NibbError::InvalidSlug(_))));
+    }
+
+    #[test]
+    fn test_legacy_snippet_gets_stable_id() {
+        let (_temp_dir, repo) = get_test_repo();
+        let snippet = get_test_snippet();
+        repo.save(&snippet).unwrap();
+        // strip the id, like a snippet written before ids existed
+        let meta_path = repo.snippet_path("test-snippet").join("meta.toml");
+        let meta = std::fs::read_to_string(&meta_path).unwrap();
+        let legacy: String = meta.lines()
+            .filter(|line| !line.starts_with("id ="))
+            .map(|line| format!("{}\n", line))
+            .collect();
+        std::fs::write(&meta_path, legacy).unwrap();
+
+        let id = repo.load("test-snippet").unwrap().meta.id;
+        assert!(!id.is_nil());
+        assert_ne!(id, snippet.meta.id);
+        assert_eq!(repo.load("test-snippet").unwrap().meta.id, id);
+        assert_eq!(repo.load_by_id(id).unwrap().meta.name, "Test Snippet");
+        // saving the loaded snippet keeps its id
+        repo.save(&repo.load("test-snippet").unwrap()).unwrap();
+        assert!(std::fs::read_to_string(&meta_path).unwrap().contains(&id.to_string()));
+    }
+
+    #[test]
+    fn test_trash() {
+        let (_temp_dir, repo) = get_test_repo();
+        let mut snippet = get_test_snippet();
+        let slug = snippet.meta.get_slug();
+        repo.save(&snippet).unwrap();
+        repo.delete(&slug).unwrap();
+        snippet.content = "second version".into();
+        repo.save(&snippet).unwrap();
+        repo.delete(&slug).unwrap();
+
+        let entries = repo.list_trash().unwrap();
+        assert_eq!(entries.len(), 2);
+        assert!(entries[0].deleted < entries[1].deleted);
+        assert!(repo.trash_path(&slug).join(&entries[1].id).join("meta.toml").exists());
+        assert!(matches!(repo.restore_trash("missing"), Err(NibbError::NotFound(_))));
+
+        // the most recent deletion comes back first
+        assert_eq!(repo.restore_trash(&slug).unwrap().content, "second version");
+        assert_eq!(repo.load(&slug).unwrap().content, "second version");
+        assert_eq!(repo.load_all().unwrap().len(), 1);
+        assert!(matches!(repo.restore_trash(&slug), Err(NibbError::InvalidSlug(_))));
+
+        assert_eq!(repo.empty_trash(Some(chrono::Duration::days(1))).unwrap(), 0);
+        assert_eq!(repo.empty_trash(None).unwrap(), 1);
+        assert!(repo.list_trash().unwrap().is_empty());
+        assert!(!repo.trash_path(&slug).exists());
+    }
+
+    #[test]
+    fn test_multi_file_snippet() {
+        let (_temp_dir, repo) = get_test_repo();
+        let mut snippet = get_test_snippet();
+        snippet.content = vec![
+            SnippetFile::new("Dockerfile", "FROM alpine".to_string()),
+            SnippetFile::new("entrypoint.sh", "#!/bin/sh".to_string()),
+        ].into();
+        repo.save(&snippet).unwrap();
+
+        let dir = repo.snippet_path("test-snippet");
+        assert!(dir.join("files.toml").exists());
+        assert!(dir.join("files").join("entrypoint.sh").exists());
+        assert!(!dir.join("content.rs").exists());
+        let loaded = repo.load("test-snippet").unwrap();
+        assert_eq!(loaded.content, snippet.content);
+        let SnippetContent::Files(files) = &loaded.content else { panic!("expected files") };
+        assert_eq!(files[0].language, FileType::Dockerfile);
+        assert_eq!(files[1].language, FileType::Shell);
+
+        let json: Snippet = serde_json::from_str(&loaded.to_json()).unwrap();
+        assert_eq!(json.content, snippet.content);
+
+        // single-content snippets still use `content.<ext>`
+        snippet.content = "fn main() {}".into();
+        repo.save(&snippet).unwrap();
+        assert!(dir.join("content.rs").exists());
+        assert!(!dir.join("files.toml").exists());
+        assert_eq!(repo.load("test-snippet").unwrap().content, "fn main() {}");
+
+        for name in ["", "..", "a/b", "a\\b"] {
+            snippet.content = vec![SnippetFile::new(name, String::new())].into();
+            assert!(matches!(repo.save(&snippet), Err(NibbError::InvalidFileName(_))));
+        }
+        snippet.content = vec![
+            SnippetFile::new("a.sh", String::new()),
+            SnippetFile::new("a.sh", String::new()),
+        ].into();
+        assert!(matches!(repo.save(&snippet), Err(NibbError::InvalidFileName(_))));
+    }
+
+    #[test]
+    fn test_binary_snippet() {
+        let (_temp_dir, repo) = get_test_repo();
+        let mut snippet = get_test_snippet();
+        snippet.meta.language = FileType::Binary;
+        let png = vec![0x89, b'P', b'N', b'G', 0x0D, 0x0A, 0x1A, 0x0A, 0xFF, 0x00];
+        snippet.content = BinaryContent::new(png.clone()).into();
+        repo.save(&snippet).unwrap();
+
+        let loaded = repo.load("test-snippet").unwrap();
+        let SnippetContent::Binary(binary) = &loaded.content else { panic!("expected binary") };
+        assert_eq!(binary.data, png);
+        assert_eq!(binary.mime, "image/png");
+        assert!(loaded.to_string().contains("<binary data: image/png, 10 bytes>"));
+
+        let json = loaded.to_json();
+        assert!(json.contains("\"data\":\"iVBORw0KGgr/AA==\""));
+        assert_eq!(serde_json::from_str::<Snippet>(&json).unwrap().content, loaded.content);
+
+        // content that is not UTF-8 does not break loading, whatever the language
+        let mut text = get_test_snippet();
+        text.meta.name = "Not Text".to_string();
+        text.content = "placeholder".into();
+        repo.save(&text).unwrap();
+        std::fs::write(repo.snippet_path("not-text").join("content.rs"), [0xFF, 0xFE, 0x00]).unwrap();
+        let loaded = repo.load("not-text").unwrap();
+        assert_eq!(loaded.content, SnippetContent::Binary(BinaryContent::new(vec![0xFF, 0xFE, 0x00])));
+        assert_eq!(repo.load_all().unwrap().len(), 2);
+    }
+
+    #[test]
+    fn test_fsck() {
+        let (_temp_dir, repo) = get_test_repo();
+        let snippets_dir = repo.base_dir.join("snippets");
+        repo.save(&get_test_snippet()).unwrap();
+        assert!(repo.fsck(false).unwrap().issues.is_empty());
+
+        // wrong directory name, duplicate content file and a stray file
+        std::fs::rename(repo.snippet_path("test-snippet"), snippets_dir.join("Renamed")).unwrap();
+        std::fs::write(snippets_dir.join("Renamed").join("content.py"), "print()").unwrap();
+        std::fs::write(snippets_dir.join("Renamed").join("notes.txt"), "stray").unwrap();
+        // content file with the wrong extension
+        let mut other = get_test_snippet();
+        other.meta.name = "Other".to_string();
+        repo.save(&other).unwrap();
+        std::fs::rename(repo.snippet_path("other").join("content.rs"), repo.snippet_path("other").join("content.txt")).unwrap();
+        // unparsable metadata
+        std::fs::create_dir(repo.snippet_path("broken")).unwrap();
+        std::fs::write(repo.snippet_path("broken").join("meta.toml"), "not = [valid").unwrap();
+        // missing content
+        let mut empty = get_test_snippet();
+        empty.meta.name = "Empty".to_string();
+        repo.save(&empty).unwrap();
+        std::fs::remove_file(repo.snippet_path("empty").join("content.rs")).unwrap();
+        // name changed by hand, the revisions belong to the directory it is in
+        let mut edited = get_test_snippet();
+        edited.meta.name = "Hand Edited".to_string();
+        repo.save(&edited).unwrap();
+        edited.content = "second version".into();
+        repo.save(&edited).unwrap();
+        let meta_path = repo.snippet_path("hand-edited").join("meta.toml");
+        let meta = std::fs::read_to_string(&meta_path).unwrap().replace("name = \"Hand Edited\"", "name = \"Edited\"");
+        std::fs::write(&meta_path, meta).unwrap();
+        // stray files in a namespace, one of them a broken meta.toml
+        let mut nested = get_test_snippet();
+        nested.meta.namespace = "rust".to_string();
+        repo.save(&nested).unwrap();
+        std::fs::write(snippets_dir.join("rust").join("meta.toml"), "not = [valid").unwrap();
+        std::fs::write(snippets_dir.join("rust").join(".DS_Store"), "").unwrap();
+
+        let report = repo.fsck(false).unwrap();
+        let found: Vec<(FsckCategory, String)> = report.issues.iter()
+            .map(|issue| (issue.category, issue.path.to_string_lossy().replace('\\', "/")))
+            .collect();
+        assert_eq!(found, vec![
+            (FsckCategory::StrayFile, "snippets/Renamed/notes.txt".to_string()),
+            (FsckCategory::DuplicateContent, "snippets/Renamed/content.py".to_string()),
+            (FsckCategory::SlugMismatch, "snippets/Renamed".to_string()),
+            (FsckCategory::UnreadableMeta, "snippets/broken".to_string()),
+            (FsckCategory::MissingContent, "snippets/empty".to_string()),
+            (FsckCategory::SlugMismatch, "snippets/hand-edited".to_string()),
+            (FsckCategory::ExtensionMismatch, "snippets/other/content.txt".to_string()),
+            (FsckCategory::StrayFile, "snippets/rust/.DS_Store".to_string()),
+            (FsckCategory::StrayFile, "snippets/rust/meta.toml".to_string()),
+        ]);
+        assert!(!report.is_clean());
+        // checking alone changes nothing
+        assert!(snippets_dir.join("Renamed").exists());
+
+        let report = repo.fsck(true).unwrap();
+        assert_eq!(report.unrepaired().map(|issue| issue.category).collect::<Vec<_>>(), vec![FsckCategory::MissingContent]);
+        assert!(repo.snippet_path("test-snippet").join("content.rs").exists());
+        assert!(!repo.snippet_path("test-snippet").join("content.py").exists());
+        assert!(repo.snippet_path("other").join("content.rs").exists());
+        assert!(!repo.snippet_path("broken").exists());
+        let lost_found: Vec<_> = std::fs::read_dir(repo.base_dir.join("lost+found")).unwrap().collect();
+        assert_eq!(lost_found.len(), 1);
+        let moved = lost_found[0].as_ref().unwrap().path();
+        assert!(moved.join("broken").join("meta.toml").exists());
+        assert!(moved.join("Renamed").join("notes.txt").exists());
+        // the revisions moved along with the snippet
+        assert_eq!(repo.list_revisions("edited").unwrap().len(), 1);
+        assert!(repo.list_revisions("hand-edited").unwrap().is_empty());
+        // only the stray files of the namespace were moved aside
+        assert!(moved.join("rust").join("meta.toml").exists());
+        assert!(moved.join("rust").join(".DS_Store").exists());
+        assert_eq!(repo.load("rust/test-snippet").unwrap().content, nested.content);
+
+        std::fs::remove_dir_all(repo.snippet_path("empty")).unwrap();
+        assert!(repo.fsck(false).unwrap().is_clean());
+        assert_eq!(repo.load_all().unwrap().len(), 4);
+        assert_eq!(repo.list_meta().unwrap().len(), 4);
+    }
+
+    #[test]
+    fn test_migrate_meta() {
+        let (_temp_dir, repo) = get_test_repo();
+        repo.save(&get_test_snippet()).unwrap();
+        let mut other = get_test_snippet();
+        other.meta.name = "Other".to_string();
+        repo.save(&other).unwrap();
+        // a file written before versioning, without `schema_version`, `id` and `visibility`
+        let legacy = |slug: &str| {
+            let meta_path = repo.snippet_path(slug).join("meta.toml");
+            let meta = std::fs::read_to_string(&meta_path).unwrap();
+            assert!(meta.starts_with(&format!("schema_version = {}", META_SCHEMA_VERSION)));
+            let legacy: String = meta.lines()
+                .filter(|line| !["schema_version =", "id =", "visibility ="].iter().any(|key| line.starts_with(key)))
+                .map(|line| format!("{}\n", line))
+                .collect();
+            std::fs::write(&meta_path, legacy).unwrap();
+            meta_path
+        };
+
+        // upgraded in memory when loaded, the file is left alone
+        let meta_path = legacy("test-snippet");
+        let before = std::fs::read_to_string(&meta_path).unwrap();
+        let loaded = repo.load("test-snippet").unwrap();
+        assert_eq!(loaded.meta.schema_version, META_SCHEMA_VERSION);
+        assert_eq!(repo.load("test-snippet").unwrap().meta.id, loaded.meta.id);
+        assert_eq!(repo.load_all().unwrap().len(), 2);
+        assert_eq!(std::fs::read_to_string(&meta_path).unwrap(), before);
+
+        // reported by fsck, and upgraded with --repair
+        let report = repo.fsck(false).unwrap();
+        assert_eq!(report.issues.len(), 1);
+        assert_eq!(report.issues[0].category, FsckCategory::OutdatedSchema);
+        assert_eq!(std::fs::read_to_string(&meta_path).unwrap(), before);
+        assert!(repo.fsck(true).unwrap().is_clean());
+        let meta = std::fs::read_to_string(&meta_path).unwrap();
+        assert!(meta.starts_with(&format!("schema_version = {}", META_SCHEMA_VERSION)));
+        assert!(meta.contains(&format!("id = \"{}\"", loaded.meta.id)));
+        assert!(meta.contains("visibility = \"private\""));
+        assert!(repo.fsck(false).unwrap().issues.is_empty());
+
+        // or all at once
+        legacy("other");
+        assert_eq!(repo.migrate().unwrap(), 1);
+        assert_eq!(repo.migrate().unwrap(), 0);
+
+        // files of a newer version are refused
+        let meta = std::fs::read_to_string(&meta_path).unwrap();
+        std::fs::write(&meta_path, meta.replacen(&format!("schema_version = {}", META_SCHEMA_VERSION), "schema_version = 99", 1)).unwrap();
+        assert!(matches!(repo.load("test-snippet"), Err(NibbError::UnsupportedSchema(_))));
+    }
+
+    #[test]
+    fn test_migrate_config() {
+        let temp_dir = TempDir::new().unwrap();
+        let base_dir = temp_dir.path().join(".nibb");
+        std::fs::create_dir(&base_dir).unwrap();
+        // a config written before versioning, without the `[git]` section
+        std::fs::write(base_dir.join("config.toml"), "[history]\nmax_revisions = 3\n").unwrap();
+
+        let repo = FSRepo::new(&base_dir).unwrap();
+        assert_eq!(repo.config.schema_version, CONFIG_SCHEMA_VERSION);
+        assert_eq!(repo.config.history.max_revisions, 3);
+        let config = std::fs::read_to_string(base_dir.join("config.toml")).unwrap();
+        assert!(config.starts_with(&format!("schema_version = {}", CONFIG_SCHEMA_VERSION)));
+        assert!(config.contains("[git]"));
+    }
+
+    #[test]
+    fn test_encrypted_snippet() {
+        let temp_dir = TempDir::new().unwrap();
+        let base_dir = temp_dir.path().join(".nibb");
+        std::fs::create_dir(&base_dir).unwrap();
+        std::fs::write(base_dir.join("config.toml"), "[encryption]\nenabled = true\nencrypt_description = true\n").unwrap();
+        let repo = FSRepo::new(&base_dir).unwrap().with_passphrase("correct horse");
+        assert!(repo.config.encryption.salt.is_some());
+
+        let snippet = get_test_snippet();
+        repo.save(&snippet).unwrap();
+        let mut public = get_test_snippet();
+        public.meta.name = "Public".to_string();
+        public.meta.visibility = Visibility::Public;
+        public.meta.description = "public-desc".to_string();
+        repo.save(&public).unwrap();
+
+        // private content and description are not stored in plain text, public ones are
+        let content = std::fs::read(repo.snippet_path("test-snippet").join("content.rs")).unwrap();
+        assert!(content.starts_with(b"NIBBENC\x01"));
+        let meta = std::fs::read_to_string(repo.snippet_path("test-snippet").join("meta.toml")).unwrap();
+        assert!(!meta.contains("test-desc"));
+        let content = std::fs::read_to_string(repo.snippet_path("public").join("content.rs")).unwrap();
+        assert_eq!(content, snippet.content.as_text().unwrap());
+
+        let loaded = repo.load("test-snippet").unwrap();
+        assert_eq!(loaded.content, snippet.content);
+        assert_eq!(loaded.meta.description, "test-desc");
+        assert!(repo.list_meta().unwrap().iter().any(|(_, meta)| meta.description == "test-desc"));
+        let index = std::fs::read_to_string(base_dir.join("index.json")).unwrap();
+        assert!(!index.contains("test-desc"));
+
+        // without a key, the snippet is listed as locked and cannot be saved
+        let locked_repo = FSRepo::new(&base_dir).unwrap();
+        let locked = locked_repo.load("test-snippet").unwrap();
+        assert_eq!(locked.content, SnippetContent::Locked);
+        assert_eq!(locked.meta.description, "<locked>");
+        assert!(locked.to_string().contains("<locked: encrypted"));
+        assert!(matches!(locked_repo.save(&locked), Err(NibbError::Locked(_))));
+        assert!(matches!(locked_repo.save(&snippet), Err(NibbError::Locked(_))));
+        assert!(matches!(locked_repo.rename("test-snippet", "Other"), Err(NibbError::Locked(_))));
+        assert_eq!(locked_repo.load("public").unwrap().content, public.content);
+
+        let wrong_repo = FSRepo::new(&base_dir).unwrap().with_passphrase("wrong");
+        assert!(matches!(wrong_repo.load("test-snippet"), Err(NibbError::Encryption(_))));
+    }
+
+    #[test]
+    fn test_encrypted_snippet_in_clone() {
+        let temp_dir = TempDir::new().unwrap();
+        let base_dir = temp_dir.path().join(".nibb");
+        std::fs::create_dir(&base_dir).unwrap();
+        std::fs::write(base_dir.join("config.toml"), "[encryption]\nenabled = true\nencrypt_description = true\n").unwrap();
+        let repo = FSRepo::new(&base_dir).unwrap().with_passphrase("correct horse");
+        let snippet = get_test_snippet();
+        repo.save(&snippet).unwrap();
+
+        // `config.toml` is not committed, so a clone has another salt, or none at all
+        for config in ["[encryption]\nenabled = true\n", ""] {
+            let clone_dir = temp_dir.path().join(format!("clone-{}", config.len()));
+            copy_dir(&base_dir.join("snippets"), &clone_dir.join("snippets"));
+            std::fs::write(clone_dir.join("config.toml"), config).unwrap();
+            let clone = FSRepo::new(&clone_dir).unwrap().with_passphrase("correct horse");
+            assert_ne!(clone.config.encryption.salt, repo.config.encryption.salt);
+            let loaded = clone.load("test-snippet").unwrap();
+            assert_eq!(loaded.content, snippet.content);
+            assert_eq!(loaded.meta.description, "test-desc");
+        }
+    }
+
+    #[test]
+    fn test_plain_text_that_looks_encrypted() {
+        let temp_dir = TempDir::new().unwrap();
+        let base_dir = temp_dir.path().join(".nibb");
+        std::fs::create_dir(&base_dir).unwrap();
+        std::fs::write(base_dir.join("config.toml"), "[encryption]\nenabled = true\n").unwrap();
+        let repo = FSRepo::new(&base_dir).unwrap().with_passphrase("correct horse");
+        let mut snippet = get_test_snippet();
+        snippet.meta.visibility = Visibility::Public;
+        let text = format!("NIBBENC\u{1}{}", "0".repeat(64));
+        snippet.content = text.clone().into();
+        repo.save(&snippet).unwrap();
+
+        assert_eq!(repo.load("test-snippet").unwrap().content, snippet.content);
+        let locked_repo = FSRepo::new(&base_dir).unwrap();
+        assert_eq!(locked_repo.load("test-snippet").unwrap().content, snippet.content);
+        // plain text is only escaped if it starts like an encrypted file
+        snippet.content = "NIBB".into();
+        repo.save(&snippet).unwrap();
+        assert_eq!(std::fs::read(repo.snippet_path("test-snippet").join("content.rs")).unwrap(), b"NIBB");
+
+        // repositories that do not encrypt read it back alike
+        let (_plain_dir, plain_repo) = get_test_repo();
+        let sealed = format!("NIBBENC\u{1}\u{10}{}", "0".repeat(16 + 24 + 16));
+        for text in [text, sealed, "NIBBENC\0rest".to_string()] {
+            snippet.content = text.into();
+            plain_repo.save(&snippet).unwrap();
+            assert_eq!(plain_repo.load("test-snippet").unwrap().content, snippet.content);
+        }
+    }
+
+    #[test]
+    fn test_namespaces() {
+        let (_temp_dir, repo) = get_test_repo();
+        let snippets_dir = repo.base_dir.join("snippets");
+        let mut snippet = get_test_snippet();
+        snippet.meta.name = "Untagged Enum".to_string();
+        snippet.meta.namespace = "rust/serde".to_string();
+        repo.save(&snippet).unwrap();
+        repo.save(&get_test_snippet()).unwrap();
+
+        assert!(snippets_dir.join("rust/serde/untagged-enum/meta.toml").is_file());
+        let loaded = repo.load("rust/serde/untagged-enum").unwrap();
+        assert_eq!(loaded.meta.namespace, "rust/serde");
+        assert!(loaded.meta.is_in("rust") && loaded.meta.is_in("rust/serde/") && !loaded.meta.is_in("ru"));
+        let slugs: Vec<_> = repo.list_meta().unwrap().into_iter().map(|(slug, _)| slug).collect();
+        assert_eq!(slugs, ["rust/serde/untagged-enum", "test-snippet"]);
+        assert_eq!(repo.load_all().unwrap().len(), 2);
+
+        // every segment is slugified, so paths cannot escape `snippets/`
+        let mut escaping = get_test_snippet();
+        escaping.meta.namespace = "../../Outside".to_string();
+        repo.save(&escaping).unwrap();
+        assert!(snippets_dir.join("outside/test-snippet").is_dir());
+        assert!(repo.load("../outside/test-snippet").is_ok());
+
+        // a snippet cannot hold other snippets, and a namespace cannot be overwritten by one
+        let mut inside = get_test_snippet();
+        inside.meta.namespace = "test-snippet".to_string();
+        assert!(matches!(repo.save(&inside), Err(NibbError::InvalidSlug(_))));
+        let mut rust = get_test_snippet();
+        rust.meta.name = "Rust".to_string();
+        assert!(matches!(repo.save(&rust), Err(NibbError::InvalidSlug(_))));
+
+        // renaming to a path moves the snippet with its history, and removes empty namespaces
+        repo.save(&snippet).unwrap();
+        let moved = repo.rename("rust/serde/untagged-enum", "json/Untagged Enum").unwrap();
+        assert_eq!(moved.meta.namespace, "json");
+        assert!(!snippets_dir.join("rust").exists());
+        assert_eq!(repo.list_revisions("json/untagged-enum").unwrap().len(), 2);
+        let moved = repo.rename("json/untagged-enum", "/Untagged Enum").unwrap();
+        assert_eq!(moved.meta.get_slug(), "untagged-enum");
+        assert!(!snippets_dir.join("json").exists());
+
+        // deleted snippets keep their namespace in the trash
+        repo.delete("outside/test-snippet").unwrap();
+        assert!(!snippets_dir.join("outside").exists());
+        assert_eq!(repo.list_trash().unwrap()[0].slug, "outside/test-snippet");
+        assert_eq!(repo.restore_trash("outside/test-snippet").unwrap().meta.namespace, "outside");
+        assert!(repo.list_trash().unwrap().is_empty());
+        assert!(repo.fsck(false).unwrap().issues.is_empty());
+    }
+
+    #[test]
+    fn test_links() {
+        let (_temp_dir, repo) = get_test_repo();
+        let target = get_test_snippet();
+        repo.save(&target).unwrap();
+        let target_id = repo.load("test-snippet").unwrap().meta.id;
+
+        // links must point to another existing snippet
+        let mut linking = get_test_snippet();
+        linking.meta.name = "Linking".to_string();
+        linking.meta.links.push(SnippetLink::new(LinkKind::SeeAlso, "Missing"));
+        assert!(matches!(repo.save(&linking), Err(NibbError::InvalidLink(_))));
+        linking.meta.links = vec![SnippetLink::new(LinkKind::SeeAlso, "Linking")];
+        assert!(matches!(repo.save(&linking), Err(NibbError::InvalidLink(_))));
+        linking.meta.links = vec![
+            SnippetLink::new(LinkKind::SeeAlso, "Test Snippet"),
+            SnippetLink::new(LinkKind::DependsOn, &target_id.to_string()),
+        ];
+        repo.save(&linking).unwrap();
+        assert_eq!(linking.meta.links[0].target, "test-snippet");
+        assert!(linking.meta.to_string().contains("Link: see_also test-snippet"));
+
+        // a batch may link to snippets saved with it
+        let mut first = get_test_snippet();
+        first.meta.name = "First".to_string();
+        first.meta.links.push(SnippetLink::new(LinkKind::DerivedFrom, "Second"));
+        let mut second = get_test_snippet();
+        second.meta.name = "Second".to_string();
+        repo.save_all(&[first, second]).unwrap();
+
+        let related = repo.related("test-snippet").unwrap();
+        assert_eq!(related.len(), 2);
+        assert!(related.iter().all(|r| r.direction == LinkDirection::Incoming && r.meta.name == "Linking"));
+        let related = repo.related("linking").unwrap();
+        assert_eq!(related.len(), 2);
+        assert!(related.iter().all(|r| r.direction == LinkDirection::Outgoing && r.meta.id == target_id));
+
+        // renaming the target rewrites links by slug, links by ID stay valid
+        repo.rename("test-snippet", "rust/Renamed").unwrap();
+        let links = repo.load("linking").unwrap().meta.links;
+        assert_eq!(links[0].target, "rust/renamed");
+        assert_eq!(links[1].target, target_id.to_string());
+        assert_eq!(repo.related("linking").unwrap().len(), 2);
+        assert!(repo.dangling_links().unwrap().is_empty());
+
+        // deleting it leaves them dangling
+        repo.delete("rust/renamed").unwrap();
+        let dangling = repo.dangling_links().unwrap();
+        assert_eq!(dangling.len(), 2);
+        assert!(dangling.iter().all(|d| d.slug == "linking"));
+        let report = repo.fsck(false).unwrap();
+        assert_eq!(report.issues.len(), 2);
+        assert!(report.issues.iter().all(|issue| issue.category == FsckCategory::DanglingLink && !issue.repaired));
+        repo.restore_trash("rust/renamed").unwrap();
+        assert!(repo.dangling_links().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_watch() {
+        let (temp_dir, repo) = get_test_repo();
+        let watcher = repo.watch(std::time::Duration::from_millis(100)).unwrap();
+        let next = || watcher.recv_timeout(std::time::Duration::from_secs(5));
+        let event = |slug: &str| Some(SnippetEvent::Created { slug: slug.to_string() });
+
+        let mut snippet = get_test_snippet();
+        repo.save(&snippet).unwrap();
+        assert_eq!(next(), event("test-snippet"));
+        snippet.content = "second version".into();
+        repo.save(&snippet).unwrap();
+        assert_eq!(next(), Some(SnippetEvent::Modified { slug: "test-snippet".to_string() }));
+        repo.rename("test-snippet", "Renamed").unwrap();
+        assert_eq!(next(), Some(SnippetEvent::Renamed { from: "test-snippet".to_string(), to: "renamed".to_string() }));
+        repo.delete("renamed").unwrap();
+        assert_eq!(next(), Some(SnippetEvent::Deleted { slug: "renamed".to_string() }));
+        // loading changes nothing
+        repo.restore_trash("renamed").unwrap();
+        assert_eq!(next(), event("renamed"));
+        repo.load_all().unwrap();
+        assert_eq!(watcher.recv_timeout(std::time::Duration::from_millis(500)), None);
+        // a file named meta.toml does not make the files of a multi-file snippet a snippet
+        let mut multi = Snippet::new(get_test_snippet().meta, String::new());
+        multi.meta.name = "Multi".to_string();
+        multi.content = vec![SnippetFile::new("meta.toml", "id = \"x\"".to_string())].into();
+        repo.save(&multi).unwrap();
+        assert_eq!(next(), event("multi"));
+        std::fs::write(temp_dir.path().join(".nibb/snippets/multi/files/meta.toml"), "id = \"y\"").unwrap();
+        assert_eq!(next(), Some(SnippetEvent::Modified { slug: "multi".to_string() }));
+        assert_eq!(watcher.recv_timeout(std::time::Duration::from_millis(500)), None);
+        assert_eq!(
+            SnippetEvent::Renamed { from: "a".to_string(), to: "b".to_string() }.to_json(),
+            r#"{"event":"renamed","from":"a","to":"b"}"#
+        );
+    }
+
+    #[test]
+    fn test_find_project_dir() {
+        let temp_dir = TempDir::new().unwrap();
+        let project = temp_dir.path().join("project");
+        let nested = project.join("src").join("module");
+        std::fs::create_dir_all(&nested).unwrap();
+        assert_eq!(find_project_dir(&nested), None);
+
+        std::fs::create_dir_all(project.join(".nibb")).unwrap();
+        assert_eq!(find_project_dir(&nested), Some(project.join(".nibb")));
+        // the innermost repository wins
+        std::fs::create_dir_all(nested.join(".nibb")).unwrap();
+        assert_eq!(find_project_dir(&nested), Some(nested.join(".nibb")));
+        assert_eq!(find_project_dir(&project), Some(project.join(".nibb")));
+    }
+
+    #[test]
+    fn test_git_stays_in_repo_dir() {
+        let (_temp_dir, repo) = get_test_repo();
+        repo.save(&get_test_snippet()).unwrap();
+        // git is disabled by default, so no repository is created
+        assert!(!repo.base_dir.join(".git").exists());
+
+        let temp_dir = TempDir::new().unwrap();
+        let base_dir = temp_dir.path().join("custom");
+        std::fs::create_dir_all(&base_dir).unwrap();
+        std::fs::write(
+            base_dir.join("config.toml"),
+            "[git]\nenabled = true\nauto_commit = true\ncommit_message = \"update: {name}\"\nauthor = \"Test\"\nauthor_email = \"test@example.com\"\n",
+        ).unwrap();
+        let repo = FSRepo::new(&base_dir).unwrap();
+        repo.save(&get_test_snippet()).unwrap();
+
+        let git = git2::Repository::open(&base_dir).unwrap();
+        let head = git.head().unwrap().peel_to_commit().unwrap();
+        assert_eq!(head.message(), Some("update: Test Snippet"));
+        assert!(head.tree().unwrap().get_path(std::path::Path::new("snippets/test-snippet/meta.toml")).is_ok());
+    }
+
+    #[test]
+    fn test_save_all_is_all_or_nothing() {
+        let temp_dir = TempDir::new().unwrap();
+        let base_dir = temp_dir.path().join("batch");
+        std::fs::create_dir_all(&base_dir).unwrap();
+        std::fs::write(
+            base_dir.join("config.toml"),
+            "[git]\nenabled = true\nauto_commit = true\ncommit_message = \"update: {name}\"\nauthor = \"Test\"\nauthor_email = \"test@example.com\"\n",
+        ).unwrap();
+        let repo = FSRepo::new(&base_dir).unwrap();
+        let snippet = get_test_snippet();
+        repo.save(&snippet).unwrap();
+        let git = git2::Repository::open(&base_dir).unwrap();
+        let first = git.head().unwrap().peel_to_commit().unwrap().id();
+
+        let mut changed = repo.load("test-snippet").unwrap();
+        changed.content = "changed".into();
+        let mut batch = vec![changed, get_test_snippet()];
+        batch[1].meta.name = "Other".to_string();
+        // the last snippet is invalid, so none of them are written
+        let mut invalid = get_test_snippet();
+        invalid.meta.name = "!!!".to_string();
+        batch.push(invalid);
+        assert!(matches!(repo.save_all(&batch), Err(NibbError::InvalidSlug(_))));
+        assert_eq!(repo.load("test-snippet").unwrap().content, snippet.content);
+        assert!(repo.load("other").is_err());
+        assert_eq!(std::fs::read_dir(base_dir.join("tmp")).unwrap().count(), 0);
+        assert_eq!(git.head().unwrap().peel_to_commit().unwrap().id(), first);
+
+        // snippets of a batch cannot share a slug or be filed under each other
+        assert!(matches!(repo.save_all(&[batch[1].clone(), batch[1].clone()]), Err(NibbError::InvalidSlug(_))));
+        let mut nested = get_test_snippet();
+        nested.meta.namespace = "other".to_string();
+        assert!(matches!(repo.save_all(&[batch[1].clone(), nested]), Err(NibbError::InvalidSlug(_))));
+
+        batch.pop();
+        repo.save_all(&batch).unwrap();
+        assert_eq!(repo.load("test-snippet").unwrap().content, SnippetContent::from("changed"));
+        assert!(repo.load("other").is_ok());
+        assert_eq!(repo.list_revisions("test-snippet").unwrap().len(), 1);
+        let head = git.head().unwrap().peel_to_commit().unwrap();
+        assert_eq!(head.message(), Some("update: 2 snippets\n\ntest-snippet\nother"));
+        assert_eq!(head.parent_id(0).unwrap(), first);
+        assert!(head.tree().unwrap().get_path(std::path::Path::new("snippets/other/meta.toml")).is_ok());
+    }
+
+    #[test]
+    fn test_save_all_rolls_back_after_swaps() {
+        let (_temp_dir, repo) = get_test_repo();
+        let mut first = get_test_snippet();
+        first.meta.name = "First".to_string();
+        let mut second = get_test_snippet();
+        second.meta.name = "Second".to_string();
+        repo.save_all(&[first.clone(), second.clone()]).unwrap();
+        let stored = repo.list_meta().unwrap();
+
+        // the revision of the second snippet cannot be recorded, after both were swapped in
+        std::fs::write(repo.base_dir.join("history/second"), "in the way").unwrap();
+        first.content = "changed".into();
+        second.content = "changed".into();
+        assert!(matches!(repo.save_all(&[first, second]), Err(NibbError::Io(_))));
+        assert_eq!(repo.load("first").unwrap().content, get_test_snippet().content);
+        assert_eq!(repo.load("second").unwrap().content, get_test_snippet().content);
+        assert!(repo.list_revisions("first").unwrap().is_empty());
+        assert!(!repo.base_dir.join("history/first").exists());
+        assert_eq!(std::fs::read_dir(repo.base_dir.join("tmp")).unwrap().count(), 0);
+        let metas = repo.list_meta().unwrap();
+        assert_eq!(metas.iter().map(|(_, meta)| meta.modified).collect::<Vec<_>>(), stored.iter().map(|(_, meta)| meta.modified).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn test_resolve_nibb_dir_prefers_explicit_path() {
+        let temp_dir = TempDir::new().unwrap();
+        assert_eq!(resolve_nibb_dir(Some(temp_dir.path())).unwrap(), temp_dir.path());
+    }
+}